@@ -0,0 +1,107 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce
+};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+const MASTER_KEY_FILE: &str = "master.key";
+const NONCE_LEN: usize = 12;
+const KEY_READ_ATTEMPTS: usize = 20;
+
+pub type MasterKey = Key;
+
+/// Load the local master secret from the app data directory, creating it on first use.
+pub fn load_master_key(app_handle: &AppHandle) -> Result<MasterKey, String> {
+    let app_dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
+    let key_path = app_dir.join(MASTER_KEY_FILE);
+
+    if key_path.exists() {
+        return read_master_key(&key_path);
+    }
+
+    if !app_dir.exists() {
+        fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    }
+
+    // Created readable by the owner only, and only if no other caller got there first: a key
+    // replaced after secrets were encrypted with it would leave them unreadable
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = match options.open(&key_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return read_master_key(&key_path),
+        Err(e) => return Err(format!("Failed to write master key: {}", e)),
+    };
+
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    file.write_all(STANDARD.encode(key).as_bytes())
+        .map_err(|e| format!("Failed to write master key: {}", e))?;
+
+    Ok(key)
+}
+
+fn read_master_key(key_path: &Path) -> Result<MasterKey, String> {
+    // A key created at the same time by another caller may not be written yet
+    let mut encoded = String::new();
+    for _ in 0..KEY_READ_ATTEMPTS {
+        encoded = fs::read_to_string(key_path)
+            .map_err(|e| format!("Failed to read master key: {}", e))?;
+        if !encoded.trim().is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let bytes = STANDARD.decode(encoded.trim())
+        .map_err(|e| format!("Master key is corrupted: {}", e))?;
+
+    if bytes.len() != 32 {
+        return Err("Master key is corrupted: unexpected length".to_string());
+    }
+
+    Ok(*Key::from_slice(&bytes))
+}
+
+/// Encrypt a secret, returning base64 of `nonce || ciphertext`.
+pub fn encrypt(key: &MasterKey, plaintext: &str) -> Result<String, String> {
+    let cipher = ChaCha20Poly1305::new(key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| format!("Failed to encrypt secret: {}", e))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(STANDARD.encode(payload))
+}
+
+pub fn decrypt(key: &MasterKey, encoded: &str) -> Result<String, String> {
+    let payload = STANDARD.decode(encoded)
+        .map_err(|e| format!("Encrypted secret is corrupted: {}", e))?;
+
+    if payload.len() <= NONCE_LEN {
+        return Err("Encrypted secret is corrupted: too short".to_string());
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(key);
+
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt secret: wrong master key or corrupted data".to_string())?;
+
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
@@ -1 +1,2 @@
-pub mod hasher;
\ No newline at end of file
+pub mod crypto;
+pub mod utf8;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use rusqlite::{Connection, Result as SqliteResult, Transaction};
 use std::collections::HashMap;
 
-const CURRENT_DB_VERSION: i32 = 1;
+const CURRENT_DB_VERSION: i32 = 11;
 
 fn version_table_exists(tx: &Transaction) -> SqliteResult<bool> {
     let count: i32 = tx.query_row(
@@ -95,10 +95,142 @@ fn migrate_to_v1(tx: &Transaction) -> SqliteResult<()> {
     Ok(())
 }
 
+fn migrate_to_v2(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS known_hosts (
+                id INTEGER PRIMARY KEY,
+                server_id INTEGER NOT NULL UNIQUE,
+                key_type TEXT NOT NULL,
+                key TEXT NOT NULL,
+                fingerprint TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (server_id) REFERENCES servers (id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v3(tx: &Transaction) -> SqliteResult<()> {
+    // Passphrases used to be stored as argon2 hashes, which can never unlock a key
+    tx.execute(
+        "UPDATE ssh_keys SET password = NULL WHERE password LIKE '$argon2%'",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v4(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "ALTER TABLE servers ADD COLUMN auth_method TEXT NOT NULL DEFAULT 'key'",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v5(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "ALTER TABLE servers ADD COLUMN jump_host_id INTEGER REFERENCES servers (id)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v6(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS tunnels (
+                id INTEGER PRIMARY KEY,
+                server_id INTEGER NOT NULL,
+                local_port INTEGER NOT NULL,
+                remote_host TEXT NOT NULL,
+                remote_port INTEGER NOT NULL,
+                auto_restore BOOLEAN NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (server_id) REFERENCES servers (id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v7(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "ALTER TABLE servers ADD COLUMN sudo_mode TEXT NOT NULL DEFAULT 'passwordless'",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v8(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "ALTER TABLE ssh_keys ADD COLUMN certificate_path TEXT",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v9(tx: &Transaction) -> SqliteResult<()> {
+    for column in ["os_id", "os_like", "os_version", "os_name"] {
+        tx.execute(&format!("ALTER TABLE servers ADD COLUMN {} TEXT", column), [])?;
+    }
+
+    Ok(())
+}
+
+fn migrate_to_v10(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS server_facts (
+                server_id INTEGER PRIMARY KEY,
+                facts TEXT NOT NULL,
+                gathered_at TEXT NOT NULL,
+                FOREIGN KEY (server_id) REFERENCES servers (id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn migrate_to_v11(tx: &Transaction) -> SqliteResult<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS provision_progress (
+                server_id INTEGER NOT NULL,
+                plan TEXT NOT NULL,
+                step TEXT NOT NULL,
+                state TEXT NOT NULL,
+                error TEXT,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (server_id, plan, step),
+                FOREIGN KEY (server_id) REFERENCES servers (id)
+            )",
+        [],
+    )?;
+
+    Ok(())
+}
+
 fn get_migrations() -> HashMap<i32, MigrationFn> {
     let mut migrations: HashMap<i32, MigrationFn> = HashMap::new();
 
     migrations.insert(1, migrate_to_v1);
+    migrations.insert(2, migrate_to_v2);
+    migrations.insert(3, migrate_to_v3);
+    migrations.insert(4, migrate_to_v4);
+    migrations.insert(5, migrate_to_v5);
+    migrations.insert(6, migrate_to_v6);
+    migrations.insert(7, migrate_to_v7);
+    migrations.insert(8, migrate_to_v8);
+    migrations.insert(9, migrate_to_v9);
+    migrations.insert(10, migrate_to_v10);
+    migrations.insert(11, migrate_to_v11);
 
     migrations
 }
@@ -0,0 +1,168 @@
+use super::known_hosts;
+use super::model::AgentIdentity;
+use ssh2::{Agent, Session};
+
+fn connect_agent(sess: &Session) -> Result<Agent, String> {
+    #[cfg(unix)]
+    if std::env::var_os("SSH_AUTH_SOCK").is_none() {
+        return Err("SSH_AUTH_SOCK is not set. Is ssh-agent running?".to_string());
+    }
+
+    let mut agent = sess.agent().map_err(|e| format!("Failed to init ssh-agent: {}", e))?;
+    agent.connect().map_err(|e| format!("Failed to connect to ssh-agent: {}", e))?;
+    agent.list_identities().map_err(|e| format!("Failed to list ssh-agent identities: {}", e))?;
+
+    Ok(agent)
+}
+
+/// Key type from an SSH wire format public key blob, whose first field is the type name.
+fn key_type_from_blob(blob: &[u8]) -> String {
+    blob.get(..4)
+        .and_then(|len| len.try_into().ok())
+        .map(|len: [u8; 4]| u32::from_be_bytes(len) as usize)
+        .and_then(|len| blob.get(4..4 + len))
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn describe(comment: &str, blob: &[u8]) -> AgentIdentity {
+    AgentIdentity {
+        comment: comment.to_string(),
+        key_type: key_type_from_blob(blob),
+        fingerprint: known_hosts::fingerprint(blob),
+    }
+}
+
+pub fn list_identities() -> Result<Vec<AgentIdentity>, String> {
+    let sess = Session::new().map_err(|e| format!("Failed to create SSH session: {}", e))?;
+    let mut agent = connect_agent(&sess)?;
+
+    let identities = agent.identities()
+        .map_err(|e| format!("Failed to read ssh-agent identities: {}", e))?
+        .iter()
+        .map(|identity| describe(identity.comment(), identity.blob()))
+        .collect();
+
+    let _ = agent.disconnect();
+
+    Ok(identities)
+}
+
+/// Try every identity the agent offers until the server accepts one.
+pub fn authenticate(sess: &Session, username: &str) -> Result<(), String> {
+    let mut agent = connect_agent(sess)?;
+
+    let identities = agent.identities()
+        .map_err(|e| format!("Failed to read ssh-agent identities: {}", e))?;
+
+    if identities.is_empty() {
+        let _ = agent.disconnect();
+        return Err("ssh-agent has no identities loaded".to_string());
+    }
+
+    let mut last_error = String::new();
+    for identity in &identities {
+        match agent.userauth(username, identity) {
+            Ok(_) if sess.authenticated() => break,
+            Ok(_) => {}
+            Err(e) => last_error = format!("{} ({})", e, identity.comment()),
+        }
+    }
+
+    let _ = agent.disconnect();
+
+    if !sess.authenticated() {
+        return Err(format!("ssh-agent authentication failed, no identity was accepted: {}", last_error));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    fn blob(key_type: &str, key: &[u8]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        for field in [key_type.as_bytes(), key] {
+            blob.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            blob.extend_from_slice(field);
+        }
+        blob
+    }
+
+    #[test]
+    fn key_type_is_the_first_blob_field() {
+        assert_eq!(key_type_from_blob(&blob("ssh-ed25519", &[7; 32])), "ssh-ed25519");
+        assert_eq!(key_type_from_blob(&blob("ecdsa-sha2-nistp256", &[7; 65])), "ecdsa-sha2-nistp256");
+    }
+
+    #[test]
+    fn truncated_blobs_have_an_unknown_key_type() {
+        let full = blob("ssh-ed25519", &[7; 32]);
+
+        for len in [0, 3, 4, 10] {
+            assert_eq!(key_type_from_blob(&full[..len]), "unknown", "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn identities_are_described_by_their_blob() {
+        let key = blob("ssh-ed25519", &[7; 32]);
+        let identity = describe("deploy@laptop", &key);
+
+        assert_eq!(identity.comment, "deploy@laptop");
+        assert_eq!(identity.key_type, "ssh-ed25519");
+        assert_eq!(identity.fingerprint, known_hosts::fingerprint(&key));
+        assert!(identity.fingerprint.starts_with("SHA256:"));
+    }
+
+    /// Starts its own ssh-agent and points SSH_AUTH_SOCK at it for the rest of the process
+    #[test]
+    #[ignore = "needs ssh-agent, ssh-add and ssh-keygen"]
+    fn lists_identities_of_a_running_agent() {
+        let dir = std::env::temp_dir().join(format!("syndeos-test-{}-agent", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let key = dir.join("id_ed25519");
+
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "syndeos-test", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(generated.success());
+
+        let mut agent = Command::new("ssh-agent").arg("-D").arg("-a").arg(&socket)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        for _ in 0..50 {
+            if socket.exists() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+
+        std::env::set_var("SSH_AUTH_SOCK", &socket);
+        let added = Command::new("ssh-add").arg(&key).stderr(Stdio::null()).status().unwrap();
+        let identities = list_identities();
+        let fingerprint = Command::new("ssh-keygen").args(["-l", "-E", "sha256", "-f"]).arg(key.with_extension("pub")).output().unwrap();
+
+        let _ = agent.kill();
+        let _ = agent.wait();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(added.success());
+        let identities = identities.unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].comment, "syndeos-test");
+        assert_eq!(identities[0].key_type, "ssh-ed25519");
+
+        let fingerprint = String::from_utf8(fingerprint.stdout).unwrap();
+        assert_eq!(Some(identities[0].fingerprint.as_str()), fingerprint.split_whitespace().nth(1));
+    }
+}
@@ -1,85 +1,206 @@
-use super::service;
+use super::job::{self, Job};
+use super::remote::{self, RemoteCommand};
+use super::worker::{self, Access};
+use super::package::PackageManager;
+use super::provision;
+use super::service_manager::ServiceManager;
+use super::{distro, forwarding, interactive, recipe, service, sftp, supervisor, terminal};
+use std::collections::HashMap;
 use tauri::AppHandle;
-use super::model::Server;
+use super::model::{AgentIdentity, CommandError, CommandResult, ConnectionError, KnownHost, OsRelease, Package, ProvisionStep, RecipeInfo, RemoteFile, Server, ServerFacts, ServiceAction, ServiceStatus, SessionInfo, SshConfigHost, StepProgress, TerminalInfo, Tunnel, TunnelStatus};
+use crate::common::crypto;
 use crate::database::connection;
 
+// =============================================================================
+// PACKAGE MANAGEMENT COMMANDS
+// =============================================================================
+
+/// List the packages installed on a server, through its distribution's package manager
+#[tauri::command]
+pub async fn list_installed_packages(server_id: i64) -> Result<Vec<Package>, CommandError> {
+    worker::run(server_id, Access::Read, move || {
+        let (_, packages) = package_manager(server_id)?;
+
+        let output = service::query(server_id, packages.list_installed())
+            .map_err(|e| e.context("Failed to list installed packages"))?;
+
+        Ok(packages.parse_installed(&output.stdout))
+    })
+    .await
+}
+
+/// List the packages with updates available, and the versions they would update to
+#[tauri::command]
+pub async fn check_package_updates(server_id: i64) -> Result<Vec<Package>, CommandError> {
+    worker::run(server_id, Access::Read, move || {
+        let (_, packages) = package_manager(server_id)?;
+
+        let output = service::cmd_timeout(server_id, packages.check_updates().idempotent(), service::DEFAULT_TIMEOUT)
+            .map_err(|e| e.context(format!("Failed to check for {} updates", packages.name())))?;
+
+        Ok(packages.parse_updates(&output.stdout))
+    })
+    .await
+}
+
+// =============================================================================
+// SERVICE MANAGEMENT COMMANDS
+// =============================================================================
+
+/// Whether a service is running and starts on boot
+#[tauri::command]
+pub async fn get_service_status(server_id: i64, service: String) -> Result<ServiceStatus, CommandError> {
+    worker::run(server_id, Access::Read, move || {
+        remote::validate_service_name(&service)?;
+
+        ServiceManager::of(server_id)?.status(server_id, &service)
+    })
+    .await
+}
+
+/// Start, stop, restart, reload, enable or disable a service, returning its status afterwards
+#[tauri::command]
+pub async fn control_service(server_id: i64, service: String, action: ServiceAction) -> Result<ServiceStatus, CommandError> {
+    worker::run(server_id, Access::Write, move || {
+        remote::validate_service_name(&service)?;
+
+        let services = ServiceManager::of(server_id)?;
+        service::cmd(server_id, services.command(action, &service))
+            .map_err(|e| e.context(format!("Failed to {} {}", action.as_str(), service)))?;
+
+        services.status(server_id, &service)
+    })
+    .await
+}
+
 // =============================================================================
 // PHP VERSION MANAGEMENT COMMANDS
 // =============================================================================
 
-/// Install a specific PHP version with common extensions
+/// Install a specific PHP version with common extensions, streaming output as job events
 #[tauri::command]
-pub fn install_php_version(version: String) -> Result<String, String> {
-    // Check if EPEL and Remi repositories are installed
-    let epel_check = service::cmd("dnf repolist | grep epel || echo 'not found'");
-    let remi_check = service::cmd("dnf repolist | grep remi || echo 'not found'");
+pub async fn install_php_version(app_handle: AppHandle, server_id: i64, version: String, job_id: Option<String>) -> Result<String, CommandError> {
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| install_php(job, &version)).await
+}
 
-    // Install EPEL repository if not present
-    if epel_check.is_err() || epel_check.unwrap().trim() == "not found" {
-        service::cmd("sudo dnf install -y epel-release")
-            .map_err(|e| format!("Failed to install EPEL repository: {}", e))?;
-    }
+pub(super) fn install_php(job: &Job, version: &str) -> Result<String, CommandError> {
+    remote::validate_php_version(version)?;
 
-    // Install Remi repository if not present
-    if remi_check.is_err() || remi_check.unwrap().trim() == "not found" {
-        service::cmd("sudo dnf install -y https://rpms.remirepo.net/enterprise/remi-release-9.rpm")
-            .map_err(|e| format!("Failed to install Remi repository: {}", e))?;
+    let (os, packages) = package_manager(job.server_id())?;
+
+    // Add the repositories PHP versions come from, unless already set up
+    for repository in packages.php_repositories(&os) {
+        if job.probe(packages.has_repository(repository)).unwrap_or(false) {
+            continue;
+        }
+
+        job.cmd_timeout(packages.add_repository(repository, &os)?, service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context(format!("Failed to install {} repository", repository.name())))?;
     }
 
     // Enable the specific PHP version module
-    service::cmd(&format!("sudo dnf module reset php -y"))
-        .map_err(|e| format!("Failed to reset PHP module: {}", e))?;
+    if packages == PackageManager::Dnf {
+        job.cmd_timeout(reset_php_module(), service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context("Failed to reset PHP module"))?;
 
-    service::cmd(&format!("sudo dnf module enable php:remi-{} -y", version))
-        .map_err(|e| format!("Failed to enable PHP {} module: {}", version, e))?;
+        job.cmd_timeout(enable_php_module(version), service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context(format!("Failed to enable PHP {} module", version)))?;
+    }
 
     // Install PHP and common extensions
-    let extensions = vec![
-        "php", "php-fpm", "php-common", "php-mysqlnd", "php-xml", "php-curl",
-        "php-gd", "php-imagick", "php-cli", "php-devel", "php-imap",
-        "php-mbstring", "php-opcache", "php-soap", "php-zip", "php-intl"
-    ];
-
-    let install_cmd = format!("sudo dnf install -y {}", extensions.join(" "));
-    service::cmd(&install_cmd)
-        .map_err(|e| format!("Failed to install PHP {}: {}", version, e))?;
+    job.cmd_timeout(packages.install(packages.php_packages(version)), service::INSTALL_TIMEOUT)
+        .map_err(|e| e.context(format!("Failed to install PHP {}", version)))?;
 
     // Verify installation
-    service::cmd("php -v")
-        .map_err(|e| format!("PHP {} installation verification failed: {}", version, e))
+    let output = job.query(RemoteCommand::new(&packages.php_binary(version)).arg("-v"))
+        .map_err(|e| e.context(format!("PHP {} installation verification failed", version)))?;
+
+    // zypper installs whichever PHP the distribution ships, so check it is the one asked for
+    if !output.stdout.starts_with(&format!("PHP {}.", version)) {
+        return Err(format!("{} provides {}, not PHP {}", os.name, output.stdout.lines().next().unwrap_or("another PHP version"), version).into());
+    }
+
+    Ok(output.stdout)
+}
+
+fn reset_php_module() -> RemoteCommand {
+    RemoteCommand::sudo("dnf").args(["module", "reset", "php", "-y"])
+}
+
+fn enable_php_module(version: &str) -> RemoteCommand {
+    RemoteCommand::sudo("dnf").args(["module", "enable", &format!("php:remi-{}", version), "-y"])
+}
+
+/// The distribution of a server and the package manager to use on it
+fn package_manager(server_id: i64) -> Result<(OsRelease, PackageManager), CommandError> {
+    let os = distro::of(server_id)?;
+    let packages = PackageManager::for_os(&os)?;
+
+    Ok((os, packages))
 }
 
 /// Remove a specific PHP version and its extensions
 #[tauri::command]
-pub fn remove_php_version(version: String) -> Result<String, String> {
+pub async fn remove_php_version(server_id: i64, version: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || remove_php(server_id, version)).await
+}
+
+fn remove_php(server_id: i64, version: String) -> Result<String, CommandError> {
+    remote::validate_php_version(&version)?;
+
+    let (_, packages) = package_manager(server_id)?;
+
     // List installed PHP packages
-    let list_cmd = "rpm -qa | grep php";
-    let packages_output = service::cmd(list_cmd)
-        .map_err(|e| format!("Failed to list PHP packages: {}", e))?;
+    let installed = service::query(server_id, packages.list_installed())
+        .map_err(|e| e.context("Failed to list PHP packages"))?;
+
+    let php_packages: Vec<String> = packages
+        .parse_installed(&installed.stdout)
+        .into_iter()
+        .filter(|package| packages.is_php_package(&package.name, &version))
+        .map(|package| package.name)
+        .collect();
 
-    if packages_output.trim().is_empty() {
-        return Err(format!("No PHP packages are installed"));
+    if php_packages.is_empty() {
+        return Err(format!("No PHP {} packages are installed", version).into());
     }
 
     // Remove all PHP packages
-    service::cmd("sudo dnf remove -y php php-*")
-        .map_err(|e| format!("Failed to remove PHP packages: {}", e))?;
+    service::cmd_timeout(server_id, packages.remove(&php_packages), service::INSTALL_TIMEOUT)
+        .map_err(|e| e.context("Failed to remove PHP packages"))?;
 
     // Reset PHP module to allow installation of different version
-    service::cmd("sudo dnf module reset php -y")
-        .map_err(|e| format!("Failed to reset PHP module: {}", e))?;
+    if packages == PackageManager::Dnf {
+        service::cmd_timeout(server_id, reset_php_module(), service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context("Failed to reset PHP module"))?;
+    }
 
     Ok(format!("PHP {} successfully removed", version))
 }
 
 /// List all installed PHP versions
 #[tauri::command]
-pub fn list_php_versions() -> Result<Vec<String>, String> {
-    // Check available PHP modules
-    let output = service::cmd("dnf module list php | grep php | awk '{print $2}' | grep -E '^remi-[0-9]+\\.[0-9]+$' | sed 's/remi-//'")
-        .map_err(|e| format!("Failed to list PHP versions: {}", e))?;
+pub async fn list_php_versions(server_id: i64) -> Result<Vec<String>, CommandError> {
+    worker::run(server_id, Access::Read, move || php_versions(server_id)).await
+}
+
+fn php_versions(server_id: i64) -> Result<Vec<String>, CommandError> {
+    let (_, packages) = package_manager(server_id)?;
+
+    let output = match packages {
+        // Check available PHP modules
+        PackageManager::Dnf => service::query(server_id, "dnf module list php | grep php | awk '{print $2}' | grep -E '^remi-[0-9]+\\.[0-9]+$' | sed 's/remi-//'"),
+        // Versions installed side by side, from package names such as php8.3-cli
+        PackageManager::Apt => service::query(server_id, packages.list_installed()).map(|result| CommandResult {
+            stdout: apt_php_versions(&packages.parse_installed(&result.stdout)).join("\n"),
+            ..result
+        }),
+        PackageManager::Zypper => service::query(server_id, "command -v php >/dev/null && php -r 'echo PHP_MAJOR_VERSION, \".\", PHP_MINOR_VERSION;' || true"),
+    }
+    .map_err(|e| e.context("Failed to list PHP versions"))?;
 
     let versions: Vec<String> = output
+        .stdout
         .trim()
         .split('\n')
         .filter(|v| !v.is_empty())
@@ -89,26 +210,66 @@ pub fn list_php_versions() -> Result<Vec<String>, String> {
     Ok(versions)
 }
 
+fn apt_php_versions(installed: &[Package]) -> Vec<String> {
+    let mut versions: Vec<String> = installed
+        .iter()
+        .filter_map(|package| {
+            let version = package.name.strip_prefix("php")?.split('-').next()?;
+            remote::validate_php_version(version).ok().map(|_| version.to_string())
+        })
+        .collect();
+
+    versions.sort_unstable();
+    versions.dedup();
+    versions
+}
+
 /// Set the default PHP version system-wide
 #[tauri::command]
-pub fn set_default_php_version(version: String) -> Result<String, String> {
-    // Reset current PHP module
-    service::cmd("sudo dnf module reset php -y")
-        .map_err(|e| format!("Failed to reset PHP module: {}", e))?;
+pub async fn set_default_php_version(server_id: i64, version: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || set_default_php(server_id, version)).await
+}
+
+fn set_default_php(server_id: i64, version: String) -> Result<String, CommandError> {
+    remote::validate_php_version(&version)?;
 
-    // Enable the specified PHP version module
-    service::cmd(&format!("sudo dnf module enable php:remi-{} -y", version))
-        .map_err(|e| format!("Failed to enable PHP {} module: {}", version, e))?;
+    let (os, packages) = package_manager(server_id)?;
 
-    // Install/update PHP to the new version
-    service::cmd("sudo dnf install -y php")
-        .map_err(|e| format!("Failed to install PHP {}: {}", version, e))?;
+    match packages {
+        PackageManager::Dnf => {
+            // Reset current PHP module
+            service::cmd_timeout(server_id, reset_php_module(), service::INSTALL_TIMEOUT)
+                .map_err(|e| e.context("Failed to reset PHP module"))?;
+
+            // Enable the specified PHP version module
+            service::cmd_timeout(server_id, enable_php_module(&version), service::INSTALL_TIMEOUT)
+                .map_err(|e| e.context(format!("Failed to enable PHP {} module", version)))?;
+
+            // Install/update PHP to the new version
+            service::cmd_timeout(server_id, packages.install(["php"]), service::INSTALL_TIMEOUT)
+                .map_err(|e| e.context(format!("Failed to install PHP {}", version)))?;
+        }
+        // Point the `php` alternative at the version, which must be installed
+        PackageManager::Apt => {
+            let binary = format!("/usr/bin/{}", packages.php_binary(&version));
+            service::cmd(server_id, RemoteCommand::sudo("update-alternatives").args(["--set", "php", &binary]))
+                .map_err(|e| e.context(format!("Failed to switch to PHP {}", version)))?;
+        }
+        PackageManager::Zypper => {
+            return Err(format!("{} ships a single PHP version, which can't be switched", os.name).into());
+        }
+    }
 
     // Verify the change
-    let verify_output = service::cmd("php -v")
-        .map_err(|e| format!("Failed to verify PHP version change: {}", e))?;
+    let verify_output = service::query(server_id, "php -v")
+        .map_err(|e| e.context("Failed to verify PHP version change"))?;
+
+    Ok(format!("PHP {} set as default. Current version: {}", version, verify_output.stdout.lines().next().unwrap_or("")))
+}
 
-    Ok(format!("PHP {} set as default. Current version: {}", version, verify_output.lines().next().unwrap_or("")))
+/// Start watching pooled sessions, emitting `connection-state` events and reconnecting lost ones.
+pub fn init_connection_supervisor(app_handle: AppHandle) {
+    supervisor::init(app_handle);
 }
 
 #[tauri::command]
@@ -125,6 +286,17 @@ pub fn get_servers(app_handle: AppHandle) -> Result<Vec<Server>, String> {
     service::get_servers(&conn)
 }
 
+/// Gather the facts of a connected server again, as happens on connect, and store them
+#[tauri::command]
+pub async fn refresh_server_facts(app_handle: AppHandle, server_id: i64) -> Result<ServerFacts, CommandError> {
+    worker::run(server_id, Access::Read, move || {
+        let conn = connection::get(&app_handle)?;
+
+        service::refresh_server_facts(&conn, server_id)
+    })
+    .await
+}
+
 #[tauri::command]
 pub fn add_server(app_handle: AppHandle, server: Server) -> Result<Server, String> {
     let conn = connection::get(&app_handle)?;
@@ -146,99 +318,448 @@ pub fn delete_server(app_handle: AppHandle, id: i64) -> Result<(), String> {
     service::delete_server(&conn, id)
 }
 
+/// Run a command that may wait on the user answering an `auth-prompt` on a blocking thread,
+/// keeping the main thread free to deliver the answer.
+async fn blocking<T, E, F>(f: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<String> + Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| E::from(format!("Command failed to run: {}", e)))?
+}
+
 #[tauri::command]
-pub fn try_connect_to_server(app_handle: AppHandle, id: i64) -> Result<bool, String> {
-    let conn = connection::get(&app_handle)?;
+pub async fn try_connect_to_server(app_handle: AppHandle, id: i64) -> Result<bool, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, id)?;
+
+        match service::try_connect_to_server(&conn, &master_key, &server, Some(&app_handle)) {
+            Ok(_) => {
+                service::restore_tunnels(&app_handle, &conn, &master_key, &server, None);
+                Ok(true)
+            },
+            Err(e) => Err(e)
+        }
+    }).await
+}
+
+/// Connect with an encrypted SSH key using a passphrase entered by the user
+#[tauri::command]
+pub async fn connect_with_key_passphrase(app_handle: AppHandle, id: i64, passphrase: String, remember: bool) -> Result<bool, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, id)?;
+
+        match service::try_connect_with_key_passphrase(&conn, &master_key, &server, &passphrase, remember, Some(&app_handle)) {
+            Ok(_) => {
+                service::restore_tunnels(&app_handle, &conn, &master_key, &server, None);
+                Ok(true)
+            },
+            Err(e) => Err(e)
+        }
+    }).await
+}
 
-    let server = service::get_server(&conn, id)?;
+#[tauri::command]
+pub async fn connect_with_password(app_handle: AppHandle, id: i64, password: String) -> Result<bool, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, id)?;
+
+        match service::try_connect_with_password(&conn, &master_key, &server, &password, Some(&app_handle)) {
+            Ok(_) => {
+                service::restore_tunnels(&app_handle, &conn, &master_key, &server, Some(&password));
+                Ok(true)
+            },
+            Err(e) => Err(e)
+        }
+    }).await
+}
 
-    match service::try_connect_to_server(&conn, &server) {
+/// Provide the sudo password of a server whose sudo mode is `password`. It is kept in memory
+/// until the server is disconnected.
+#[tauri::command]
+pub async fn set_sudo_password(server_id: i64, password: String) -> Result<(), CommandError> {
+    worker::run(server_id, Access::Read, move || service::set_sudo_password(server_id, &password)).await
+}
+
+/// Answer a keyboard-interactive `auth-prompt` with one response per prompt, or cancel it with null
+#[tauri::command]
+pub fn respond_auth_prompt(request_id: String, responses: Option<Vec<String>>) -> Result<(), String> {
+    interactive::respond(&request_id, responses)
+}
+
+#[tauri::command]
+pub fn disconnect_from_server(server_id: i64) -> Result<bool, String> {
+    match service::disconnect_from_server(server_id) {
         Ok(_) => Ok(true),
         Err(e) => Err(e)
     }
 }
 
+/// List the identities offered by the running ssh-agent
 #[tauri::command]
-pub fn connect_with_password(app_handle: AppHandle, id: i64, password: String) -> Result<bool, String> {
+pub fn list_agent_identities() -> Result<Vec<AgentIdentity>, String> {
+    service::list_agent_identities()
+}
+
+/// Trust the new host key of a server after a host key mismatch
+#[tauri::command]
+pub async fn accept_host_key(app_handle: AppHandle, server_id: i64, fingerprint: String) -> Result<KnownHost, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, server_id)?;
+
+        service::accept_host_key(&conn, &master_key, &server, &fingerprint)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn get_known_host(app_handle: AppHandle, server_id: i64) -> Result<Option<KnownHost>, String> {
     let conn = connection::get(&app_handle)?;
 
-    let server = service::get_server(&conn, id)?;
+    service::get_known_host(&conn, server_id)
+}
 
-    match service::connect_with_password(&server, &password) {
-        Ok(_) => Ok(true),
-        Err(e) => Err(e)
-    }
+/// Forget the stored host key so the next connection trusts whatever key is presented
+#[tauri::command]
+pub fn forget_host_key(app_handle: AppHandle, server_id: i64) -> Result<(), String> {
+    let conn = connection::get(&app_handle)?;
+
+    service::forget_host_key(&conn, server_id)
 }
 
+/// Import host keys for known servers from ~/.ssh/known_hosts
 #[tauri::command]
-pub fn disconnect_from_server() -> Result<bool, String> {
-    match service::disconnect_from_server() {
-        Ok(_) => Ok(true),
-        Err(e) => Err(e)
-    }
+pub fn import_known_hosts(app_handle: AppHandle) -> Result<Vec<KnownHost>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    service::import_known_hosts(&conn)
+}
+
+/// Hosts from ~/.ssh/config that can be imported, previewed as servers
+#[tauri::command]
+pub fn preview_ssh_config(app_handle: AppHandle) -> Result<Vec<SshConfigHost>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    service::preview_ssh_config(&conn)
+}
+
+/// Import the hosts with the given aliases from ~/.ssh/config as servers
+#[tauri::command]
+pub fn import_ssh_config(app_handle: AppHandle, aliases: Vec<String>) -> Result<Vec<Server>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    service::import_ssh_config(&conn, &aliases)
+}
+
+/// Disconnect every open server session
+#[tauri::command]
+pub fn disconnect_all_servers() -> Result<Vec<i64>, String> {
+    service::disconnect_all_servers()
+}
+
+/// List the currently open server sessions
+#[tauri::command]
+pub fn list_sessions() -> Result<Vec<SessionInfo>, String> {
+    service::list_sessions()
+}
+
+/// Close sessions that have been idle for longer than the given number of seconds
+#[tauri::command]
+pub fn evict_idle_sessions(max_idle_seconds: u64) -> Result<Vec<i64>, String> {
+    service::evict_idle_sessions(max_idle_seconds)
+}
+
+// =============================================================================
+// TERMINAL COMMANDS
+// =============================================================================
+
+/// Open an interactive shell. Output arrives as `terminal-output` events tagged with the returned id.
+#[tauri::command]
+pub async fn open_terminal(app_handle: AppHandle, server_id: i64, cols: u32, rows: u32, password: Option<String>) -> Result<TerminalInfo, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, server_id)?;
+
+        service::open_terminal(app_handle, &conn, &master_key, &server, cols, rows, password)
+    }).await
+}
+
+#[tauri::command]
+pub fn write_terminal(terminal_id: String, data: String) -> Result<(), String> {
+    terminal::write(&terminal_id, &data)
+}
+
+#[tauri::command]
+pub fn resize_terminal(terminal_id: String, cols: u32, rows: u32) -> Result<(), String> {
+    terminal::resize(&terminal_id, cols, rows)
+}
+
+/// Attach to a running terminal, returning its recent output to redraw from.
+/// Detaching only means no longer listening to its events; the shell keeps running.
+#[tauri::command]
+pub fn attach_terminal(terminal_id: String) -> Result<String, String> {
+    terminal::scrollback(&terminal_id)
+}
+
+#[tauri::command]
+pub fn close_terminal(terminal_id: String) -> Result<(), String> {
+    terminal::close(&terminal_id)
+}
+
+#[tauri::command]
+pub fn list_terminals(server_id: Option<i64>) -> Result<Vec<TerminalInfo>, String> {
+    terminal::list(server_id)
+}
+
+// =============================================================================
+// PORT FORWARDING COMMANDS
+// =============================================================================
+
+/// Forward a local port to `remote_host:remote_port` as seen from the server, e.g. `127.0.0.1:3306`
+#[tauri::command]
+pub async fn create_tunnel(app_handle: AppHandle, server_id: i64, local_port: u16, remote_host: String, remote_port: u16, auto_restore: bool, password: Option<String>) -> Result<TunnelStatus, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let server = service::get_server(&conn, server_id)?;
+
+        let tunnel = Tunnel {
+            id: None,
+            server_id,
+            local_port,
+            remote_host,
+            remote_port,
+            auto_restore,
+            created_at: None,
+        };
+
+        service::create_tunnel(app_handle, &conn, &master_key, &server, tunnel, password.as_deref())
+    }).await
+}
+
+/// Start a stopped tunnel again
+#[tauri::command]
+pub async fn start_tunnel(app_handle: AppHandle, tunnel_id: i64, password: Option<String>) -> Result<TunnelStatus, ConnectionError> {
+    blocking(move || {
+        let conn = connection::get(&app_handle)?;
+        let master_key = crypto::load_master_key(&app_handle)?;
+
+        let tunnel = forwarding::get_tunnel(&conn, tunnel_id)?;
+        let server = service::get_server(&conn, tunnel.server_id)?;
+
+        service::start_tunnel(app_handle, &conn, &master_key, &server, tunnel, password.as_deref(), true)
+    }).await
+}
+
+/// Every saved tunnel, optionally only those of one server, with live connection counts
+#[tauri::command]
+pub fn list_tunnels(app_handle: AppHandle, server_id: Option<i64>) -> Result<Vec<TunnelStatus>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    Ok(forwarding::get_tunnels(&conn, server_id)?
+        .into_iter()
+        .map(forwarding::status)
+        .collect())
+}
+
+/// Stop a tunnel but keep it, so it can be started again or restored on reconnect
+#[tauri::command]
+pub fn close_tunnel(tunnel_id: i64) -> Result<bool, String> {
+    forwarding::stop(tunnel_id)
+}
+
+#[tauri::command]
+pub fn delete_tunnel(app_handle: AppHandle, tunnel_id: i64) -> Result<(), String> {
+    let conn = connection::get(&app_handle)?;
+
+    forwarding::delete_tunnel(&conn, tunnel_id)
+}
+
+// =============================================================================
+// FILE MANAGER COMMANDS
+// =============================================================================
+
+#[tauri::command]
+pub async fn list_remote_dir(server_id: i64, path: String) -> Result<Vec<RemoteFile>, String> {
+    worker::run(server_id, Access::Read, move || sftp::list_dir(server_id, &path)).await
+}
+
+#[tauri::command]
+pub async fn rename_remote_path(server_id: i64, from: String, to: String) -> Result<(), String> {
+    worker::run(server_id, Access::Write, move || sftp::rename(server_id, &from, &to)).await
+}
+
+#[tauri::command]
+pub async fn delete_remote_path(server_id: i64, path: String, recursive: bool) -> Result<(), String> {
+    worker::run(server_id, Access::Write, move || sftp::delete(server_id, &path, recursive)).await
+}
+
+#[tauri::command]
+pub async fn create_remote_dir(server_id: i64, path: String, mode: Option<u32>) -> Result<(), String> {
+    worker::run(server_id, Access::Write, move || sftp::mkdir(server_id, &path, mode)).await
+}
+
+#[tauri::command]
+pub async fn chmod_remote_path(server_id: i64, path: String, mode: u32) -> Result<(), String> {
+    worker::run(server_id, Access::Write, move || sftp::chmod(server_id, &path, mode)).await
+}
+
+/// Read a small text file for editing in the app
+#[tauri::command]
+pub async fn read_remote_file(server_id: i64, path: String) -> Result<String, String> {
+    worker::run(server_id, Access::Read, move || sftp::read_text(server_id, &path)).await
+}
+
+#[tauri::command]
+pub async fn write_remote_file(server_id: i64, path: String, content: String) -> Result<(), String> {
+    worker::run(server_id, Access::Write, move || sftp::write_text(server_id, &path, &content)).await
+}
+
+/// Upload a file as a job, reporting `transfer-progress` events. Pass `resume` to continue a partial upload.
+#[tauri::command]
+pub async fn upload_file(app_handle: AppHandle, server_id: i64, local_path: String, remote_path: String, resume: bool, job_id: Option<String>) -> Result<String, String> {
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| sftp::upload(job, &local_path, &remote_path, resume)).await
+}
+
+/// Download a file as a job, reporting `transfer-progress` events. Pass `resume` to continue a partial download.
+#[tauri::command]
+pub async fn download_file(app_handle: AppHandle, server_id: i64, remote_path: String, local_path: String, resume: bool, job_id: Option<String>) -> Result<String, String> {
+    job::run(app_handle, server_id, Access::Read, job_id, move |job| sftp::download(job, &remote_path, &local_path, resume)).await
+}
+
+/// Cancel a running job, interrupting its current command. Returns false if it already finished.
+#[tauri::command]
+pub fn cancel_job(job_id: String) -> Result<bool, String> {
+    job::cancel(&job_id)
 }
 
 // =============================================================================
 // NODE.JS VERSION MANAGEMENT COMMANDS
 // =============================================================================
 
-/// Install a specific Node.js version via NVM
+/// Install a specific Node.js version via NVM, streaming output as job events
 #[tauri::command]
-pub fn install_node_version(version: String) -> Result<String, String> {
+pub async fn install_node_version(app_handle: AppHandle, server_id: i64, version: String, job_id: Option<String>) -> Result<String, CommandError> {
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| install_node(job, &version)).await
+}
+
+/// Loads nvm into a non-interactive shell, where it isn't sourced from .bashrc
+const LOAD_NVM: &str = "export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\"";
+
+const NVM_INSTALL_SCRIPT: &str = "curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.39.0/install.sh | bash";
+
+/// Run nvm with the given arguments
+fn nvm<I, S>(args: I) -> RemoteCommand
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    RemoteCommand::shell(LOAD_NVM).and(RemoteCommand::new("nvm").args(args))
+}
+
+/// Succeeds if nvm is installed. It is a shell function, so it has to be loaded to be found.
+fn has_nvm() -> RemoteCommand {
+    RemoteCommand::shell(LOAD_NVM).and(RemoteCommand::new("command").args(["-v", "nvm"]))
+}
+
+fn install_curl(job: &Job) -> Result<(), CommandError> {
+    if job.probe("command -v curl").unwrap_or(false) {
+        return Ok(());
+    }
+
+    let (_, packages) = package_manager(job.server_id())?;
+    job.cmd_timeout(packages.install(["curl"]), service::INSTALL_TIMEOUT)
+        .map_err(|e| e.context("Failed to install curl"))?;
+
+    Ok(())
+}
+
+fn install_node(job: &Job, version: &str) -> Result<String, CommandError> {
+    remote::validate_node_version(version)?;
+
     // Check if NVM is installed
-    let nvm_check = service::cmd("command -v nvm || echo 'not found'");
+    let has_nvm = job.probe(has_nvm()).unwrap_or(false);
+
+    if !has_nvm {
+        // The install script is fetched with curl, which minimal images may lack
+        install_curl(job)?;
 
-    if nvm_check.is_err() || nvm_check.unwrap().trim() == "not found" {
         // Install NVM
-        service::cmd("curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.39.0/install.sh | bash")
-            .map_err(|e| format!("Failed to install NVM: {}", e))?;
+        job.cmd_timeout(NVM_INSTALL_SCRIPT, service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context("Failed to install NVM"))?;
 
         // Source NVM for current session
-        service::cmd("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\"")
-            .map_err(|e| format!("Failed to source NVM: {}", e))?;
+        job.cmd(LOAD_NVM)
+            .map_err(|e| e.context("Failed to source NVM"))?;
     }
 
     // Install the specified Node.js version
-    let install_cmd = format!("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm install {}", version);
-    service::cmd(&install_cmd)
-        .map_err(|e| format!("Failed to install Node.js {}: {}", version, e))?;
+    job.cmd_timeout(nvm(["install", version]), service::INSTALL_TIMEOUT)
+        .map_err(|e| e.context(format!("Failed to install Node.js {}", version)))?;
 
     // Verify installation
-    let verify_cmd = format!("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm use {} && node --version", version);
-    service::cmd(&verify_cmd)
-        .map_err(|e| format!("Node.js {} installation verification failed: {}", version, e))
+    let verify_cmd = nvm(["use", version]).and(RemoteCommand::new("node").arg("--version"));
+    job.query(verify_cmd)
+        .map(|result| result.stdout)
+        .map_err(|e| e.context(format!("Node.js {} installation verification failed", version)))
 }
 
 /// Remove a specific Node.js version
 #[tauri::command]
-pub fn remove_node_version(version: String) -> Result<String, String> {
+pub async fn remove_node_version(server_id: i64, version: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || remove_node(server_id, version)).await
+}
+
+fn remove_node(server_id: i64, version: String) -> Result<String, CommandError> {
+    remote::validate_node_version(&version)?;
+
     // Check if NVM is installed
-    let nvm_check = service::cmd("command -v nvm || echo 'not found'");
-    if nvm_check.is_err() || nvm_check.unwrap().trim() == "not found" {
-        return Err("NVM is not installed".to_string());
+    if !service::probe(server_id, has_nvm()).unwrap_or(false) {
+        return Err("NVM is not installed".into());
     }
 
     // Remove the specified Node.js version
-    let remove_cmd = format!("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm uninstall {}", version);
-    service::cmd(&remove_cmd)
-        .map_err(|e| format!("Failed to remove Node.js {}: {}", version, e))?;
+    service::cmd_timeout(server_id, nvm(["uninstall", &version]), service::INSTALL_TIMEOUT)
+        .map_err(|e| e.context(format!("Failed to remove Node.js {}", version)))?;
 
     Ok(format!("Node.js {} successfully removed", version))
 }
 
 /// List all installed Node.js versions
 #[tauri::command]
-pub fn list_node_versions() -> Result<Vec<String>, String> {
+pub async fn list_node_versions(server_id: i64) -> Result<Vec<String>, CommandError> {
+    worker::run(server_id, Access::Read, move || node_versions(server_id)).await
+}
+
+fn node_versions(server_id: i64) -> Result<Vec<String>, CommandError> {
     // Check if NVM is installed
-    let nvm_check = service::cmd("command -v nvm || echo 'not found'");
-    if nvm_check.is_err() || nvm_check.unwrap().trim() == "not found" {
+    if !service::probe(server_id, has_nvm()).unwrap_or(false) {
         return Ok(vec![]);
     }
 
-    let output = service::cmd("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm list --no-colors")
-        .map_err(|e| format!("Failed to list Node.js versions: {}", e))?;
+    let output = service::query(server_id, nvm(["list", "--no-colors"]))
+        .map_err(|e| e.context("Failed to list Node.js versions"))?;
 
     let versions: Vec<String> = output
+        .stdout
         .lines()
         .filter_map(|line| {
             let trimmed = line.trim();
@@ -255,189 +776,152 @@ pub fn list_node_versions() -> Result<Vec<String>, String> {
 
 /// Set the default Node.js version
 #[tauri::command]
-pub fn set_default_node_version(version: String) -> Result<String, String> {
+pub async fn set_default_node_version(server_id: i64, version: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || set_default_node(server_id, version)).await
+}
+
+fn set_default_node(server_id: i64, version: String) -> Result<String, CommandError> {
+    remote::validate_node_version(&version)?;
+
     // Check if NVM is installed
-    let nvm_check = service::cmd("command -v nvm || echo 'not found'");
-    if nvm_check.is_err() || nvm_check.unwrap().trim() == "not found" {
-        return Err("NVM is not installed".to_string());
+    if !service::probe(server_id, has_nvm()).unwrap_or(false) {
+        return Err("NVM is not installed".into());
     }
 
     // Set the default version
-    let set_cmd = format!("export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm alias default {}", version);
-    service::cmd(&set_cmd)
-        .map_err(|e| format!("Failed to set Node.js {} as default: {}", version, e))?;
+    service::cmd(server_id, nvm(["alias", "default", &version]))
+        .map_err(|e| e.context(format!("Failed to set Node.js {} as default", version)))?;
 
     // Verify the change
-    let verify_cmd = "export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && node --version";
-    let verify_output = service::cmd(verify_cmd)
-        .map_err(|e| format!("Failed to verify Node.js version change: {}", e))?;
+    let verify_cmd = RemoteCommand::shell(LOAD_NVM).and(RemoteCommand::new("node").arg("--version"));
+    let verify_output = service::query(server_id, verify_cmd)
+        .map_err(|e| e.context("Failed to verify Node.js version change"))?;
 
-    Ok(format!("Node.js {} set as default. Current version: {}", version, verify_output.trim()))
+    Ok(format!("Node.js {} set as default. Current version: {}", version, verify_output.stdout.trim()))
 }
 
 // =============================================================================
 // APPLICATION MANAGEMENT COMMANDS
 // =============================================================================
 
-/// Create a new application with dedicated user and runtime versions
-#[tauri::command]
-pub fn create_application(app_name: String, username: String, php_version: Option<String>, node_version: Option<String>) -> Result<String, String> {
-    let php_ver = php_version.unwrap_or_else(|| "8.4".to_string());
-    let node_ver = node_version.unwrap_or_else(|| "lts".to_string());
+/// Reload a service through the server's service manager
+fn reload_service(server_id: i64, name: &str) -> Result<CommandResult, CommandError> {
+    let services = ServiceManager::of(server_id)?;
+
+    service::cmd(server_id, services.command(ServiceAction::Reload, name))
+}
 
-    // Check if user exists, create if not
-    let user_exists = service::cmd(&format!("id -u {} &>/dev/null && echo 'exists' || echo 'not exists'", username));
-    if user_exists.is_err() || user_exists.unwrap().trim() != "exists" {
-        // Create the user with home directory
-        service::cmd(&format!("sudo useradd -m -s /bin/bash {}", username))
-            .map_err(|e| format!("Failed to create user {}: {}", username, e))?;
+/// Where an application's PHP-FPM pool is and the service running it. apt keeps a pool
+/// directory per PHP version, so the version is the one whose directory holds the pool,
+/// enabled or not.
+struct PhpPool {
+    dir: String,
+    service: String,
+}
 
-        // Add user to nginx group for web permissions
-        service::cmd(&format!("sudo usermod -aG nginx {}", username))
-            .map_err(|e| format!("Failed to add user to nginx group: {}", e))?;
+impl PhpPool {
+    fn config(&self, app_name: &str) -> String {
+        format!("{}/{}.conf", self.dir, app_name)
     }
+}
 
-    // Create application directory in user's home
-    let app_root = format!("/home/{}/app", username);
-    service::cmd(&format!("sudo mkdir -p {}", app_root))
-        .map_err(|e| format!("Failed to create application directory: {}", e))?;
-
-    // Set proper ownership for application directory
-    service::cmd(&format!("sudo chown -R {}:nginx {}", username, app_root))
-        .map_err(|e| format!("Failed to set application directory ownership: {}", e))?;
-
-    // Create application-specific log directory
-    let log_dir = format!("/var/log/nginx/{}", app_name);
-    service::cmd(&format!("sudo mkdir -p {}", log_dir))
-        .map_err(|e| format!("Failed to create log directory: {}", e))?;
-
-    service::cmd(&format!("sudo chown -R {}:nginx {}", username, log_dir))
-        .map_err(|e| format!("Failed to set log directory ownership: {}", e))?;
-
-    // Create a basic index.html file
-    let index_content = format!("<html><head><title>{}</title></head><body><h1>Welcome to {}</h1><p>Your application has been successfully created!</p><p>User: {}</p><p>PHP Version: {}</p><p>Node Version: {}</p></body></html>",
-        app_name, app_name, username, php_ver, node_ver);
-    service::cmd(&format!("echo '{}' | sudo tee {}/index.html", index_content, app_root))
-        .map_err(|e| format!("Failed to create index.html: {}", e))?;
-
-    // Install NVM for the user if not already installed
-    let nvm_check = service::cmd(&format!("sudo -u {} bash -c 'command -v nvm || echo \"not found\"'", username));
-    if nvm_check.is_err() || nvm_check.unwrap().trim() == "not found" {
-        // Try to install NVM via DNF first (Alma Linux package)
-        let dnf_nvm = service::cmd("sudo dnf install -y nvm");
-        if dnf_nvm.is_err() {
-            // Fallback to curl installation if DNF package not available
-            service::cmd(&format!("sudo -u {} bash -c 'curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.39.0/install.sh | bash'", username))
-                .map_err(|e| format!("Failed to install NVM for user {}: {}", username, e))?;
+fn php_pool(server_id: i64, app_name: &str) -> Result<PhpPool, CommandError> {
+    let (_, packages) = package_manager(server_id)?;
+
+    let versions = match packages {
+        PackageManager::Apt | PackageManager::Zypper => php_versions(server_id)?,
+        PackageManager::Dnf => Vec::new(),
+    };
+
+    let mut version = versions.first().cloned().unwrap_or_default();
+    for candidate in &versions {
+        let config = format!("{}/{}.conf", packages.php_fpm_pool_dir(candidate), app_name);
+        let disabled = format!("{}.disabled", config);
+
+        if service::probe(server_id, RemoteCommand::new("test").args(["-e", &config, "-o", "-e", &disabled]))? {
+            version = candidate.clone();
+            break;
         }
     }
 
-    // Install specified Node.js version for the user
-    let node_install_cmd = format!("sudo -u {} bash -c 'export NVM_DIR=\"$HOME/.nvm\" && [ -s \"$NVM_DIR/nvm.sh\" ] && . \"$NVM_DIR/nvm.sh\" && nvm install {} && nvm use {}'",
-        username, node_ver, node_ver);
-    service::cmd(&node_install_cmd)
-        .map_err(|e| format!("Failed to install Node.js {} for user {}: {}", node_ver, username, e))?;
-
-    // Create PHP-FPM pool configuration for the application
-    let pool_config = format!(r#"[{}]
-user = {}
-group = nginx
-listen = /run/php-fpm/{}.sock
-listen.owner = {}
-listen.group = nginx
-listen.mode = 0660
-pm = dynamic
-pm.max_children = 5
-pm.start_servers = 2
-pm.min_spare_servers = 1
-pm.max_spare_servers = 3
-php_admin_value[error_log] = /var/log/nginx/{}/php_errors.log
-php_admin_flag[log_errors] = on
-"#, app_name, username, app_name, username, app_name);
-
-    service::cmd(&format!("echo '{}' | sudo tee /etc/php-fpm.d/{}.conf", pool_config, app_name))
-        .map_err(|e| format!("Failed to create PHP-FPM pool configuration: {}", e))?;
-
-    // Create Nginx configuration
-    let nginx_config = format!(r#"server {{
-    listen 80;
-    listen [::]:80;
-
-    root {};
-    index index.php index.html index.htm;
-
-    server_name {};
-
-    access_log /var/log/nginx/{}/access.log;
-    error_log /var/log/nginx/{}/error.log;
-
-    location / {{
-        try_files $uri $uri/ /index.php$is_args$args;
-    }}
-
-    location ~ \.php$ {{
-        fastcgi_pass unix:/run/php-fpm/{}.sock;
-        fastcgi_index index.php;
-        fastcgi_param SCRIPT_FILENAME $document_root$fastcgi_script_name;
-        include fastcgi_params;
-    }}
-
-    location ~ /\.ht {{
-        deny all;
-    }}
-}}"#, app_root, app_name, app_name, app_name, app_name);
-
-    // Write Nginx configuration file
-    service::cmd(&format!("echo '{}' | sudo tee /etc/nginx/conf.d/{}.conf", nginx_config, app_name))
-        .map_err(|e| format!("Failed to create Nginx configuration: {}", e))?;
+    Ok(PhpPool {
+        dir: packages.php_fpm_pool_dir(&version),
+        service: packages.php_fpm_service(&version),
+    })
+}
 
-    // Test Nginx configuration
-    let test_result = service::cmd("sudo nginx -t");
-    if test_result.is_err() {
-        // If the test fails, remove the configuration to prevent Nginx from failing to start
-        let _ = service::cmd(&format!("sudo rm -f /etc/nginx/conf.d/{}.conf", app_name));
-        let _ = service::cmd(&format!("sudo rm -f /etc/php-fpm.d/{}.conf", app_name));
-        return Err(format!("Nginx configuration test failed: {}", test_result.err().unwrap()));
-    }
+fn user_exists(server_id: i64, username: &str) -> Result<bool, CommandError> {
+    service::probe(server_id, RemoteCommand::new("id").arg("-u").arg(username))
+}
 
-    // Reload PHP-FPM to load the new pool
-    service::cmd("sudo systemctl reload php-fpm")
-        .map_err(|e| format!("Failed to reload PHP-FPM: {}", e))?;
+/// Create a new application with dedicated user and runtime versions, as the `application`
+/// recipe describes, streaming output as job events
+#[tauri::command]
+pub async fn create_application(app_handle: AppHandle, server_id: i64, app_name: String, username: String, php_version: Option<String>, node_version: Option<String>, job_id: Option<String>) -> Result<String, CommandError> {
+    let handle = app_handle.clone();
 
-    // Reload Nginx
-    service::cmd("sudo systemctl reload nginx")
-        .map_err(|e| format!("Failed to reload Nginx: {}", e))?;
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| create_app(&handle, job, app_name, username, php_version, node_version)).await
+}
+
+fn create_app(app_handle: &AppHandle, job: &Job, app_name: String, username: String, php_version: Option<String>, node_version: Option<String>) -> Result<String, CommandError> {
+    remote::validate_app_name(&app_name)?;
+    remote::validate_username(&username)?;
+
+    let mut vars = HashMap::from([
+        ("app_name".to_string(), app_name.clone()),
+        ("username".to_string(), username.clone()),
+    ]);
+
+    // Versions not chosen are the recipe's defaults
+    if let Some(version) = php_version {
+        remote::validate_php_version(&version)?;
+        vars.insert("php_version".to_string(), version);
+    }
+    if let Some(version) = node_version {
+        remote::validate_node_version(&version)?;
+        vars.insert("node_version".to_string(), version);
+    }
+
+    let (vars, _) = run_recipe_job(app_handle, job, "application", &format!("application:{}", app_name), &vars, &[])?;
+    let var = |name: &str| vars.get(name).map(String::as_str).unwrap_or("default");
 
     Ok(format!("Application {} successfully created for user {} at {} (PHP: {}, Node: {})",
-        app_name, username, app_root, php_ver, node_ver))
+        app_name, username, var("app_root"), var("php_version"), var("node_version")))
 }
 
 /// Remove an existing application
 #[tauri::command]
-pub fn remove_application(app_name: String) -> Result<String, String> {
+pub async fn remove_application(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || remove_app(server_id, app_name)).await
+}
+
+fn remove_app(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    remote::validate_app_name(&app_name)?;
+
     // Remove Nginx configuration
-    service::cmd(&format!("sudo rm -f /etc/nginx/conf.d/{}.conf", app_name))
-        .map_err(|e| format!("Failed to remove Nginx configuration: {}", e))?;
+    service::cmd(server_id, RemoteCommand::sudo("rm").args(["-f", &format!("/etc/nginx/conf.d/{}.conf", app_name)]))
+        .map_err(|e| e.context("Failed to remove Nginx configuration"))?;
 
     // Remove PHP-FPM pool configuration
-    service::cmd(&format!("sudo rm -f /etc/php-fpm.d/{}.conf", app_name))
-        .map_err(|e| format!("Failed to remove PHP-FPM pool configuration: {}", e))?;
+    let pool = php_pool(server_id, &app_name)?;
+    service::cmd(server_id, RemoteCommand::sudo("rm").args(["-f", &pool.config(&app_name)]))
+        .map_err(|e| e.context("Failed to remove PHP-FPM pool configuration"))?;
 
     // Remove application-specific log directory
-    service::cmd(&format!("sudo rm -rf /var/log/nginx/{}", app_name))
-        .map_err(|e| format!("Failed to remove log directory: {}", e))?;
+    service::cmd(server_id, RemoteCommand::sudo("rm").args(["-rf", &format!("/var/log/nginx/{}", app_name)]))
+        .map_err(|e| e.context("Failed to remove log directory"))?;
 
     // Test Nginx configuration
-    service::cmd("sudo nginx -t")
-        .map_err(|e| format!("Nginx configuration test failed after removal: {}", e))?;
+    service::query(server_id, RemoteCommand::sudo("nginx").arg("-t"))
+        .map_err(|e| e.context("Nginx configuration test failed after removal"))?;
 
     // Reload PHP-FPM
-    service::cmd("sudo systemctl reload php-fpm")
-        .map_err(|e| format!("Failed to reload PHP-FPM: {}", e))?;
+    reload_service(server_id, &pool.service)
+        .map_err(|e| e.context("Failed to reload PHP-FPM"))?;
 
     // Reload Nginx
-    service::cmd("sudo systemctl reload nginx")
-        .map_err(|e| format!("Failed to reload Nginx: {}", e))?;
+    reload_service(server_id, "nginx")
+        .map_err(|e| e.context("Failed to reload Nginx"))?;
 
     // Note: Application directory and user are preserved for safety
     // They can be manually removed if needed
@@ -445,35 +929,55 @@ pub fn remove_application(app_name: String) -> Result<String, String> {
     Ok(format!("Application {} successfully removed", app_name))
 }
 
+/// Field `field` of the lines matching `pattern` in a config file, without the trailing `;`
+/// of nginx directives
+fn grep_directive(path: &str, pattern: &str, field: u32) -> RemoteCommand {
+    RemoteCommand::new("grep").arg(pattern).arg(path)
+        .pipe(RemoteCommand::new("awk").arg(format!("{{print ${}}}", field)))
+        .pipe(RemoteCommand::new("sed").arg("s/;//"))
+}
+
 /// List all configured applications with details
 #[tauri::command]
-pub fn list_applications() -> Result<Vec<serde_json::Value>, String> {
-    let output = service::cmd("ls /etc/nginx/conf.d/*.conf 2>/dev/null | xargs -I {} basename {} .conf || echo ''")
-        .map_err(|e| format!("Failed to list applications: {}", e))?;
+pub async fn list_applications(server_id: i64) -> Result<Vec<serde_json::Value>, CommandError> {
+    worker::run(server_id, Access::Read, move || applications(server_id)).await
+}
+
+fn applications(server_id: i64) -> Result<Vec<serde_json::Value>, CommandError> {
+    let output = service::query(server_id, "ls /etc/nginx/conf.d/*.conf 2>/dev/null | xargs -I {} basename {} .conf || echo ''")
+        .map_err(|e| e.context("Failed to list applications"))?;
 
+    // Config files not created by us may have names that are unsafe to use in commands
     let app_names: Vec<&str> = output
+        .stdout
         .trim()
         .split('\n')
-        .filter(|w| !w.is_empty())
+        .filter(|w| !w.is_empty() && remote::validate_app_name(w).is_ok())
         .collect();
 
     let mut applications = Vec::new();
 
     for app_name in app_names {
+        let nginx_config = format!("/etc/nginx/conf.d/{}.conf", app_name);
+        let php_pool = php_pool(server_id, app_name)?.config(app_name);
+
         // Extract server_name from Nginx config
-        let server_name_cmd = format!("grep 'server_name' /etc/nginx/conf.d/{}.conf | awk '{{print $2}}' | sed 's/;//'", app_name);
-        let server_name = service::cmd(&server_name_cmd).unwrap_or_else(|_| app_name.to_string());
+        let server_name = service::cmd(server_id, grep_directive(&nginx_config, "server_name", 2))
+            .map(|result| result.stdout)
+            .unwrap_or_else(|_| app_name.to_string());
 
         // Extract user from PHP-FPM pool config
-        let user_cmd = format!("grep '^user = ' /etc/php-fpm.d/{}.conf | awk '{{print $3}}' || echo 'unknown'", app_name);
-        let user = service::cmd(&user_cmd).unwrap_or_else(|_| "unknown".to_string());
+        let user = service::cmd(server_id, grep_directive(&php_pool, "^user = ", 3))
+            .map(|result| result.stdout)
+            .unwrap_or_else(|_| "unknown".to_string());
 
         // Get application root path
-        let root_cmd = format!("grep 'root' /etc/nginx/conf.d/{}.conf | awk '{{print $2}}' | sed 's/;//'", app_name);
-        let app_root = service::cmd(&root_cmd).unwrap_or_else(|_| format!("/home/{}/app", user.trim()));
+        let app_root = service::cmd(server_id, grep_directive(&nginx_config, "root", 2))
+            .map(|result| result.stdout)
+            .unwrap_or_else(|_| format!("/home/{}/app", user.trim()));
 
         // Check if application is enabled (config file exists)
-        let enabled = std::path::Path::new(&format!("/etc/nginx/conf.d/{}.conf", app_name)).exists();
+        let enabled = service::probe(server_id, RemoteCommand::new("test").args(["-f", &nginx_config]))?;
 
         let app_info = serde_json::json!({
             "name": app_name,
@@ -482,8 +986,8 @@ pub fn list_applications() -> Result<Vec<serde_json::Value>, String> {
             "root_path": app_root.trim(),
             "enabled": enabled,
             "log_directory": format!("/var/log/nginx/{}", app_name),
-            "php_pool": format!("/etc/php-fpm.d/{}.conf", app_name),
-            "nginx_config": format!("/etc/nginx/conf.d/{}.conf", app_name)
+            "php_pool": php_pool,
+            "nginx_config": nginx_config
         });
 
         applications.push(app_info);
@@ -494,61 +998,78 @@ pub fn list_applications() -> Result<Vec<serde_json::Value>, String> {
 
 /// Enable an application
 #[tauri::command]
-pub fn enable_application(app_name: String) -> Result<String, String> {
+pub async fn enable_application(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || enable_app(server_id, app_name)).await
+}
+
+fn enable_app(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    remote::validate_app_name(&app_name)?;
+
     // Check if application configuration exists
     let nginx_config_path = format!("/etc/nginx/conf.d/{}.conf", app_name);
-    let php_pool_path = format!("/etc/php-fpm.d/{}.conf", app_name);
+    let pool = php_pool(server_id, &app_name)?;
+    let php_pool_path = pool.config(&app_name);
 
     // Check if configurations exist
-    service::cmd(&format!("test -f {}", nginx_config_path))
-        .map_err(|_| format!("Application Nginx configuration for {} does not exist", app_name))?;
+    if !service::probe(server_id, RemoteCommand::new("test").args(["-f", &nginx_config_path]))? {
+        return Err(format!("Application Nginx configuration for {} does not exist", app_name).into());
+    }
 
-    service::cmd(&format!("test -f {}", php_pool_path))
-        .map_err(|_| format!("Application PHP-FPM pool for {} does not exist", app_name))?;
+    if !service::probe(server_id, RemoteCommand::new("test").args(["-f", &php_pool_path]))? {
+        return Err(format!("Application PHP-FPM pool for {} does not exist", app_name).into());
+    }
 
     // Test Nginx configuration
-    service::cmd("sudo nginx -t")
-        .map_err(|e| format!("Nginx configuration test failed: {}", e))?;
+    service::query(server_id, RemoteCommand::sudo("nginx").arg("-t"))
+        .map_err(|e| e.context("Nginx configuration test failed"))?;
 
     // Reload PHP-FPM to ensure pool is active
-    service::cmd("sudo systemctl reload php-fpm")
-        .map_err(|e| format!("Failed to reload PHP-FPM: {}", e))?;
+    reload_service(server_id, &pool.service)
+        .map_err(|e| e.context("Failed to reload PHP-FPM"))?;
 
     // Reload Nginx
-    service::cmd("sudo systemctl reload nginx")
-        .map_err(|e| format!("Failed to reload Nginx: {}", e))?;
+    reload_service(server_id, "nginx")
+        .map_err(|e| e.context("Failed to reload Nginx"))?;
 
     Ok(format!("Application {} successfully enabled", app_name))
 }
 
 /// Disable an application
 #[tauri::command]
-pub fn disable_application(app_name: String) -> Result<String, String> {
+pub async fn disable_application(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || disable_app(server_id, app_name)).await
+}
+
+fn disable_app(server_id: i64, app_name: String) -> Result<String, CommandError> {
+    remote::validate_app_name(&app_name)?;
+
     // Move Nginx configuration to disabled state (rename with .disabled extension)
     let nginx_config = format!("/etc/nginx/conf.d/{}.conf", app_name);
     let nginx_disabled = format!("/etc/nginx/conf.d/{}.conf.disabled", app_name);
 
-    service::cmd(&format!("sudo mv {} {} 2>/dev/null || true", nginx_config, nginx_disabled))
-        .map_err(|e| format!("Failed to disable Nginx configuration for {}: {}", app_name, e))?;
+    // A configuration that is already gone is not an error
+    service::exit_ok(service::cmd(server_id, RemoteCommand::sudo("mv").args([&nginx_config, &nginx_disabled])))
+        .map_err(|e| e.context(format!("Failed to disable Nginx configuration for {}", app_name)))?;
 
     // Move PHP-FPM pool configuration to disabled state
-    let php_pool = format!("/etc/php-fpm.d/{}.conf", app_name);
-    let php_disabled = format!("/etc/php-fpm.d/{}.conf.disabled", app_name);
+    let pool = php_pool(server_id, &app_name)?;
+    let php_pool = pool.config(&app_name);
+    let php_disabled = format!("{}.disabled", php_pool);
 
-    service::cmd(&format!("sudo mv {} {} 2>/dev/null || true", php_pool, php_disabled))
-        .map_err(|e| format!("Failed to disable PHP-FPM pool for {}: {}", app_name, e))?;
+    service::exit_ok(service::cmd(server_id, RemoteCommand::sudo("mv").args([&php_pool, &php_disabled])))
+        .map_err(|e| e.context(format!("Failed to disable PHP-FPM pool for {}", app_name)))?;
 
     // Test Nginx configuration
-    service::cmd("sudo nginx -t")
-        .map_err(|e| format!("Nginx configuration test failed: {}", e))?;
+    service::query(server_id, RemoteCommand::sudo("nginx").arg("-t"))
+        .map_err(|e| e.context("Nginx configuration test failed"))?;
 
     // Reload PHP-FPM
-    service::cmd("sudo systemctl reload php-fpm")
-        .map_err(|e| format!("Failed to reload PHP-FPM: {}", e))?;
+    reload_service(server_id, &pool.service)
+        .map_err(|e| e.context("Failed to reload PHP-FPM"))?;
 
     // Reload Nginx
-    service::cmd("sudo systemctl reload nginx")
-        .map_err(|e| format!("Failed to reload Nginx: {}", e))?;
+    reload_service(server_id, "nginx")
+        .map_err(|e| e.context("Failed to reload Nginx"))?;
 
     Ok(format!("Application {} successfully disabled", app_name))
 }
@@ -557,32 +1078,47 @@ pub fn disable_application(app_name: String) -> Result<String, String> {
 // USER MANAGEMENT COMMANDS
 // =============================================================================
 
+/// Set a user's password through chpasswd's stdin, so it never appears in a command line
+fn set_password(server_id: i64, username: &str, password: &str) -> Result<CommandResult, CommandError> {
+    if password.contains(['\n', '\r']) {
+        return Err("Passwords can't contain line breaks".into());
+    }
+
+    service::cmd(server_id, RemoteCommand::sudo("chpasswd").stdin(format!("{}:{}\n", username, password)))
+}
+
 /// Create a new system user
 #[tauri::command]
-pub fn create_user(username: String, password: String, sudo_access: bool) -> Result<String, String> {
-    // Check if user already exists
-    let user_exists = service::cmd(&format!("id -u {} &>/dev/null && echo 'exists' || echo 'not exists'", username));
+pub async fn create_user(server_id: i64, username: String, password: String, sudo_access: bool) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || add_user(server_id, username, password, sudo_access)).await
+}
+
+fn add_user(server_id: i64, username: String, password: String, sudo_access: bool) -> Result<String, CommandError> {
+    remote::validate_username(&username)?;
 
-    if user_exists.is_ok() && user_exists.unwrap().trim() == "exists" {
-        return Err(format!("User {} already exists", username));
+    let os = distro::of(server_id)?;
+
+    // Check if user already exists
+    if user_exists(server_id, &username)? {
+        return Err(format!("User {} already exists", username).into());
     }
 
     // Create the user with home directory
-    service::cmd(&format!("sudo useradd -m -s /bin/bash {}", username))
-        .map_err(|e| format!("Failed to create user {}: {}", username, e))?;
+    service::cmd(server_id, RemoteCommand::sudo("useradd").args(["-m", "-s", "/bin/bash", &username]))
+        .map_err(|e| e.context(format!("Failed to create user {}", username)))?;
 
     // Set the user password
-    service::cmd(&format!("echo '{}:{}' | sudo chpasswd", username, password))
-        .map_err(|e| format!("Failed to set password for user {}: {}", username, e))?;
+    set_password(server_id, &username, &password)
+        .map_err(|e| e.context(format!("Failed to set password for user {}", username)))?;
 
     // Add user to nginx group for web permissions
-    service::cmd(&format!("sudo usermod -aG nginx {}", username))
-        .map_err(|e| format!("Failed to add user to nginx group: {}", e))?;
+    service::cmd(server_id, RemoteCommand::sudo("usermod").args(["-aG", os.web_group(), &username]))
+        .map_err(|e| e.context(format!("Failed to add user to {} group", os.web_group())))?;
 
     // Add sudo access if requested
     if sudo_access {
-        service::cmd(&format!("sudo usermod -aG wheel {}", username))
-            .map_err(|e| format!("Failed to add user to wheel group: {}", e))?;
+        service::cmd(server_id, RemoteCommand::sudo("usermod").args(["-aG", os.admin_group(), &username]))
+            .map_err(|e| e.context(format!("Failed to add user to {} group", os.admin_group())))?;
     }
 
     Ok(format!("User {} successfully created{}", username, if sudo_access { " with sudo access" } else { "" }))
@@ -590,22 +1126,26 @@ pub fn create_user(username: String, password: String, sudo_access: bool) -> Res
 
 /// Remove an existing user
 #[tauri::command]
-pub fn remove_user(username: String) -> Result<String, String> {
-    // Check if user exists
-    let user_exists = service::cmd(&format!("id -u {} &>/dev/null && echo 'exists' || echo 'not exists'", username));
+pub async fn remove_user(server_id: i64, username: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || delete_user(server_id, username)).await
+}
+
+fn delete_user(server_id: i64, username: String) -> Result<String, CommandError> {
+    remote::validate_username(&username)?;
 
-    if user_exists.is_err() || user_exists.unwrap().trim() != "exists" {
-        return Err(format!("User {} does not exist", username));
+    // Check if user exists
+    if !user_exists(server_id, &username)? {
+        return Err(format!("User {} does not exist", username).into());
     }
 
     // Remove user's applications if they exist
-    let apps_result = list_applications();
+    let apps_result = applications(server_id);
     if let Ok(apps) = apps_result {
         for app in apps {
             if let Some(app_user) = app.get("user") {
                 if app_user.as_str() == Some(&username) {
                     if let Some(app_name) = app.get("name") {
-                        let _ = remove_application(app_name.as_str().unwrap_or("").to_string());
+                        let _ = remove_app(server_id, app_name.as_str().unwrap_or("").to_string());
                     }
                 }
             }
@@ -613,20 +1153,25 @@ pub fn remove_user(username: String) -> Result<String, String> {
     }
 
     // Delete the user with home directory
-    service::cmd(&format!("sudo userdel -r {}", username))
-        .map_err(|e| format!("Failed to delete user {}: {}", username, e))?;
+    service::cmd(server_id, RemoteCommand::sudo("userdel").args(["-r", &username]))
+        .map_err(|e| e.context(format!("Failed to delete user {}", username)))?;
 
     Ok(format!("User {} successfully removed", username))
 }
 
 /// List all system users
 #[tauri::command]
-pub fn list_users() -> Result<Vec<String>, String> {
+pub async fn list_users(server_id: i64) -> Result<Vec<String>, CommandError> {
+    worker::run(server_id, Access::Read, move || users(server_id)).await
+}
+
+fn users(server_id: i64) -> Result<Vec<String>, CommandError> {
     // Get users with UID >= 1000 (regular users, not system users)
-    let output = service::cmd("awk -F: '$3 >= 1000 && $3 != 65534 {print $1}' /etc/passwd")
-        .map_err(|e| format!("Failed to list users: {}", e))?;
+    let output = service::query(server_id, "awk -F: '$3 >= 1000 && $3 != 65534 {print $1}' /etc/passwd")
+        .map_err(|e| e.context("Failed to list users"))?;
 
     let users: Vec<String> = output
+        .stdout
         .trim()
         .split('\n')
         .filter(|u| !u.is_empty())
@@ -638,17 +1183,21 @@ pub fn list_users() -> Result<Vec<String>, String> {
 
 /// Change user password
 #[tauri::command]
-pub fn change_user_password(username: String, new_password: String) -> Result<String, String> {
-    // Check if user exists
-    let user_exists = service::cmd(&format!("id -u {} &>/dev/null && echo 'exists' || echo 'not exists'", username));
+pub async fn change_user_password(server_id: i64, username: String, new_password: String) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Write, move || change_password(server_id, username, new_password)).await
+}
+
+fn change_password(server_id: i64, username: String, new_password: String) -> Result<String, CommandError> {
+    remote::validate_username(&username)?;
 
-    if user_exists.is_err() || user_exists.unwrap().trim() != "exists" {
-        return Err(format!("User {} does not exist", username));
+    // Check if user exists
+    if !user_exists(server_id, &username)? {
+        return Err(format!("User {} does not exist", username).into());
     }
 
     // Change the user password
-    service::cmd(&format!("echo '{}:{}' | sudo chpasswd", username, new_password))
-        .map_err(|e| format!("Failed to change password for user {}: {}", username, e))?;
+    set_password(server_id, &username, &new_password)
+        .map_err(|e| e.context(format!("Failed to change password for user {}", username)))?;
 
     Ok(format!("Password for user {} successfully changed", username))
 }
@@ -657,101 +1206,104 @@ pub fn change_user_password(username: String, new_password: String) -> Result<St
 // SERVER INITIAL SETUP COMMAND
 // =============================================================================
 
+/// The recipe server setup runs, whose progress is stored under the same name
+const SETUP_RECIPE: &str = "setup";
+
 /// Comprehensive server setup command that installs and configures all necessary components
+/// as the `setup` recipe describes, streaming output and the state of each step as job events.
+/// Optional steps in `skip` are left out, and a setup that failed resumes from the step that failed.
 #[tauri::command]
-pub fn setup_server() -> Result<String, String> {
-    let mut setup_log = Vec::new();
-
-    // Update system packages
-    setup_log.push("Updating system packages...".to_string());
-    service::cmd("sudo dnf update -y")
-        .map_err(|e| format!("Failed to update system packages: {}", e))?;
-
-    // Install essential packages
-    setup_log.push("Installing essential packages...".to_string());
-    service::cmd("sudo dnf install -y curl wget git unzip tar gzip epel-release")
-        .map_err(|e| format!("Failed to install essential packages: {}", e))?;
-
-    // Install and configure Nginx
-    setup_log.push("Installing and configuring Nginx...".to_string());
-    service::cmd("sudo dnf install -y nginx")
-        .map_err(|e| format!("Failed to install Nginx: {}", e))?;
-
-    // Start and enable Nginx
-    service::cmd("sudo systemctl start nginx")
-        .map_err(|e| format!("Failed to start Nginx: {}", e))?;
-    service::cmd("sudo systemctl enable nginx")
-        .map_err(|e| format!("Failed to enable Nginx: {}", e))?;
-
-    // Install and configure MariaDB
-    setup_log.push("Installing and configuring MariaDB...".to_string());
-    service::cmd("sudo dnf install -y mariadb-server mariadb")
-        .map_err(|e| format!("Failed to install MariaDB: {}", e))?;
-
-    // Start and enable MariaDB
-    service::cmd("sudo systemctl start mariadb")
-        .map_err(|e| format!("Failed to start MariaDB: {}", e))?;
-    service::cmd("sudo systemctl enable mariadb")
-        .map_err(|e| format!("Failed to enable MariaDB: {}", e))?;
-
-    // Secure MariaDB installation (basic setup)
-    service::cmd("sudo mysql -e \"UPDATE mysql.user SET Password = PASSWORD('root') WHERE User = 'root'; DELETE FROM mysql.user WHERE User=''; DELETE FROM mysql.user WHERE User='root' AND Host NOT IN ('localhost', '127.0.0.1', '::1'); DROP DATABASE IF EXISTS test; DELETE FROM mysql.db WHERE Db='test' OR Db='test\\_%'; FLUSH PRIVILEGES;\"")
-        .map_err(|e| format!("Failed to secure MariaDB: {}", e))?;
-
-    // Install NVM (Node Version Manager)
-    setup_log.push("Installing NVM (Node Version Manager)...".to_string());
-    service::cmd("curl -o- https://raw.githubusercontent.com/nvm-sh/nvm/v0.39.0/install.sh | bash")
-        .map_err(|e| format!("Failed to install NVM: {}", e))?;
-
-    // Install latest stable PHP version
-    setup_log.push("Installing latest stable PHP version...".to_string());
-    install_php_version("8.4".to_string())?;
-
-    // Configure PHP-FPM
-    service::cmd("sudo systemctl start php-fpm")
-        .map_err(|e| format!("Failed to start PHP-FPM: {}", e))?;
-    service::cmd("sudo systemctl enable php-fpm")
-        .map_err(|e| format!("Failed to enable PHP-FPM: {}", e))?;
-
-    // Set up basic security configurations
-    setup_log.push("Setting up basic security configurations...".to_string());
-
-    // Configure firewalld (RHEL/Alma Linux default firewall)
-    service::cmd("sudo systemctl start firewalld")
-        .map_err(|e| format!("Failed to start firewalld: {}", e))?;
-    service::cmd("sudo systemctl enable firewalld")
-        .map_err(|e| format!("Failed to enable firewalld: {}", e))?;
-    service::cmd("sudo firewall-cmd --permanent --add-service=ssh")
-        .map_err(|e| format!("Failed to allow SSH in firewalld: {}", e))?;
-    service::cmd("sudo firewall-cmd --permanent --add-service=http")
-        .map_err(|e| format!("Failed to allow HTTP in firewalld: {}", e))?;
-    service::cmd("sudo firewall-cmd --permanent --add-service=https")
-        .map_err(|e| format!("Failed to allow HTTPS in firewalld: {}", e))?;
-    service::cmd("sudo firewall-cmd --reload")
-        .map_err(|e| format!("Failed to reload firewalld: {}", e))?;
-
-    // Create necessary directories and set permissions
-    setup_log.push("Creating necessary directories...".to_string());
-    service::cmd("sudo mkdir -p /var/www")
-        .map_err(|e| format!("Failed to create /var/www directory: {}", e))?;
-    service::cmd("sudo chown -R nginx:nginx /var/www")
-        .map_err(|e| format!("Failed to set permissions on /var/www: {}", e))?;
-
-    // Create a default index page
-    let default_content = "<html><head><title>Server Setup Complete</title></head><body><h1>Welcome!</h1><p>Your server has been successfully configured with Nginx, MariaDB, PHP, and NVM.</p></body></html>";
-    service::cmd(&format!("echo '{}' | sudo tee /usr/share/nginx/html/index.html", default_content))
-        .map_err(|e| format!("Failed to create default index page: {}", e))?;
-
-    // Configure SELinux for web services (Alma Linux specific)
-    setup_log.push("Configuring SELinux for web services...".to_string());
-    service::cmd("sudo setsebool -P httpd_can_network_connect 1")
-        .map_err(|e| format!("Failed to configure SELinux for HTTP network connections: {}", e))?;
-    service::cmd("sudo setsebool -P httpd_execmem 1")
-        .map_err(|e| format!("Failed to configure SELinux for HTTP memory execution: {}", e))?;
-
-    setup_log.push("Server setup completed successfully!".to_string());
-
-    Ok(setup_log.join("\n"))
+pub async fn setup_server(app_handle: AppHandle, server_id: i64, skip: Option<Vec<String>>, job_id: Option<String>) -> Result<String, CommandError> {
+    let handle = app_handle.clone();
+
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| {
+        let (_, log) = run_recipe_job(&handle, job, SETUP_RECIPE, SETUP_RECIPE, &HashMap::new(), &skip.unwrap_or_default())?;
+        job.info("Server setup completed successfully!");
+
+        Ok(log.join("\n"))
+    })
+    .await
+}
+
+/// The steps of server setup, in the order they run
+#[tauri::command]
+pub fn list_setup_steps(app_handle: AppHandle) -> Result<Vec<ProvisionStep>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    Ok(recipe::load(&app_handle, &conn, SETUP_RECIPE)?.describe())
+}
+
+/// Where the last setup of a server got to
+#[tauri::command]
+pub fn get_setup_progress(app_handle: AppHandle, server_id: i64) -> Result<Vec<StepProgress>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    provision::get_progress(&conn, server_id, SETUP_RECIPE)
+}
+
+/// Forget the last setup of a server, so the next one starts from the first step
+#[tauri::command]
+pub fn reset_setup_progress(app_handle: AppHandle, server_id: i64) -> Result<(), String> {
+    let conn = connection::get(&app_handle)?;
+
+    provision::clear_progress(&conn, server_id, SETUP_RECIPE)
+}
+
+// =============================================================================
+// RECIPE COMMANDS
+// =============================================================================
+
+/// The bundled recipes and those in the recipes directory, which replace bundled ones of the same name
+#[tauri::command]
+pub fn list_recipes(app_handle: AppHandle) -> Result<Vec<RecipeInfo>, String> {
+    let conn = connection::get(&app_handle)?;
+
+    Ok(recipe::load_all(&app_handle, &conn)?
+        .into_iter()
+        .map(|(recipe, path)| RecipeInfo {
+            steps: recipe.describe(),
+            name: recipe.name,
+            description: recipe.description,
+            path: path.map(|path| path.to_string_lossy().to_string()),
+        })
+        .collect())
+}
+
+/// The directory user recipes are loaded from
+#[tauri::command]
+pub fn get_recipes_directory(app_handle: AppHandle) -> Result<String, String> {
+    let conn = connection::get(&app_handle)?;
+
+    recipe::directory(&app_handle, &conn).map(|path| path.to_string_lossy().to_string())
+}
+
+/// Run a recipe on a server with the given variables, streaming output as job events. Its
+/// progress is stored under the recipe's name, like server setup's.
+#[tauri::command]
+pub async fn run_recipe(app_handle: AppHandle, server_id: i64, name: String, vars: Option<HashMap<String, String>>, skip: Option<Vec<String>>, job_id: Option<String>) -> Result<String, CommandError> {
+    let handle = app_handle.clone();
+
+    job::run(app_handle, server_id, Access::Write, job_id, move |job| {
+        let (_, log) = run_recipe_job(&handle, job, &name, &name, &vars.unwrap_or_default(), &skip.unwrap_or_default())?;
+
+        Ok(log.join("\n"))
+    })
+    .await
+}
+
+/// Load a recipe and run its steps as `plan`, returning the variables it ran with and what
+/// each step did
+fn run_recipe_job(app_handle: &AppHandle, job: &Job, name: &str, plan: &str, vars: &HashMap<String, String>, skip: &[String]) -> Result<(HashMap<String, String>, Vec<String>), CommandError> {
+    let conn = connection::get(app_handle)?;
+    let ctx = provision::Context::new(job)?;
+
+    let recipe = recipe::load(app_handle, &conn, name)?;
+    let vars = recipe.resolve_vars(&ctx, vars)?;
+    let steps = recipe.steps(&ctx, &vars)?;
+
+    let log = provision::run(&conn, &ctx, plan, &steps, skip)?;
+
+    Ok((vars, log))
 }
 
 // =============================================================================
@@ -760,7 +1312,24 @@ pub fn setup_server() -> Result<String, String> {
 
 /// Test command for development purposes
 #[tauri::command]
-pub fn test() -> Result<String, String> {
-    list_php_versions()
-        .map(|versions| format!("Available PHP versions: {:?}", versions))
+pub async fn test(server_id: i64) -> Result<String, CommandError> {
+    worker::run(server_id, Access::Read, move || {
+        php_versions(server_id).map(|versions| format!("Available PHP versions: {:?}", versions))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apt_php_versions_are_unique() {
+        let installed: Vec<Package> = ["php8.3-cli", "php8.1", "php8.3-fpm", "php-common", "phpmyadmin", "php8.1-fpm"]
+            .iter()
+            .map(|name| Package { name: name.to_string(), version: String::new() })
+            .collect();
+
+        assert_eq!(apt_php_versions(&installed), ["8.1", "8.3"]);
+    }
 }
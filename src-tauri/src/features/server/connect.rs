@@ -0,0 +1,308 @@
+use rusqlite::Connection;
+use super::model::{AuthMethod, ConnectionError, Server};
+use super::{agent, interactive, known_hosts, proxy, service, tunnel};
+use crate::common::crypto::MasterKey;
+use crate::features::{setting, ssh_key};
+use crate::features::ssh_key::model::CertificateStatus;
+use ssh2::Session;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// Longest chain of jump hosts followed before giving up
+const MAX_JUMP_HOSTS: usize = 8;
+
+struct ConnectionSettings {
+    timeout: Duration,
+    retry_attempts: u32,
+    keep_alive_interval: u32,
+}
+
+impl ConnectionSettings {
+    fn load(conn: &Connection) -> Self {
+        let timeout = setting::get_setting_or(conn, "connection/ssh_timeout", "30").parse().unwrap_or(30);
+        let retry_attempts = setting::get_setting_or(conn, "connection/retry_attempts", "5").parse().unwrap_or(5);
+        let keep_alive_interval = setting::get_setting_or(conn, "connection/keep_alive_interval", "60").parse().unwrap_or(60);
+
+        ConnectionSettings {
+            timeout: Duration::from_secs(u64::max(timeout, 1)),
+            retry_attempts: u32::max(retry_attempts, 1),
+            keep_alive_interval,
+        }
+    }
+
+    /// Exponential backoff between attempts: 1s, 2s, 4s, ... capped at 30s
+    fn backoff(&self, attempt: u32) -> Duration {
+        Duration::from_secs(u64::min(1 << u32::min(attempt - 1, 5), 30))
+    }
+}
+
+/// Credentials stored for a server that can be used without asking the user
+enum StoredCredentials {
+    Agent,
+    Key { path: String, certificate: Option<String>, passphrase: Option<String> },
+}
+
+fn private_key_path(conn: &Connection, ssh_key_id: i64) -> Result<String, String> {
+    let ssh_key_path = service::get_ssh_key_path(conn, ssh_key_id)
+        .map_err(|e| format!("Failed to get SSH key: {}", e))?;
+
+    let substr = ".pub";
+    Ok(ssh_key_path.replace(substr, ""))
+}
+
+/// The certificate to present along with a key. One that is expired or not valid yet fails the
+/// connection up front, as a server requiring it would reject the bare key anyway.
+fn certificate_path(conn: &Connection, ssh_key_id: i64) -> Result<Option<String>, String> {
+    let Some((path, certificate)) = ssh_key::get_ssh_key_certificate(conn, ssh_key_id)? else {
+        return Ok(None);
+    };
+
+    match certificate.status {
+        CertificateStatus::Valid | CertificateStatus::Expiring => Ok(Some(path)),
+        CertificateStatus::Expired => Err(format!(
+            "Certificate {} ({}) expired at {}",
+            path,
+            certificate.key_id,
+            certificate.valid_before.unwrap_or_default()
+        )),
+        CertificateStatus::NotYetValid => Err(format!(
+            "Certificate {} ({}) is not valid before {}",
+            path,
+            certificate.key_id,
+            certificate.valid_after.unwrap_or_default()
+        )),
+    }
+}
+
+fn port(server: &Server) -> Result<u16, String> {
+    u16::try_from(server.port).map_err(|_| format!("Invalid port: {}", server.port))
+}
+
+fn offers_keyboard_interactive(sess: &Session, username: &str) -> bool {
+    sess.auth_methods(username)
+        .map(|methods| methods.split(',').any(|method| method == "keyboard-interactive"))
+        .unwrap_or(false)
+}
+
+/// Establishes authenticated sessions, either directly or through a chain of jump hosts
+/// where every hop authenticates with its own stored credentials.
+pub struct Connector<'a> {
+    conn: &'a Connection,
+    master_key: &'a MasterKey,
+    settings: ConnectionSettings,
+    prompts: Option<&'a AppHandle>,
+}
+
+impl<'a> Connector<'a> {
+    pub fn new(conn: &'a Connection, master_key: &'a MasterKey) -> Self {
+        Connector {
+            conn,
+            master_key,
+            settings: ConnectionSettings::load(conn),
+            prompts: None,
+        }
+    }
+
+    /// Relay keyboard-interactive prompts, such as one-time codes, to the frontend.
+    /// Without this only a password prompt can be answered, and only with a known password.
+    pub fn with_prompts(mut self, app_handle: &'a AppHandle) -> Self {
+        self.prompts = Some(app_handle);
+        self
+    }
+
+    /// Connect using the credentials stored for the server (SSH key or ssh-agent).
+    pub fn connect(&self, server: &Server) -> Result<Session, ConnectionError> {
+        self.connect_via(server, &mut Vec::new())
+    }
+
+    pub fn connect_with_password(&self, server: &Server, password: &str) -> Result<Session, ConnectionError> {
+        let sess = self.open_session(server, &mut Vec::new())?;
+
+        let error = sess.userauth_password(&server.username, password)
+            .map_err(|e| format!("Password authentication failed: {}", e))
+            .err();
+
+        self.finish_authentication(sess, server, error, Some(password))
+    }
+
+    pub fn connect_with_key_passphrase(&self, server: &Server, passphrase: &str) -> Result<Session, ConnectionError> {
+        let ssh_key_id = server.ssh_key_id.ok_or("No SSH key set for this server")?;
+        let path = private_key_path(self.conn, ssh_key_id)?;
+        let certificate = certificate_path(self.conn, ssh_key_id)?;
+
+        let sess = self.open_session(server, &mut Vec::new())?;
+
+        let error = sess.userauth_pubkey_file(&server.username, certificate.as_deref().map(Path::new), Path::new(&path), Some(passphrase))
+            .map_err(|e| format!("SSH key authentication failed: {}", e))
+            .err();
+
+        self.finish_authentication(sess, server, error, None)
+    }
+
+    /// Reach the server and complete the SSH handshake without checking its host key or
+    /// authenticating. Jump hosts along the way are still fully verified.
+    pub fn handshake(&self, server: &Server) -> Result<Session, ConnectionError> {
+        self.handshake_via(server, &mut Vec::new())
+    }
+
+    fn stored_credentials(&self, server: &Server) -> Result<StoredCredentials, ConnectionError> {
+        let server_id = server.id.ok_or("Server ID is required to connect")?;
+
+        match server.auth_method {
+            AuthMethod::Agent => Ok(StoredCredentials::Agent),
+            AuthMethod::Password => Err(format!(
+                "{} uses password authentication, and password flow not initiated from here.",
+                server.name
+            ).into()),
+            AuthMethod::Key => {
+                let Some(ssh_key_id) = server.ssh_key_id else {
+                    return Err("No SSH key set for this server, and password flow not initiated from here.".into());
+                };
+
+                let path = private_key_path(self.conn, ssh_key_id)?;
+                let certificate = certificate_path(self.conn, ssh_key_id)?;
+
+                let passphrase = match ssh_key::get_ssh_key_passphrase(self.conn, self.master_key, ssh_key_id)? {
+                    Some(passphrase) => Some(passphrase),
+                    None if ssh_key::is_private_key_encrypted(Path::new(&path))? => {
+                        return Err(ConnectionError::PassphraseRequired { server_id, ssh_key_id });
+                    }
+                    None => None,
+                };
+
+                Ok(StoredCredentials::Key { path, certificate, passphrase })
+            }
+        }
+    }
+
+    fn connect_via(&self, server: &Server, chain: &mut Vec<i64>) -> Result<Session, ConnectionError> {
+        let credentials = self.stored_credentials(server)?;
+        let sess = self.open_session(server, chain)?;
+
+        let error = match credentials {
+            StoredCredentials::Agent => agent::authenticate(&sess, &server.username).err(),
+            StoredCredentials::Key { path, certificate, passphrase } => {
+                // libssh2 sends whatever public key file it is given, so a certificate works here
+                sess.userauth_pubkey_file(&server.username, certificate.as_deref().map(Path::new), Path::new(&path), passphrase.as_deref())
+                    .map_err(|e| format!("SSH key authentication failed: {}", e))
+                    .err()
+            }
+        };
+
+        self.finish_authentication(sess, server, error, None)
+    }
+
+    /// Servers may require more than one method, e.g. a key followed by a one-time code, so
+    /// keyboard-interactive is tried whenever the server still offers it after the first method.
+    /// The connect timeout only guards connecting and authenticating; commands on an
+    /// established session may legitimately run quiet for longer than that.
+    fn finish_authentication(&self, sess: Session, server: &Server, error: Option<String>, password: Option<&str>) -> Result<Session, ConnectionError> {
+        let mut error = error;
+
+        if !sess.authenticated() && offers_keyboard_interactive(&sess, &server.username) {
+            // The user may take a while to answer, which the connect timeout must not cut short
+            sess.set_timeout(0);
+
+            if let Err(e) = interactive::authenticate(&sess, server, self.prompts, password) {
+                error = Some(e);
+            }
+        }
+
+        if !sess.authenticated() {
+            return Err(error.unwrap_or_else(|| "Authentication failed".to_string()).into());
+        }
+
+        sess.set_timeout(0);
+
+        Ok(sess)
+    }
+
+    fn open_session(&self, server: &Server, chain: &mut Vec<i64>) -> Result<Session, ConnectionError> {
+        let sess = self.handshake_via(server, chain)?;
+
+        known_hosts::verify_host_key(self.conn, server, &sess)?;
+
+        sess.set_keepalive(true, self.settings.keep_alive_interval);
+
+        Ok(sess)
+    }
+
+    fn handshake_via(&self, server: &Server, chain: &mut Vec<i64>) -> Result<Session, ConnectionError> {
+        let Some(jump_host_id) = server.jump_host_id else {
+            return Ok(self.handshake_with_retry(server)?);
+        };
+
+        chain.extend(server.id);
+        if chain.contains(&jump_host_id) {
+            return Err(format!("Jump host chain of {} loops back on itself", server.name).into());
+        }
+        if chain.len() > MAX_JUMP_HOSTS {
+            return Err(format!("Jump host chain of {} is longer than {} hops", server.name, MAX_JUMP_HOSTS).into());
+        }
+
+        let jump_host = service::get_server(self.conn, jump_host_id)
+            .map_err(|e| format!("Failed to load jump host: {}", e))?;
+
+        let bastion = self.connect_via(&jump_host, chain).map_err(|e| match e {
+            ConnectionError::Failed { message } => ConnectionError::Failed {
+                message: format!("Jump host {}: {}", jump_host.name, message),
+            },
+            other => other,
+        })?;
+
+        let stream = tunnel::open(bastion, &server.hostname, port(server)?)?;
+
+        Ok(self.handshake_over(server, stream)?)
+    }
+
+    fn handshake_over(&self, server: &Server, stream: TcpStream) -> Result<Session, String> {
+        let mut sess = Session::new()
+            .map_err(|e| format!("Failed to create SSH session: {}", e))?;
+        sess.set_timeout(self.settings.timeout.as_millis() as u32);
+        sess.set_tcp_stream(stream);
+        known_hosts::prefer_known_key_type(self.conn, server, &sess)?;
+        sess.handshake().map_err(|e| format!("SSH handshake failed: {}", e))?;
+
+        Ok(sess)
+    }
+
+    fn connect_tcp(&self, server: &Server) -> Result<TcpStream, String> {
+        if let Some(proxy) = proxy::for_server(self.conn, self.master_key, server)? {
+            return proxy::connect(&proxy, &server.hostname, port(server)?, self.settings.timeout);
+        }
+
+        let addrs = (server.hostname.as_str(), port(server)?).to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", server.hostname, e))?;
+
+        let mut last_error = format!("No addresses found for {}", server.hostname);
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, self.settings.timeout) {
+                Ok(tcp) => return Ok(tcp),
+                Err(e) => last_error = format!("Failed to connect to server: {}", e),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Direct connections are retried with backoff; tunneled hops fail fast since their
+    /// jump host is already known to be reachable.
+    fn handshake_with_retry(&self, server: &Server) -> Result<Session, String> {
+        let mut attempt = 1;
+
+        loop {
+            match self.connect_tcp(server).and_then(|tcp| self.handshake_over(server, tcp)) {
+                Ok(sess) => return Ok(sess),
+                Err(e) if attempt < self.settings.retry_attempts => {
+                    let backoff = self.settings.backoff(attempt);
+                    eprintln!("Connection attempt {} to {} failed: {}. Retrying in {}s", attempt, server.hostname, e, backoff.as_secs());
+                    std::thread::sleep(backoff);
+                    attempt += 1;
+                }
+                Err(e) => return Err(format!("{} (after {} attempts)", e, attempt)),
+            }
+        }
+    }
+}
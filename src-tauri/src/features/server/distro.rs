@@ -0,0 +1,159 @@
+use super::model::{CommandError, OsRelease};
+use super::service;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Distributions detected on connect, so commands don't need the database to pick a package
+/// manager
+static DISTROS: Lazy<Mutex<HashMap<i64, OsRelease>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// `/usr/lib/os-release` is the fallback location systemd documents for distributions that
+/// don't ship the `/etc` symlink
+const READ_OS_RELEASE: &str = "cat /etc/os-release 2>/dev/null || cat /usr/lib/os-release";
+
+/// Parse `os-release` contents: `KEY=value` lines, with values optionally quoted.
+pub fn parse(contents: &str) -> OsRelease {
+    let mut os = OsRelease::default();
+
+    for line in contents.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'').to_string();
+
+        match key {
+            "ID" => os.id = value.to_lowercase(),
+            "ID_LIKE" => os.id_like = value.split_whitespace().map(str::to_lowercase).collect(),
+            "VERSION_ID" => os.version_id = value,
+            "PRETTY_NAME" => os.name = value,
+            "NAME" if os.name.is_empty() => os.name = value,
+            _ => {}
+        }
+    }
+
+    os
+}
+
+/// Read the distribution of a connected server.
+pub fn detect(server_id: i64) -> Result<OsRelease, CommandError> {
+    let output = service::query(server_id, READ_OS_RELEASE)
+        .map_err(|e| e.context("Failed to read /etc/os-release"))?;
+
+    let os = parse(&output.stdout);
+    if os.id.is_empty() {
+        return Err("/etc/os-release has no ID".into());
+    }
+
+    if let Ok(mut distros) = DISTROS.lock() {
+        distros.insert(server_id, os.clone());
+    }
+
+    Ok(os)
+}
+
+/// The distribution of a connected server, detecting it if that didn't happen on connect.
+pub fn of(server_id: i64) -> Result<OsRelease, CommandError> {
+    let known = DISTROS.lock().ok().and_then(|distros| distros.get(&server_id).cloned());
+
+    match known {
+        Some(os) => Ok(os),
+        None => detect(server_id),
+    }
+}
+
+impl OsRelease {
+    /// Whether this is the given distribution or one derived from it
+    pub fn is_like(&self, id: &str) -> bool {
+        self.id == id || self.id_like.iter().any(|like| like == id)
+    }
+
+    /// RHEL, AlmaLinux, Rocky, CentOS Stream, Fedora and the like
+    pub fn is_rhel_family(&self) -> bool {
+        self.is_like("rhel") || self.is_like("fedora") || self.is_like("centos")
+    }
+
+    pub fn is_debian_family(&self) -> bool {
+        self.is_like("debian") || self.is_like("ubuntu")
+    }
+
+    pub fn is_suse_family(&self) -> bool {
+        self.is_like("suse") || self.is_like("opensuse") || self.id.starts_with("opensuse") || self.id == "sles"
+    }
+
+    /// The major version, `9` for `9.4`
+    pub fn major_version(&self) -> &str {
+        self.version_id.split('.').next().unwrap_or_default()
+    }
+
+    /// The group nginx runs as, which owns application files
+    pub fn web_group(&self) -> &'static str {
+        if self.is_debian_family() { "www-data" } else { "nginx" }
+    }
+
+    /// The group whose members may use sudo
+    pub fn admin_group(&self) -> &'static str {
+        if self.is_debian_family() { "sudo" } else { "wheel" }
+    }
+
+    /// Where nginx's default server serves files from
+    pub fn default_web_root(&self) -> &'static str {
+        if self.is_debian_family() { "/var/www/html" } else { "/usr/share/nginx/html" }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROCKY: &str = r#"NAME="Rocky Linux"
+VERSION="9.4 (Blue Onyx)"
+ID="rocky"
+ID_LIKE="rhel centos fedora"
+VERSION_ID="9.4"
+PRETTY_NAME="Rocky Linux 9.4 (Blue Onyx)"
+"#;
+
+    const UBUNTU: &str = "PRETTY_NAME=\"Ubuntu 24.04 LTS\"\nNAME=\"Ubuntu\"\nVERSION_ID=\"24.04\"\nID=ubuntu\nID_LIKE=debian\n";
+
+    #[test]
+    fn parses_id_like() {
+        let os = parse(ROCKY);
+
+        assert_eq!(os.id, "rocky");
+        assert_eq!(os.id_like, ["rhel", "centos", "fedora"]);
+        assert_eq!(os.version_id, "9.4");
+        assert_eq!(os.major_version(), "9");
+        assert_eq!(os.name, "Rocky Linux 9.4 (Blue Onyx)");
+        assert!(os.is_rhel_family());
+        assert!(!os.is_debian_family());
+        assert_eq!(os.web_group(), "nginx");
+    }
+
+    #[test]
+    fn derived_distribution_follows_its_parent() {
+        let os = parse(UBUNTU);
+
+        assert!(os.is_debian_family());
+        assert_eq!(os.web_group(), "www-data");
+        assert_eq!(os.admin_group(), "sudo");
+        assert_eq!(os.default_web_root(), "/var/www/html");
+    }
+
+    #[test]
+    fn name_falls_back_without_pretty_name() {
+        let os = parse("NAME='openSUSE Leap'\nID=\"opensuse-leap\"\nID_LIKE=\"suse opensuse\"\n");
+
+        assert_eq!(os.name, "openSUSE Leap");
+        assert!(os.is_suse_family());
+    }
+
+    #[test]
+    fn ignores_comments_and_garbage() {
+        let os = parse("# comment\n\nnot a pair\nID=Debian\n");
+
+        assert_eq!(os.id, "debian");
+        assert!(os.id_like.is_empty());
+        assert_eq!(os.version_id, "");
+    }
+}
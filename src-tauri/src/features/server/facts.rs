@@ -0,0 +1,232 @@
+use super::model::{CommandError, Disk, ServerFacts};
+use super::service;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Everything is gathered in one round trip, each section headed by a `## name` line. Services
+/// are listed from systemd unit files and init scripts, as daemons in /usr/sbin aren't on a
+/// regular user's PATH everywhere.
+const GATHER_FACTS: &str = "\
+echo '## kernel'; uname -r; \
+echo '## arch'; uname -m; \
+echo '## cpus'; nproc 2>/dev/null || getconf _NPROCESSORS_ONLN; \
+echo '## memory'; awk '/^MemTotal:/ {print $2}' /proc/meminfo; \
+echo '## disks'; df -PT -B1 -x tmpfs -x devtmpfs -x overlay -x squashfs -x efivarfs 2>/dev/null | tail -n +2; \
+echo '## addresses'; hostname -I 2>/dev/null || ip -o addr show scope global | awk '{print $4}'; \
+echo '## services'; systemctl list-unit-files --type=service --no-legend 2>/dev/null | awk '$2 != \"alias\" {print $1}'; ls /etc/init.d 2>/dev/null; \
+echo '## selinux'; getenforce 2>/dev/null; \
+true";
+
+/// Asked only when no interface has a public address, as on cloud servers behind NAT
+const EXTERNAL_IP: &str = "curl -4fsS --max-time 3 https://checkip.amazonaws.com";
+
+/// Service names and what they are reported as
+const WEB_SERVERS: [(&str, &str); 5] = [
+    ("nginx", "nginx"),
+    ("httpd", "apache"),
+    ("apache2", "apache"),
+    ("caddy", "caddy"),
+    ("lighttpd", "lighttpd"),
+];
+
+const DATABASE_SERVERS: [(&str, &str); 8] = [
+    ("mariadb", "mariadb"),
+    ("mysql", "mysql"),
+    ("mysqld", "mysql"),
+    ("postgresql", "postgresql"),
+    ("mongod", "mongodb"),
+    ("redis", "redis"),
+    ("redis-server", "redis"),
+    ("valkey", "valkey"),
+];
+
+/// Gather the facts of a connected server.
+pub fn gather(server_id: i64) -> Result<ServerFacts, CommandError> {
+    let output = service::query(server_id, GATHER_FACTS)
+        .map_err(|e| e.context("Failed to gather server facts"))?;
+
+    let mut facts = parse(&output.stdout);
+
+    if facts.public_ips.is_empty() {
+        if let Ok(output) = service::query(server_id, EXTERNAL_IP) {
+            facts.public_ips.extend(output.stdout.trim().parse::<IpAddr>().ok().map(|ip| ip.to_string()));
+        }
+    }
+
+    Ok(facts)
+}
+
+fn parse(output: &str) -> ServerFacts {
+    let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut current = "";
+
+    for line in output.lines() {
+        match line.strip_prefix("## ") {
+            Some(name) => current = name.trim(),
+            None if !line.trim().is_empty() => sections.entry(current).or_default().push(line.trim()),
+            None => {}
+        }
+    }
+
+    let section = |name: &str| sections.get(name).cloned().unwrap_or_default();
+    let first = |name: &str| section(name).first().map(|line| line.to_string()).unwrap_or_default();
+
+    let services: Vec<&str> = section("services")
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .map(|name| name.strip_suffix(".service").unwrap_or(name))
+        .collect();
+
+    let mut database_servers = installed(&services, &DATABASE_SERVERS);
+    // MariaDB installs `mysql` and `mysqld` aliases for compatibility
+    if database_servers.iter().any(|name| name == "mariadb") {
+        database_servers.retain(|name| name != "mysql");
+    }
+
+    ServerFacts {
+        kernel: first("kernel"),
+        arch: first("arch"),
+        cpus: first("cpus").parse().unwrap_or(0),
+        memory: first("memory").parse::<u64>().unwrap_or(0) * 1024,
+        disks: section("disks").iter().filter_map(|line| parse_disk(line)).collect(),
+        public_ips: public_ips(&section("addresses")),
+        web_servers: installed(&services, &WEB_SERVERS),
+        database_servers,
+        selinux: section("selinux").first().map(|mode| mode.to_lowercase()),
+        gathered_at: chrono::Local::now().to_rfc3339(),
+    }
+}
+
+/// A `df -PT -B1` line: device, type, size, used, available, capacity, mount point
+fn parse_disk(line: &str) -> Option<Disk> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 7 {
+        return None;
+    }
+
+    Some(Disk {
+        device: fields[0].to_string(),
+        filesystem: fields[1].to_string(),
+        size: fields[2].parse().ok()?,
+        used: fields[3].parse().ok()?,
+        // Mount points may contain spaces
+        mount_point: fields[6..].join(" "),
+    })
+}
+
+fn installed(services: &[&str], known: &[(&str, &'static str)]) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+
+    for (service, name) in known {
+        if services.contains(service) && !found.iter().any(|f| f == name) {
+            found.push(name.to_string());
+        }
+    }
+
+    found
+}
+
+fn public_ips(lines: &[&str]) -> Vec<String> {
+    let mut ips: Vec<String> = Vec::new();
+
+    for address in lines.iter().flat_map(|line| line.split_whitespace()) {
+        let address = address.split('/').next().unwrap_or(address);
+
+        if let Ok(ip) = address.parse::<IpAddr>() {
+            if is_public(&ip) && !ips.contains(&ip.to_string()) {
+                ips.push(ip.to_string());
+            }
+        }
+    }
+
+    ips
+}
+
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            // 100.64.0.0/10 is carrier-grade NAT
+            let shared = a == 100 && (64..128).contains(&b);
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || shared)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // fc00::/7 is unique local, fe80::/10 link-local
+            let local = (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || local)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "## kernel
+5.14.0-427.el9.x86_64
+## arch
+x86_64
+## cpus
+4
+## memory
+8003584
+## disks
+/dev/vda1      xfs   42872786944 5368709120 37504077824      13% /
+/dev/vdb       ext4  107374182400 1073741824 106300440576      1% /mnt/backup disk
+## addresses
+10.0.0.5 203.0.113.10 100.64.1.2 2001:db8::1 fe80::1
+## services
+mariadb.service enabled
+mysql.service enabled
+nginx.service enabled
+redis.service disabled
+sshd.service enabled
+## selinux
+Enforcing
+";
+
+    #[test]
+    fn parses_every_section() {
+        let facts = parse(OUTPUT);
+
+        assert_eq!(facts.kernel, "5.14.0-427.el9.x86_64");
+        assert_eq!(facts.arch, "x86_64");
+        assert_eq!(facts.cpus, 4);
+        assert_eq!(facts.memory, 8003584 * 1024);
+        assert_eq!(facts.web_servers, ["nginx"]);
+        assert_eq!(facts.database_servers, ["mariadb", "redis"]);
+        assert_eq!(facts.selinux.as_deref(), Some("enforcing"));
+    }
+
+    #[test]
+    fn disks_keep_mount_points_with_spaces() {
+        let disks = parse(OUTPUT).disks;
+
+        assert_eq!(disks.len(), 2);
+        assert_eq!((disks[0].device.as_str(), disks[0].filesystem.as_str(), disks[0].mount_point.as_str()), ("/dev/vda1", "xfs", "/"));
+        assert_eq!(disks[0].size, 42872786944);
+        assert_eq!(disks[0].used, 5368709120);
+        assert_eq!(disks[1].mount_point, "/mnt/backup disk");
+        assert!(parse_disk("/dev/vda1 xfs - - - - /").is_none());
+        assert!(parse_disk("/dev/vda1 xfs 1 2").is_none());
+    }
+
+    #[test]
+    fn only_public_addresses_are_kept() {
+        assert_eq!(parse(OUTPUT).public_ips, ["203.0.113.10", "2001:db8::1"]);
+        assert_eq!(public_ips(&["192.168.1.2/24 127.0.0.1 fd00::1 203.0.113.10/24 203.0.113.10"]), ["203.0.113.10"]);
+    }
+
+    #[test]
+    fn missing_sections_are_empty() {
+        let facts = parse("## kernel\n6.8.0-31-generic\n## services\nnginx\napache2\n");
+
+        assert_eq!(facts.kernel, "6.8.0-31-generic");
+        assert_eq!(facts.cpus, 0);
+        assert_eq!(facts.memory, 0);
+        assert!(facts.disks.is_empty());
+        assert_eq!(facts.web_servers, ["nginx", "apache"]);
+        assert_eq!(facts.selinux, None);
+    }
+}
@@ -0,0 +1,261 @@
+use super::model::{CommandError, OsRelease};
+use super::remote::{quote, RemoteCommand};
+use super::service;
+
+/// Incoming TCP traffic to let through the firewall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirewallRule {
+    /// The well-known service on `port`, for backends that allow by service name
+    pub service: Option<&'static str>,
+    pub port: u16,
+}
+
+impl FirewallRule {
+    pub const HTTP: FirewallRule = FirewallRule { service: Some("http"), port: 80 };
+    pub const HTTPS: FirewallRule = FirewallRule { service: Some("https"), port: 443 };
+
+    /// SSH, on whichever port sshd listens on
+    pub fn ssh(port: u16) -> Self {
+        FirewallRule { service: (port == 22).then_some("ssh"), port }
+    }
+}
+
+/// A firewall that can be turned on and told which traffic to allow. Rules take effect once
+/// `apply` has run and survive reboots.
+pub trait Firewall: Send {
+    fn name(&self) -> &'static str;
+
+    /// The command whose presence tells the firewall is installed
+    fn binary(&self) -> &'static str;
+
+    /// A check that succeeds if the firewall is installed. It runs as root, as /usr/sbin isn't
+    /// on a regular user's PATH on Debian and Ubuntu.
+    fn installed(&self) -> RemoteCommand {
+        RemoteCommand::sudo("sh").args(["-c", &format!("command -v {}", self.binary())])
+    }
+
+    /// The package to install it from
+    fn package(&self) -> &'static str;
+
+    /// The service to start and enable before configuring it, if any
+    fn service(&self) -> Option<&'static str>;
+
+    /// Turn filtering on, allowing SSH first so the connection isn't cut off
+    fn activate(&self, ssh: &FirewallRule) -> RemoteCommand;
+
+    fn allow(&self, rule: &FirewallRule) -> RemoteCommand;
+
+    /// A check that succeeds if the rule is in effect
+    fn allows(&self, rule: &FirewallRule) -> RemoteCommand;
+
+    /// Load or save the rules, for backends that don't do it as they are added
+    fn apply(&self) -> Option<RemoteCommand>;
+}
+
+/// RHEL, Fedora and openSUSE. Rules go to the permanent configuration, then are reloaded.
+pub struct Firewalld;
+
+impl Firewall for Firewalld {
+    fn name(&self) -> &'static str {
+        "firewalld"
+    }
+
+    fn binary(&self) -> &'static str {
+        "firewall-cmd"
+    }
+
+    fn package(&self) -> &'static str {
+        "firewalld"
+    }
+
+    fn service(&self) -> Option<&'static str> {
+        Some("firewalld")
+    }
+
+    fn activate(&self, ssh: &FirewallRule) -> RemoteCommand {
+        self.allow(ssh)
+    }
+
+    fn allow(&self, rule: &FirewallRule) -> RemoteCommand {
+        let target = match rule.service {
+            Some(service) => format!("--add-service={}", service),
+            None => format!("--add-port={}/tcp", rule.port),
+        };
+
+        RemoteCommand::sudo("firewall-cmd").arg("--permanent").arg(target)
+    }
+
+    fn allows(&self, rule: &FirewallRule) -> RemoteCommand {
+        let target = match rule.service {
+            Some(service) => format!("--query-service={}", service),
+            None => format!("--query-port={}/tcp", rule.port),
+        };
+
+        RemoteCommand::sudo("firewall-cmd").arg("--permanent").arg(target)
+    }
+
+    fn apply(&self) -> Option<RemoteCommand> {
+        Some(RemoteCommand::sudo("firewall-cmd").arg("--reload"))
+    }
+}
+
+/// Ubuntu's default. Rules apply and persist as they are added.
+pub struct Ufw;
+
+impl Firewall for Ufw {
+    fn name(&self) -> &'static str {
+        "ufw"
+    }
+
+    fn binary(&self) -> &'static str {
+        "ufw"
+    }
+
+    fn package(&self) -> &'static str {
+        "ufw"
+    }
+
+    fn service(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn activate(&self, ssh: &FirewallRule) -> RemoteCommand {
+        ufw_allow(ssh)
+            .and(RemoteCommand::new("ufw").args(["--force", "enable"]))
+            .elevated()
+    }
+
+    fn allow(&self, rule: &FirewallRule) -> RemoteCommand {
+        ufw_allow(rule).elevated()
+    }
+
+    /// Rules are only in effect while ufw is enabled
+    fn allows(&self, rule: &FirewallRule) -> RemoteCommand {
+        RemoteCommand::shell(&format!(
+            "ufw status | grep -q '^Status: active' && ufw status | grep -qE '^{}/tcp +ALLOW'",
+            rule.port
+        ))
+        .elevated()
+    }
+
+    fn apply(&self) -> Option<RemoteCommand> {
+        None
+    }
+}
+
+fn ufw_allow(rule: &FirewallRule) -> RemoteCommand {
+    RemoteCommand::new("ufw").arg("allow").arg(format!("{}/tcp", rule.port))
+}
+
+/// Plain nftables, with the rules in a table of their own that drops all other incoming traffic.
+/// The table is saved to a file of its own, included from the one the nftables service loads
+/// on boot so the rules others keep there stay as they are.
+pub struct Nftables {
+    config_path: &'static str,
+}
+
+/// The table the app manages, leaving any others alone
+const NFT_TABLE: &str = "inet syndeos";
+
+const NFT_TABLE_PATH: &str = "/etc/nftables.d/syndeos.nft";
+
+impl Firewall for Nftables {
+    fn name(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn binary(&self) -> &'static str {
+        "nft"
+    }
+
+    fn package(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn service(&self) -> Option<&'static str> {
+        Some("nftables")
+    }
+
+    /// Recreates the table, so rules allowed earlier have to be allowed again
+    fn activate(&self, ssh: &FirewallRule) -> RemoteCommand {
+        let ruleset = format!(
+            "table {table}\ndelete table {table}\ntable {table} {{\n\
+             \tchain input {{\n\
+             \t\ttype filter hook input priority 0; policy drop;\n\
+             \t\tct state established,related accept\n\
+             \t\tiif lo accept\n\
+             \t\tmeta l4proto {{ icmp, ipv6-icmp }} accept\n\
+             \t\t{rule}\n\
+             \t}}\n\
+             }}\n",
+            table = NFT_TABLE,
+            rule = nft_rule(ssh),
+        );
+
+        RemoteCommand::sudo("nft").args(["-f", "-"]).stdin(ruleset)
+    }
+
+    fn allow(&self, rule: &FirewallRule) -> RemoteCommand {
+        let rule = nft_rule(rule);
+
+        RemoteCommand::shell(&format!(
+            "nft list chain {table} input | grep -qF '{rule}' || nft add rule {table} input {rule}",
+            table = NFT_TABLE,
+            rule = rule,
+        ))
+        .elevated()
+    }
+
+    fn allows(&self, rule: &FirewallRule) -> RemoteCommand {
+        RemoteCommand::shell(&format!("nft list chain {} input | grep -qF '{}'", NFT_TABLE, nft_rule(rule))).elevated()
+    }
+
+    fn apply(&self) -> Option<RemoteCommand> {
+        // Loading the file replaces the table without touching the rest of the ruleset
+        let include = format!("include \"{}\"", NFT_TABLE_PATH);
+
+        Some(RemoteCommand::shell(&format!(
+            "mkdir -p /etc/nftables.d && \
+             {{ echo 'table {table}'; echo 'delete table {table}'; nft list table {table}; }} > {path} && \
+             {{ grep -qxF {include} {config} 2>/dev/null || echo {include} >> {config}; }}",
+            table = NFT_TABLE,
+            path = NFT_TABLE_PATH,
+            include = quote(&include),
+            config = self.config_path,
+        ))
+        .elevated())
+    }
+}
+
+fn nft_rule(rule: &FirewallRule) -> String {
+    format!("tcp dport {} accept", rule.port)
+}
+
+/// The firewall to configure on a server: the one already installed, else the distribution's
+/// default.
+pub fn detect(server_id: i64, os: &OsRelease) -> Result<Box<dyn Firewall>, CommandError> {
+    let nftables = Nftables {
+        config_path: if os.is_debian_family() { "/etc/nftables.conf" } else { "/etc/sysconfig/nftables.conf" },
+    };
+
+    let installed: [Box<dyn Firewall>; 3] = [Box::new(Firewalld), Box::new(Ufw), Box::new(nftables)];
+    for firewall in installed {
+        if service::probe(server_id, firewall.installed())? {
+            return Ok(firewall);
+        }
+    }
+
+    if os.is_debian_family() {
+        Ok(Box::new(Ufw))
+    } else {
+        Ok(Box::new(Firewalld))
+    }
+}
+
+/// The port sshd accepted the current connection on, which must stay open
+pub fn ssh_port(server_id: i64) -> u16 {
+    service::query(server_id, "echo \"$SSH_CONNECTION\"")
+        .ok()
+        .and_then(|output| output.stdout.split_whitespace().nth(3)?.parse().ok())
+        .unwrap_or(22)
+}
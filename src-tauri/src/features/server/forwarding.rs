@@ -0,0 +1,276 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use super::model::{Tunnel, TunnelStatus};
+use super::tunnel::{Flow, Idle, Pipe, BUFFER_SIZE};
+use once_cell::sync::Lazy;
+use ssh2::Session;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter};
+
+const TUNNEL_COLUMNS: &str = "id, server_id, local_port, remote_host, remote_port, auto_restore, created_at";
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    active: Arc<AtomicU32>,
+    total: Arc<AtomicU64>,
+}
+
+static RUNNING: Lazy<Mutex<HashMap<i64, Running>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Why each tunnel that stopped on its own did so, until it is started again
+static LAST_ERRORS: Lazy<Mutex<HashMap<i64, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn running() -> Result<MutexGuard<'static, HashMap<i64, Running>>, String> {
+    RUNNING.lock().map_err(|_| "Failed to acquire tunnel registry lock".to_string())
+}
+
+fn tunnel_from_row(row: &Row) -> rusqlite::Result<Tunnel> {
+    Ok(Tunnel {
+        id: Some(row.get(0)?),
+        server_id: row.get(1)?,
+        local_port: row.get(2)?,
+        remote_host: row.get(3)?,
+        remote_port: row.get(4)?,
+        auto_restore: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+pub fn get_tunnel(conn: &Connection, id: i64) -> Result<Tunnel, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM tunnels WHERE id = ?1", TUNNEL_COLUMNS),
+        params![id],
+        tunnel_from_row,
+    ).optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Tunnel {} not found", id))
+}
+
+pub fn get_tunnels(conn: &Connection, server_id: Option<i64>) -> Result<Vec<Tunnel>, String> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tunnels WHERE ?1 IS NULL OR server_id = ?1 ORDER BY server_id, local_port",
+        TUNNEL_COLUMNS
+    )).map_err(|e| e.to_string())?;
+
+    let tunnels = stmt.query_map(params![server_id], tunnel_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(tunnels)
+}
+
+pub fn add_tunnel(conn: &Connection, tunnel: Tunnel) -> Result<Tunnel, String> {
+    if tunnel.local_port == 0 || tunnel.remote_port == 0 {
+        return Err("Tunnel ports must be between 1 and 65535".to_string());
+    }
+    if tunnel.remote_host.trim().is_empty() {
+        return Err("Tunnel remote host is required".to_string());
+    }
+
+    conn.execute(
+        "INSERT INTO tunnels (server_id, local_port, remote_host, remote_port, auto_restore, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            tunnel.server_id,
+            tunnel.local_port,
+            tunnel.remote_host.trim(),
+            tunnel.remote_port,
+            tunnel.auto_restore,
+            chrono::Local::now().to_rfc3339()
+        ],
+    ).map_err(|e| e.to_string())?;
+
+    get_tunnel(conn, conn.last_insert_rowid())
+}
+
+pub fn delete_tunnel(conn: &Connection, id: i64) -> Result<(), String> {
+    stop(id)?;
+
+    conn.execute("DELETE FROM tunnels WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn delete_server_tunnels(conn: &Connection, server_id: i64) -> Result<(), String> {
+    for tunnel in get_tunnels(conn, Some(server_id))? {
+        if let Some(id) = tunnel.id {
+            stop(id)?;
+        }
+    }
+
+    conn.execute("DELETE FROM tunnels WHERE server_id = ?1", params![server_id])
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn is_running(id: i64) -> bool {
+    running().map(|running| running.contains_key(&id)).unwrap_or(false)
+}
+
+pub fn status(tunnel: Tunnel) -> TunnelStatus {
+    let id = tunnel.id.unwrap_or_default();
+
+    let (running, active_connections, total_connections) = match RUNNING.lock() {
+        Ok(running) => match running.get(&id) {
+            Some(state) => (true, state.active.load(Ordering::Relaxed), state.total.load(Ordering::Relaxed)),
+            None => (false, 0, 0),
+        },
+        Err(_) => (false, 0, 0),
+    };
+
+    let error = LAST_ERRORS.lock().ok().and_then(|errors| errors.get(&id).cloned());
+
+    TunnelStatus { tunnel, running, active_connections, total_connections, error }
+}
+
+fn emit_status(app_handle: &AppHandle, tunnel: &Tunnel) {
+    if let Err(e) = app_handle.emit("tunnel-updated", status(tunnel.clone())) {
+        eprintln!("Failed to emit status of tunnel {:?}: {}", tunnel.id, e);
+    }
+}
+
+/// Listen on the tunnel's local port and forward every connection over `session`, which the
+/// tunnel owns from here on. Connection counts are reported as `tunnel-updated` events.
+pub fn start(app_handle: AppHandle, session: Session, tunnel: Tunnel) -> Result<TunnelStatus, String> {
+    let id = tunnel.id.ok_or("Tunnel ID is required to start it")?;
+
+    if is_running(id) {
+        return Ok(status(tunnel));
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", tunnel.local_port))
+        .map_err(|e| format!("Failed to listen on local port {}: {}", tunnel.local_port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+    let state = Running {
+        stop: Arc::new(AtomicBool::new(false)),
+        active: Arc::new(AtomicU32::new(0)),
+        total: Arc::new(AtomicU64::new(0)),
+    };
+    let (stop, active, total) = (state.stop.clone(), state.active.clone(), state.total.clone());
+
+    running()?.insert(id, state);
+    if let Ok(mut errors) = LAST_ERRORS.lock() {
+        errors.remove(&id);
+    }
+
+    let thread_tunnel = tunnel.clone();
+    std::thread::spawn(move || {
+        let tunnel = thread_tunnel;
+        let result = forward(&app_handle, &tunnel, &session, &listener, &stop, &active, &total);
+
+        if let Ok(mut running) = running() {
+            if running.get(&id).is_some_and(|current| Arc::ptr_eq(&current.stop, &stop)) {
+                running.remove(&id);
+            }
+        }
+        if let Err(e) = result {
+            eprintln!("Tunnel {} on local port {} stopped: {}", id, tunnel.local_port, e);
+            if let Ok(mut errors) = LAST_ERRORS.lock() {
+                errors.insert(id, e);
+            }
+        }
+
+        let _ = session.disconnect(None, "Tunnel closed", None);
+        emit_status(&app_handle, &tunnel);
+    });
+
+    Ok(status(tunnel))
+}
+
+/// Stop a tunnel, closing its listener and every forwarded connection.
+/// Returns false if it was not running.
+pub fn stop(id: i64) -> Result<bool, String> {
+    match running()?.remove(&id) {
+        Some(state) => {
+            state.stop.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn forward(
+    app_handle: &AppHandle,
+    tunnel: &Tunnel,
+    session: &Session,
+    listener: &TcpListener,
+    stop: &AtomicBool,
+    active: &AtomicU32,
+    total: &AtomicU64,
+) -> Result<(), String> {
+    session.set_blocking(false);
+
+    let mut pipes: Vec<Pipe> = Vec::new();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut idle = Idle::new();
+
+    let result = loop {
+        if stop.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+
+        let mut moved = false;
+        let mut changed = false;
+
+        match listener.accept() {
+            Ok((stream, _)) => {
+                // Opening a channel takes a few round trips, simplest done blocking
+                session.set_blocking(true);
+                let channel = session.channel_direct_tcpip(&tunnel.remote_host, tunnel.remote_port, None);
+                session.set_blocking(false);
+
+                match channel.map_err(|e| e.to_string()).and_then(|channel| Pipe::new(channel, stream)) {
+                    Ok(pipe) => {
+                        pipes.push(pipe);
+                        total.fetch_add(1, Ordering::Relaxed);
+                        changed = true;
+                    }
+                    Err(e) => eprintln!("Tunnel to {}:{} refused a connection: {}", tunnel.remote_host, tunnel.remote_port, e),
+                }
+                moved = true;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => break Err(format!("Local listener failed: {}", e)),
+        }
+
+        let mut i = 0;
+        while i < pipes.len() {
+            match pipes[i].pump(&mut buf) {
+                Ok(Flow::Moved) => {
+                    moved = true;
+                    i += 1;
+                }
+                Ok(Flow::Idle) => i += 1,
+                Ok(Flow::Closed) | Err(_) => {
+                    pipes.swap_remove(i).close();
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            active.store(pipes.len() as u32, Ordering::Relaxed);
+            emit_status(app_handle, tunnel);
+        }
+
+        if moved {
+            idle.reset();
+        } else if let Err(e) = idle.wait(session) {
+            break Err(format!("Connection to the server was lost: {}", e));
+        }
+    };
+
+    session.set_blocking(true);
+    for pipe in pipes {
+        pipe.close();
+    }
+
+    result
+}
@@ -0,0 +1,138 @@
+use super::model::{AuthPrompt, AuthPromptField, Server};
+use once_cell::sync::Lazy;
+use ssh2::{KeyboardInteractivePrompt, Prompt, Session};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How long to wait for the user to answer a prompt. sshd gives up after two minutes by default.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+static NEXT_PROMPT: AtomicU64 = AtomicU64::new(1);
+
+/// Answers to one round of prompts, or `None` if the user cancelled
+type Answers = Option<Vec<String>>;
+
+/// Prompts waiting for an answer from the frontend, by request id
+static PENDING: Lazy<Mutex<HashMap<String, Sender<Answers>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Prompter<'a> {
+    app_handle: Option<&'a AppHandle>,
+    server: &'a Server,
+    /// Answers the first password prompt, so password plus OTP only asks the user for the code
+    password: Option<&'a str>,
+    error: Option<String>,
+}
+
+impl Prompter<'_> {
+    fn ask(&mut self, username: &str, instructions: &str, prompts: Vec<AuthPromptField>) -> Answers {
+        let Some(app_handle) = self.app_handle else {
+            self.error = Some("The server asks for keyboard-interactive input, which this connection can't prompt for".to_string());
+            return None;
+        };
+
+        let request_id = format!("auth-{}-{}", chrono::Local::now().timestamp_millis(), NEXT_PROMPT.fetch_add(1, Ordering::Relaxed));
+        let (sender, receiver) = mpsc::channel();
+
+        PENDING.lock().ok()?.insert(request_id.clone(), sender);
+
+        let prompt = AuthPrompt {
+            request_id: request_id.clone(),
+            server_id: self.server.id.unwrap_or_default(),
+            server_name: self.server.name.clone(),
+            username: username.to_string(),
+            instructions: instructions.to_string(),
+            prompts,
+        };
+
+        let answers = match app_handle.emit("auth-prompt", prompt) {
+            Ok(()) => receiver.recv_timeout(PROMPT_TIMEOUT).unwrap_or_else(|_| {
+                self.error = Some("Timed out waiting for an answer to the authentication prompt".to_string());
+                None
+            }),
+            Err(e) => {
+                self.error = Some(format!("Failed to show authentication prompt: {}", e));
+                None
+            }
+        };
+
+        if let Ok(mut pending) = PENDING.lock() {
+            pending.remove(&request_id);
+        }
+
+        if answers.is_none() && self.error.is_none() {
+            self.error = Some("Authentication was cancelled".to_string());
+        }
+
+        answers
+    }
+}
+
+impl KeyboardInteractivePrompt for Prompter<'_> {
+    fn prompt<'a>(&mut self, username: &str, instructions: &str, prompts: &[Prompt<'a>]) -> Vec<String> {
+        let mut answers: Vec<Option<String>> = vec![None; prompts.len()];
+
+        if let Some(password) = self.password {
+            let password_prompt = prompts.iter()
+                .position(|prompt| !prompt.echo && prompt.text.to_lowercase().contains("password"));
+
+            if let Some(index) = password_prompt {
+                answers[index] = Some(password.to_string());
+                self.password = None;
+            }
+        }
+
+        let unanswered: Vec<usize> = (0..prompts.len()).filter(|&i| answers[i].is_none()).collect();
+
+        // Some servers send a round without prompts just to show a message
+        if !unanswered.is_empty() && self.error.is_none() {
+            let fields = unanswered.iter()
+                .map(|&i| AuthPromptField {
+                    text: prompts[i].text.to_string(),
+                    echo: prompts[i].echo,
+                })
+                .collect();
+
+            if let Some(responses) = self.ask(username, instructions, fields) {
+                for (&i, response) in unanswered.iter().zip(responses) {
+                    answers[i] = Some(response);
+                }
+            }
+        }
+
+        answers.into_iter().map(Option::unwrap_or_default).collect()
+    }
+}
+
+/// Authenticate with keyboard-interactive, relaying every prompt to the frontend as an
+/// `auth-prompt` event. Without an app handle only a password prompt can be answered.
+pub fn authenticate(sess: &Session, server: &Server, app_handle: Option<&AppHandle>, password: Option<&str>) -> Result<(), String> {
+    let mut prompter = Prompter {
+        app_handle,
+        server,
+        password,
+        error: None,
+    };
+
+    let result = sess.userauth_keyboard_interactive(&server.username, &mut prompter);
+
+    if let Some(error) = prompter.error {
+        return Err(error);
+    }
+
+    result.map_err(|e| format!("Keyboard-interactive authentication failed: {}", e))
+}
+
+/// Answer a pending `auth-prompt`, one response per prompt, or `None` to cancel.
+pub fn respond(request_id: &str, responses: Answers) -> Result<(), String> {
+    let sender = PENDING.lock()
+        .map_err(|_| "Failed to acquire prompt registry lock".to_string())?
+        .remove(request_id)
+        .ok_or_else(|| format!("Authentication prompt {} is no longer waiting for an answer", request_id))?;
+
+    sender.send(responses)
+        .map_err(|_| format!("Authentication prompt {} is no longer waiting for an answer", request_id))
+}
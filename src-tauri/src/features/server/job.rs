@@ -0,0 +1,161 @@
+use super::model::{CommandError, CommandResult, JobFinished, JobOutput, OutputStream};
+use super::remote::RemoteCommand;
+use super::service;
+use super::worker::{self, Access};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+static NEXT_JOB: AtomicU64 = AtomicU64::new(1);
+
+/// Cancellation flags of the jobs that are running or waiting for the server, by job id
+static RUNNING: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// A long-running operation on a server whose command output is streamed to the UI as
+/// `job-output` events, followed by a single `job-finished` event.
+pub struct Job {
+    app_handle: AppHandle,
+    id: String,
+    server_id: i64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    /// The frontend passes its own id so it can subscribe before invoking the command.
+    fn new(app_handle: AppHandle, server_id: i64, id: Option<String>) -> Self {
+        let id = id.unwrap_or_else(|| {
+            format!("job-{}-{}", chrono::Local::now().timestamp_millis(), NEXT_JOB.fetch_add(1, Ordering::Relaxed))
+        });
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Ok(mut running) = RUNNING.lock() {
+            running.insert(id.clone(), cancelled.clone());
+        }
+
+        Job { app_handle, id, server_id, cancelled }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn server_id(&self) -> i64 {
+        self.server_id
+    }
+
+    /// Fails once the job has been cancelled, for jobs doing work other than running commands.
+    pub fn check_cancelled(&self) -> Result<(), String> {
+        match self.cancelled.load(Ordering::Relaxed) {
+            true => Err(format!("Job {} was cancelled", self.id)),
+            false => Ok(()),
+        }
+    }
+
+    pub fn cmd(&self, command: impl Into<RemoteCommand>) -> Result<CommandResult, CommandError> {
+        self.cmd_timeout(command, service::DEFAULT_TIMEOUT)
+    }
+
+    /// Run a command as part of the job. Once the job is cancelled, this fails without running anything.
+    pub fn cmd_timeout(&self, command: impl Into<RemoteCommand>, timeout: Duration) -> Result<CommandResult, CommandError> {
+        self.check_cancelled()?;
+
+        service::cmd_streaming(self.server_id, &command.into(), timeout, &self.cancelled, |stream, data| self.emit_output(stream, data))
+    }
+
+    /// Like `service::query`, as part of the job.
+    pub fn query(&self, command: impl Into<RemoteCommand>) -> Result<CommandResult, CommandError> {
+        self.cmd_timeout(command.into().idempotent(), service::QUERY_TIMEOUT)
+    }
+
+    /// Like `service::probe`, as part of the job.
+    pub fn probe(&self, command: impl Into<RemoteCommand>) -> Result<bool, CommandError> {
+        service::exit_ok(self.query(command))
+    }
+
+    /// Report progress that does not come from a remote command.
+    pub fn info(&self, message: &str) {
+        self.emit_output(OutputStream::Info, &format!("{}\n", message));
+    }
+
+    /// Emit a job specific event, such as transfer progress.
+    pub fn emit<P: Serialize + Clone>(&self, event: &str, payload: P) {
+        if let Err(e) = self.app_handle.emit(event, payload) {
+            eprintln!("Failed to emit {} for job {}: {}", event, self.id, e);
+        }
+    }
+
+    fn emit_output(&self, stream: OutputStream, data: &str) {
+        if data.is_empty() {
+            return;
+        }
+
+        let output = JobOutput {
+            job_id: self.id.clone(),
+            server_id: self.server_id,
+            stream,
+            data: data.to_string(),
+        };
+
+        if let Err(e) = self.app_handle.emit("job-output", output) {
+            eprintln!("Failed to emit output for job {}: {}", self.id, e);
+        }
+    }
+
+    fn finish<T, E: Display>(&self, result: &Result<T, E>) {
+        let finished = JobFinished {
+            job_id: self.id.clone(),
+            server_id: self.server_id,
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+
+        if let Err(e) = self.app_handle.emit("job-finished", finished) {
+            eprintln!("Failed to emit completion of job {}: {}", self.id, e);
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let Ok(mut running) = RUNNING.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
+
+/// Stop a running job. Its current command is interrupted and no further commands are run.
+/// Returns false if no job with that id is running.
+pub fn cancel(job_id: &str) -> Result<bool, String> {
+    let running = RUNNING.lock().map_err(|_| "Failed to acquire job registry lock".to_string())?;
+
+    match running.get(job_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Run `f` as a job on the worker pool, so the UI stays responsive and receives its output.
+pub async fn run<T, E, F>(app_handle: AppHandle, server_id: i64, access: Access, job_id: Option<String>, f: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: Display + From<String> + Send + 'static,
+    F: FnOnce(&Job) -> Result<T, E> + Send + 'static,
+{
+    // Registered before waiting for the server, so a job queued behind another can be cancelled
+    let job = Job::new(app_handle, server_id, job_id);
+
+    worker::run(server_id, access, move || {
+        let result = job.check_cancelled().map_err(E::from).and_then(|_| f(&job));
+        job.finish(&result);
+        result
+    })
+    .await
+}
@@ -0,0 +1,213 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use super::model::{ConnectionError, HostKeyMismatch, KnownHost, Server};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, HostKeyType, KnownHostFileKind, MethodType, Session};
+use std::fs;
+
+/// Host key algorithms libssh2 negotiates by default, most preferred first
+const HOST_KEY_METHODS: [&str; 8] = [
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "rsa-sha2-512",
+    "rsa-sha2-256",
+    "ssh-rsa",
+    "ssh-dss",
+];
+
+/// OpenSSH style fingerprint, e.g. `SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU`
+pub fn fingerprint(key: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(key)))
+}
+
+fn key_type_name(key_type: HostKeyType) -> &'static str {
+    match key_type {
+        HostKeyType::Rsa => "ssh-rsa",
+        HostKeyType::Dss => "ssh-dss",
+        HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        HostKeyType::Ed25519 => "ssh-ed25519",
+        HostKeyType::Unknown => "unknown",
+    }
+}
+
+/// The host key the server presented during the handshake, as `(key_type, raw_key)`.
+pub fn session_host_key(sess: &Session) -> Result<(String, Vec<u8>), String> {
+    let (key, key_type) = sess.host_key().ok_or("Server did not present a host key")?;
+
+    Ok((key_type_name(key_type).to_string(), key.to_vec()))
+}
+
+pub fn get_known_host(conn: &Connection, server_id: i64) -> Result<Option<KnownHost>, String> {
+    conn.query_row(
+        "SELECT id, server_id, key_type, key, fingerprint, created_at, updated_at
+         FROM known_hosts WHERE server_id = ?1",
+        params![server_id],
+        |row| Ok(KnownHost {
+            id: Some(row.get(0)?),
+            server_id: row.get(1)?,
+            key_type: row.get(2)?,
+            key: row.get(3)?,
+            fingerprint: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+        })
+    ).optional().map_err(|e| e.to_string())
+}
+
+pub fn save_known_host(conn: &Connection, server_id: i64, key_type: &str, key: &[u8]) -> Result<KnownHost, String> {
+    let now = chrono::Local::now().to_rfc3339();
+
+    conn.execute(
+        "INSERT INTO known_hosts (server_id, key_type, key, fingerprint, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(server_id) DO UPDATE SET
+            key_type = excluded.key_type,
+            key = excluded.key,
+            fingerprint = excluded.fingerprint,
+            updated_at = excluded.updated_at",
+        params![server_id, key_type, STANDARD.encode(key), fingerprint(key), now, now],
+    ).map_err(|e| e.to_string())?;
+
+    get_known_host(conn, server_id)?.ok_or_else(|| "Failed to store host key".to_string())
+}
+
+pub fn delete_known_host(conn: &Connection, server_id: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM known_hosts WHERE server_id = ?1",
+        params![server_id],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// A server usually has several host keys, and only one of them is stored. Offer the stored
+/// key's type first, so the handshake negotiates that key rather than whichever the default
+/// order picks, which would look like a changed key. Call before the handshake.
+pub fn prefer_known_key_type(conn: &Connection, server: &Server, sess: &Session) -> Result<(), String> {
+    let Some(server_id) = server.id else { return Ok(()) };
+    let Some(known) = get_known_host(conn, server_id)? else { return Ok(()) };
+
+    sess.method_pref(MethodType::HostKey, &host_key_preference(&known.key_type))
+        .map_err(|e| format!("Failed to set host key preference: {}", e))
+}
+
+/// The default host key algorithms with those for `key_type` moved to the front. RSA keys
+/// sign with SHA-2 on current servers, which turn `ssh-rsa` itself off.
+fn host_key_preference(key_type: &str) -> String {
+    let preferred: &[&str] = match key_type {
+        "ssh-rsa" => &["rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"],
+        other => &[other],
+    };
+
+    preferred.iter()
+        .copied()
+        .chain(HOST_KEY_METHODS.iter().copied().filter(|method| !preferred.contains(method)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Trust on first use: remember the key the first time a server is seen, and refuse to
+/// continue if it ever changes until the user explicitly accepts the new key.
+pub fn verify_host_key(conn: &Connection, server: &Server, sess: &Session) -> Result<(), ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to verify its host key")?;
+    let (key_type, key) = session_host_key(sess)?;
+    let actual_fingerprint = fingerprint(&key);
+
+    match get_known_host(conn, server_id)? {
+        None => {
+            save_known_host(conn, server_id, &key_type, &key)?;
+            Ok(())
+        }
+        Some(known) if known.fingerprint == actual_fingerprint => Ok(()),
+        Some(known) => Err(ConnectionError::HostKeyMismatch(Box::new(HostKeyMismatch {
+            server_id,
+            hostname: server.hostname.clone(),
+            expected_key_type: known.key_type,
+            expected_fingerprint: known.fingerprint,
+            actual_key_type: key_type,
+            actual_fingerprint,
+        }))),
+    }
+}
+
+/// Import host keys for the given servers from `~/.ssh/known_hosts`. Servers that already have
+/// a stored key are left untouched. Hashed entries are matched through libssh2.
+pub fn import_known_hosts(conn: &Connection, servers: &[Server]) -> Result<Vec<KnownHost>, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
+    let known_hosts_path = home_dir.join(".ssh").join("known_hosts");
+
+    if !known_hosts_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(&known_hosts_path)
+        .map_err(|e| format!("Failed to read known_hosts file: {}", e))?;
+
+    let sess = Session::new().map_err(|e| format!("Failed to create SSH session: {}", e))?;
+    let mut known_hosts = sess.known_hosts().map_err(|e| format!("Failed to init known hosts: {}", e))?;
+    known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH)
+        .map_err(|e| format!("Failed to parse known_hosts file: {}", e))?;
+
+    // (key_type, raw_key) for every plain host key line
+    let entries: Vec<(String, Vec<u8>)> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('@'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _hosts = fields.next()?;
+            let key_type = fields.next()?;
+            let key = STANDARD.decode(fields.next()?).ok()?;
+            Some((key_type.to_string(), key))
+        })
+        .collect();
+
+    let mut imported = Vec::new();
+
+    for server in servers {
+        let Some(server_id) = server.id else { continue };
+
+        if get_known_host(conn, server_id)?.is_some() {
+            continue;
+        }
+
+        let port = u16::try_from(server.port).unwrap_or(22);
+        let matched = entries.iter().find(|(_, key)| {
+            [&server.hostname, &server.ip_address]
+                .iter()
+                .filter(|host| !host.is_empty())
+                .any(|host| matches!(known_hosts.check_port(host, port, key), CheckResult::Match))
+        });
+
+        if let Some((key_type, key)) = matched {
+            imported.push(save_known_host(conn, server_id, key_type, key)?);
+        }
+    }
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_key_type_is_offered_first() {
+        assert_eq!(
+            host_key_preference("ecdsa-sha2-nistp256"),
+            "ecdsa-sha2-nistp256,ssh-ed25519,ecdsa-sha2-nistp384,ecdsa-sha2-nistp521,rsa-sha2-512,rsa-sha2-256,ssh-rsa,ssh-dss"
+        );
+        assert_eq!(host_key_preference("ssh-ed25519"), HOST_KEY_METHODS.join(","));
+    }
+
+    #[test]
+    fn rsa_keys_prefer_sha2_signatures() {
+        assert!(host_key_preference("ssh-rsa").starts_with("rsa-sha2-512,rsa-sha2-256,ssh-rsa,ssh-ed25519,"));
+        assert_eq!(host_key_preference("ssh-rsa").split(',').count(), HOST_KEY_METHODS.len());
+    }
+}
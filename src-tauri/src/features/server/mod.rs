@@ -1,5 +1,27 @@
 pub mod commands;
 pub mod model;
+mod agent;
+mod connect;
+mod distro;
+mod facts;
+mod firewall;
+mod forwarding;
+mod interactive;
+mod job;
+mod known_hosts;
+mod package;
+mod provision;
+mod proxy;
+mod recipe;
+mod remote;
 mod service;
+mod service_manager;
+mod session;
+mod sftp;
+mod ssh_config;
+mod supervisor;
+mod terminal;
+mod tunnel;
+mod worker;
 
 pub use commands::*;
\ No newline at end of file
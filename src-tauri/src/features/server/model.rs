@@ -1,6 +1,6 @@
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Server {
     pub id: Option<i64>,
     pub name: String,
@@ -9,8 +9,619 @@ pub struct Server {
     pub port: i64,
     pub username: String,
     pub ssh_key_id: Option<i64>,
+    #[serde(default)]
+    pub auth_method: AuthMethod,
+    #[serde(default)]
+    pub jump_host_id: Option<i64>,
+    #[serde(default)]
+    pub sudo_mode: SudoMode,
+    /// The distribution detected the last time the server was connected to
+    #[serde(default)]
+    pub os: Option<OsRelease>,
+    /// What was found on the server the last time it was connected to or refreshed
+    #[serde(default)]
+    pub facts: Option<ServerFacts>,
     pub notes: Option<String>,
     pub settings: serde_json::Value,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    #[default]
+    Key,
+    Agent,
+    Password,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Key => "key",
+            AuthMethod::Agent => "agent",
+            AuthMethod::Password => "password",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "agent" => AuthMethod::Agent,
+            "password" => AuthMethod::Password,
+            _ => AuthMethod::Key,
+        }
+    }
+}
+
+/// How privileged commands get root on a server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMode {
+    /// sudo is configured with NOPASSWD
+    #[default]
+    Passwordless,
+    /// sudo asks for the user's password, which is entered once per session
+    Password,
+    /// The server is connected to as root, so no sudo is needed
+    Root,
+}
+
+impl SudoMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SudoMode::Passwordless => "passwordless",
+            SudoMode::Password => "password",
+            SudoMode::Root => "root",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "password" => SudoMode::Password,
+            "root" => SudoMode::Root,
+            _ => SudoMode::Passwordless,
+        }
+    }
+}
+
+/// A server's distribution, as described by its `/etc/os-release`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OsRelease {
+    /// `ID`, such as `almalinux`, `ubuntu` or `opensuse-leap`
+    pub id: String,
+    /// `ID_LIKE`, the distributions this one is derived from
+    pub id_like: Vec<String>,
+    /// `VERSION_ID`, such as `9.4` or `24.04`
+    pub version_id: String,
+    /// `PRETTY_NAME`, for display
+    pub name: String,
+}
+
+/// A snapshot of a server's hardware and software, gathered on connect
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerFacts {
+    pub kernel: String,
+    /// `uname -m`, such as `x86_64` or `aarch64`
+    pub arch: String,
+    pub cpus: u32,
+    /// Total RAM in bytes
+    pub memory: u64,
+    pub disks: Vec<Disk>,
+    /// Addresses the server is reachable at from the internet
+    pub public_ips: Vec<String>,
+    /// Installed web servers, such as `nginx` or `apache`
+    pub web_servers: Vec<String>,
+    /// Installed database servers, such as `mariadb` or `postgresql`
+    pub database_servers: Vec<String>,
+    /// `enforcing`, `permissive` or `disabled`; `None` without SELinux
+    pub selinux: Option<String>,
+    pub gathered_at: String,
+}
+
+/// A mounted filesystem, sizes in bytes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Disk {
+    pub device: String,
+    pub filesystem: String,
+    pub mount_point: String,
+    pub size: u64,
+    pub used: u64,
+}
+
+/// A package installed on a server, or an update available for one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+}
+
+/// What to do with a service on a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    /// Start on boot
+    Enable,
+    /// Don't start on boot
+    Disable,
+}
+
+impl ServiceAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceAction::Start => "start",
+            ServiceAction::Stop => "stop",
+            ServiceAction::Restart => "restart",
+            ServiceAction::Reload => "reload",
+            ServiceAction::Enable => "enable",
+            ServiceAction::Disable => "disable",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    /// As reported by the service manager: `active`, `inactive`, `failed`, ...
+    pub state: String,
+    pub active: bool,
+    /// Whether the service starts on boot
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgentIdentity {
+    pub comment: String,
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Reconnecting,
+}
+
+/// Payload of the `connection-state` event, sent whenever a server's pooled session comes or goes.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionEvent {
+    pub server_id: i64,
+    pub state: ConnectionState,
+    /// Which reconnect attempt this is, counting from 1
+    pub attempt: Option<u32>,
+    /// Why the connection was lost or the last reconnect attempt failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub server_id: i64,
+    pub connected_at: String,
+    pub idle_seconds: u64,
+    pub busy: bool,
+}
+
+/// Outcome of a remote command. Interrupted commands keep the output they produced so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub command: String,
+    pub stdout: String,
+    pub stderr: String,
+    /// None if the command was interrupted before it exited
+    pub exit_code: Option<i32>,
+    /// Wall time in milliseconds
+    pub duration: u64,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// Why a remote command did not succeed. `message` describes the failure, prefixed with
+/// what the caller was trying to do.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    /// The command could not be run at all, e.g. no session or no channel
+    Failed { message: String },
+    /// The command ran and exited with a non-zero status
+    Exited { message: String, result: CommandResult },
+    TimedOut { message: String, result: CommandResult },
+    Cancelled { message: String, result: CommandResult },
+    /// The server's sudo mode is `password` and no valid sudo password is known for the session
+    SudoPasswordRequired { message: String, server_id: i64 },
+    /// The connection was lost. `result` is None if the command never started.
+    Disconnected { message: String, server_id: i64, result: Option<CommandResult> },
+}
+
+impl CommandError {
+    pub fn message(&self) -> &str {
+        match self {
+            CommandError::Failed { message }
+            | CommandError::Exited { message, .. }
+            | CommandError::TimedOut { message, .. }
+            | CommandError::Cancelled { message, .. }
+            | CommandError::SudoPasswordRequired { message, .. }
+            | CommandError::Disconnected { message, .. } => message,
+        }
+    }
+
+    pub fn result(&self) -> Option<&CommandResult> {
+        match self {
+            CommandError::Failed { .. } | CommandError::SudoPasswordRequired { .. } => None,
+            CommandError::Exited { result, .. }
+            | CommandError::TimedOut { result, .. }
+            | CommandError::Cancelled { result, .. } => Some(result),
+            CommandError::Disconnected { result, .. } => result.as_ref(),
+        }
+    }
+
+    /// Prefix the message with what was being done, e.g. "Failed to reload Nginx".
+    pub fn context(mut self, context: impl std::fmt::Display) -> Self {
+        match &mut self {
+            CommandError::Failed { message }
+            | CommandError::Exited { message, .. }
+            | CommandError::TimedOut { message, .. }
+            | CommandError::Cancelled { message, .. }
+            | CommandError::SudoPasswordRequired { message, .. }
+            | CommandError::Disconnected { message, .. } => *message = format!("{}: {}", context, message),
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())?;
+
+        match self.result().map(|result| result.stderr.trim()) {
+            Some(stderr) if !stderr.is_empty() => write!(f, "\n{}", stderr),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Failed { message }
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        CommandError::Failed { message: message.to_string() }
+    }
+}
+
+impl From<CommandError> for String {
+    fn from(error: CommandError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Payload of the `auth-prompt` event, answered with `respond_auth_prompt`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthPrompt {
+    pub request_id: String,
+    pub server_id: i64,
+    pub server_name: String,
+    pub username: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPromptField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthPromptField {
+    pub text: String,
+    /// Whether the answer may be shown while typing, false for passwords and codes
+    pub echo: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+    /// Progress messages from the job itself rather than the remote command
+    Info,
+}
+
+/// Payload of the `job-output` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobOutput {
+    pub job_id: String,
+    pub server_id: i64,
+    pub stream: OutputStream,
+    pub data: String,
+}
+
+/// Payload of the `job-finished` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobFinished {
+    pub job_id: String,
+    pub server_id: i64,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalInfo {
+    pub id: String,
+    pub server_id: i64,
+    pub cols: u32,
+    pub rows: u32,
+    pub opened_at: String,
+}
+
+/// Payload of the `terminal-output` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalOutput {
+    pub terminal_id: String,
+    pub server_id: i64,
+    pub data: String,
+}
+
+/// Payload of the `terminal-closed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalClosed {
+    pub terminal_id: String,
+    pub server_id: i64,
+    pub exit_status: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RemoteFile {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: u64,
+    /// Permission bits, e.g. `0o644`
+    pub mode: u32,
+    /// `ls -l` style, e.g. `drwxr-xr-x`
+    pub permissions: String,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// Payload of the `transfer-progress` event, emitted after every chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferProgress {
+    pub job_id: String,
+    pub server_id: i64,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    pub transferred: u64,
+    pub total: u64,
+}
+
+/// A step of provisioning a server, as offered to the user before running it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvisionStep {
+    pub id: String,
+    pub name: String,
+    /// Whether the user may leave the step out
+    pub optional: bool,
+    /// The step this one needs, which leaving out leaves this one out too
+    pub requires: Option<String>,
+}
+
+/// A recipe as listed for the user to pick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeInfo {
+    pub name: String,
+    pub description: String,
+    /// The file it was loaded from, or None if it is bundled with the app
+    pub path: Option<String>,
+    pub steps: Vec<ProvisionStep>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepState {
+    /// Checking whether the server is already in the state the step brings it to
+    Checking,
+    Applying,
+    /// Applied in this run or an earlier one
+    Done,
+    /// Already in place, so nothing was changed
+    Satisfied,
+    Skipped,
+    Failed,
+}
+
+impl StepState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepState::Checking => "checking",
+            StepState::Applying => "applying",
+            StepState::Done => "done",
+            StepState::Satisfied => "satisfied",
+            StepState::Skipped => "skipped",
+            StepState::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "checking" => StepState::Checking,
+            "applying" => StepState::Applying,
+            "done" => StepState::Done,
+            "satisfied" => StepState::Satisfied,
+            "skipped" => StepState::Skipped,
+            _ => StepState::Failed,
+        }
+    }
+
+    /// Whether a resumed run can leave the step alone
+    pub fn is_finished(&self) -> bool {
+        matches!(self, StepState::Done | StepState::Satisfied | StepState::Skipped)
+    }
+}
+
+/// Payload of the `provision-step` event, emitted whenever a step changes state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvisionStepEvent {
+    pub job_id: String,
+    pub server_id: i64,
+    pub plan: String,
+    pub step: String,
+    pub name: String,
+    pub state: StepState,
+    pub error: Option<String>,
+}
+
+/// Where a step got to in the last run of a plan on a server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepProgress {
+    pub step: String,
+    pub state: StepState,
+    pub error: Option<String>,
+    pub updated_at: String,
+}
+
+/// A local port forwarded to `remote_host:remote_port` as seen from the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tunnel {
+    pub id: Option<i64>,
+    pub server_id: i64,
+    pub local_port: u16,
+    pub remote_host: String,
+    pub remote_port: u16,
+    /// Start again whenever the server is connected
+    pub auto_restore: bool,
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelStatus {
+    #[serde(flatten)]
+    pub tunnel: Tunnel,
+    pub running: bool,
+    pub active_connections: u32,
+    pub total_connections: u64,
+    /// Why the tunnel last stopped, if it did not stop on request
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    Socks5,
+    Http,
+}
+
+impl ProxyKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "socks5" => Some(ProxyKind::Socks5),
+            "http" => Some(ProxyKind::Http),
+            _ => None,
+        }
+    }
+}
+
+/// Proxy used to reach a server. Set globally in the advanced settings, or per server under
+/// `proxy` in the server settings JSON, where `"none"` connects directly regardless of the global one.
+/// Passwords are encrypted secret settings, never part of the JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(rename = "type")]
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// A host from `~/.ssh/config`, previewed as the server it would be imported as
+#[derive(Debug, Serialize)]
+pub struct SshConfigHost {
+    pub alias: String,
+    pub server: Server,
+    /// The first IdentityFile that exists, linked to `server.ssh_key_id` if the key is stored
+    pub identity_file: Option<String>,
+    /// Resolved into `server.jump_host_id` when its last hop is a known server
+    pub proxy_jump: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KnownHost {
+    pub id: Option<i64>,
+    pub server_id: i64,
+    pub key_type: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HostKeyMismatch {
+    pub server_id: i64,
+    pub hostname: String,
+    pub expected_key_type: String,
+    pub expected_fingerprint: String,
+    pub actual_key_type: String,
+    pub actual_fingerprint: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConnectionError {
+    HostKeyMismatch(Box<HostKeyMismatch>),
+    PassphraseRequired {
+        server_id: i64,
+        ssh_key_id: i64,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionError::HostKeyMismatch(mismatch) => write!(
+                f,
+                "Host key for {} has changed (expected {}, got {})",
+                mismatch.hostname, mismatch.expected_fingerprint, mismatch.actual_fingerprint
+            ),
+            ConnectionError::PassphraseRequired { ssh_key_id, .. } => write!(f, "SSH key {} is encrypted and needs a passphrase", ssh_key_id),
+            ConnectionError::Failed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for ConnectionError {
+    fn from(message: String) -> Self {
+        ConnectionError::Failed { message }
+    }
+}
+
+impl From<&str> for ConnectionError {
+    fn from(message: &str) -> Self {
+        ConnectionError::Failed { message: message.to_string() }
+    }
+}
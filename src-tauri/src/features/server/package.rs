@@ -0,0 +1,405 @@
+use super::model::{OsRelease, Package};
+use super::remote::{quote, RemoteCommand};
+
+/// The package manager of a server's distribution. Every operation is a command to run; the
+/// caller decides the timeout, as installs can take a long time on slow mirrors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    /// RHEL, AlmaLinux, Rocky and Fedora
+    Dnf,
+    /// Debian and Ubuntu
+    Apt,
+    /// openSUSE and SLES
+    Zypper,
+}
+
+/// Third-party repositories the app installs packages from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repository {
+    /// Extra Packages for Enterprise Linux, which Remi depends on
+    Epel,
+    /// Remi's RPM repository, with every supported PHP version as a dnf module stream
+    Remi,
+    /// Ondřej Surý's PHP builds: the ondrej/php PPA on Ubuntu, packages.sury.org on Debian
+    OndrejPhp,
+}
+
+impl Repository {
+    /// As written in recipes
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "epel" => Some(Repository::Epel),
+            "remi" => Some(Repository::Remi),
+            "ondrej_php" => Some(Repository::OndrejPhp),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Repository::Epel => "EPEL",
+            Repository::Remi => "Remi",
+            Repository::OndrejPhp => "ondrej/php",
+        }
+    }
+}
+
+/// Keeps apt and debconf from stopping to ask questions, which no one could answer
+const APT_GET: [&str; 3] = ["env", "DEBIAN_FRONTEND=noninteractive", "apt-get"];
+
+impl PackageManager {
+    pub fn for_os(os: &OsRelease) -> Result<Self, String> {
+        if os.is_rhel_family() {
+            Ok(PackageManager::Dnf)
+        } else if os.is_debian_family() {
+            Ok(PackageManager::Apt)
+        } else if os.is_suse_family() {
+            Ok(PackageManager::Zypper)
+        } else {
+            Err(format!("{} is not supported: no dnf, apt or zypper", display_name(os)))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Dnf => "dnf",
+            PackageManager::Apt => "apt",
+            PackageManager::Zypper => "zypper",
+        }
+    }
+
+    fn program(&self) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf => RemoteCommand::new("dnf"),
+            PackageManager::Apt => RemoteCommand::new(APT_GET[0]).args(&APT_GET[1..]),
+            PackageManager::Zypper => RemoteCommand::new("zypper").arg("--non-interactive"),
+        }
+    }
+
+    fn sudo(&self) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf => RemoteCommand::sudo("dnf"),
+            PackageManager::Apt => RemoteCommand::sudo(APT_GET[0]).args(&APT_GET[1..]),
+            PackageManager::Zypper => RemoteCommand::sudo("zypper").arg("--non-interactive"),
+        }
+    }
+
+    pub fn install<I, S>(&self, packages: I) -> RemoteCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        match self {
+            PackageManager::Dnf | PackageManager::Apt => self.sudo().args(["install", "-y"]).args(packages),
+            PackageManager::Zypper => self.sudo().arg("install").args(packages),
+        }
+    }
+
+    pub fn remove<I, S>(&self, packages: I) -> RemoteCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        match self {
+            PackageManager::Dnf | PackageManager::Apt => self.sudo().args(["remove", "-y"]).args(packages),
+            PackageManager::Zypper => self.sudo().arg("remove").args(packages),
+        }
+    }
+
+    /// A check that succeeds if all the packages are installed. dpkg keeps removed packages
+    /// whose configuration was left behind, so their status is compared instead.
+    pub fn is_installed(&self, packages: &[&str]) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf | PackageManager::Zypper => RemoteCommand::new("rpm").arg("-q").args(packages),
+            PackageManager::Apt => RemoteCommand::shell(&format!(
+                "[ \"$(dpkg-query -W -f '${{db:Status-Abbrev}}\\n' {} 2>/dev/null | grep -c '^ii')\" -eq {} ]",
+                packages.iter().map(|package| quote(package)).collect::<Vec<_>>().join(" "),
+                packages.len()
+            )),
+        }
+    }
+
+    /// Refresh the package lists and upgrade everything installed
+    pub fn upgrade(&self) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf => self.sudo().args(["update", "-y"]),
+            PackageManager::Apt => self.program().arg("update").and(self.program().args(["upgrade", "-y"])).elevated(),
+            PackageManager::Zypper => self.sudo().arg("update"),
+        }
+    }
+
+    /// Print the installed packages, one `name<TAB>version` per line
+    pub fn list_installed(&self) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf | PackageManager::Zypper => RemoteCommand::new("rpm").args(["-qa", "--qf", "%{NAME}\\t%{VERSION}-%{RELEASE}\\n"]),
+            PackageManager::Apt => RemoteCommand::new("dpkg-query").args(["-W", "-f", "${Package}\\t${Version}\\t${db:Status-Abbrev}\\n"]),
+        }
+    }
+
+    pub fn parse_installed(&self, output: &str) -> Vec<Package> {
+        let mut packages: Vec<Package> = output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let name = fields.next()?.trim();
+                let version = fields.next()?.trim();
+
+                // dpkg also lists removed packages that left configuration behind
+                if *self == PackageManager::Apt && !fields.next().is_some_and(|status| status.starts_with("ii")) {
+                    return None;
+                }
+
+                (!name.is_empty()).then(|| Package { name: name.to_string(), version: version.to_string() })
+            })
+            .collect();
+
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages
+    }
+
+    /// List the packages with an update available. dnf exits with 100 when there are some,
+    /// and apt only knows about updates as of the last refresh.
+    pub fn check_updates(&self) -> RemoteCommand {
+        match self {
+            PackageManager::Dnf => RemoteCommand::shell("dnf -q check-update; [ $? -ne 1 ]"),
+            PackageManager::Apt => RemoteCommand::shell("apt list --upgradable 2>/dev/null"),
+            PackageManager::Zypper => RemoteCommand::new("zypper").args(["-q", "--non-interactive", "list-updates"]),
+        }
+    }
+
+    /// Parse the output of `check_updates` into the packages and the versions available
+    pub fn parse_updates(&self, output: &str) -> Vec<Package> {
+        output
+            .lines()
+            .filter_map(|line| match self {
+                // `name.arch  version  repository`; wrapped lines start with whitespace
+                PackageManager::Dnf => {
+                    if line.starts_with(char::is_whitespace) {
+                        return None;
+                    }
+                    let mut fields = line.split_whitespace();
+                    let (name, version, _repository) = (fields.next()?, fields.next()?, fields.next()?);
+                    let name = name.rsplit_once('.').map_or(name, |(name, _arch)| name);
+                    Some(Package { name: name.to_string(), version: version.to_string() })
+                }
+                // `name/suite version arch [upgradable from: old]`
+                PackageManager::Apt => {
+                    let (name, rest) = line.split_once('/')?;
+                    let version = rest.split_whitespace().nth(1)?;
+                    Some(Package { name: name.to_string(), version: version.to_string() })
+                }
+                // `v | repository | name | current | available | arch`
+                PackageManager::Zypper => {
+                    let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+                    if fields.len() < 5 || fields[0] != "v" {
+                        return None;
+                    }
+                    Some(Package { name: fields[2].to_string(), version: fields[4].to_string() })
+                }
+            })
+            .collect()
+    }
+
+    /// A check that succeeds if the repository is already set up
+    pub fn has_repository(&self, repository: Repository) -> RemoteCommand {
+        match repository {
+            Repository::Epel => RemoteCommand::shell("dnf repolist | grep -q epel"),
+            Repository::Remi => RemoteCommand::shell("dnf repolist | grep -q remi"),
+            Repository::OndrejPhp => RemoteCommand::shell("grep -rqsE 'ondrej/php|packages.sury.org/php' /etc/apt/sources.list /etc/apt/sources.list.d"),
+        }
+    }
+
+    /// Set up a repository and refresh the package lists where that isn't automatic
+    pub fn add_repository(&self, repository: Repository, os: &OsRelease) -> Result<RemoteCommand, String> {
+        let unavailable = || format!("The {} repository is not available for {}", repository.name(), display_name(os));
+
+        match (self, repository) {
+            (PackageManager::Dnf, Repository::Epel) if os.id != "fedora" => Ok(self.install(["epel-release"])),
+            (PackageManager::Dnf, Repository::Remi) => {
+                let release = match os.id.as_str() {
+                    "fedora" => format!("https://rpms.remirepo.net/fedora/remi-release-{}.rpm", os.major_version()),
+                    _ => format!("https://rpms.remirepo.net/enterprise/remi-release-{}.rpm", os.major_version()),
+                };
+                Ok(self.install([release]))
+            }
+            (PackageManager::Apt, Repository::OndrejPhp) if os.is_like("ubuntu") => Ok(self
+                .program().args(["install", "-y", "software-properties-common"])
+                .and(RemoteCommand::new("add-apt-repository").args(["-y", "ppa:ondrej/php"]))
+                .and(self.program().arg("update"))
+                .elevated()),
+            // Debian has no PPAs; the same builds are published at packages.sury.org
+            (PackageManager::Apt, Repository::OndrejPhp) => Ok(self
+                .program().args(["install", "-y", "curl", "ca-certificates", "lsb-release"])
+                .and(RemoteCommand::shell("curl -sSL https://packages.sury.org/php/README.txt | bash -x"))
+                .and(self.program().arg("update"))
+                .elevated()),
+            _ => Err(unavailable()),
+        }
+    }
+
+    /// The repositories PHP versions other than the distribution's own come from
+    pub fn php_repositories(&self, os: &OsRelease) -> Vec<Repository> {
+        match self {
+            PackageManager::Dnf if os.id == "fedora" => vec![Repository::Remi],
+            PackageManager::Dnf => vec![Repository::Epel, Repository::Remi],
+            PackageManager::Apt => vec![Repository::OndrejPhp],
+            PackageManager::Zypper => vec![],
+        }
+    }
+
+    /// PHP with the extensions applications commonly need. With dnf the version is picked by
+    /// enabling its module stream first; openSUSE ships a single PHP per major version.
+    pub fn php_packages(&self, version: &str) -> Vec<String> {
+        let (prefix, extensions): (String, &[&str]) = match self {
+            PackageManager::Dnf => ("php".to_string(), &[
+                "fpm", "common", "mysqlnd", "xml", "curl", "gd", "imagick", "cli", "devel", "imap",
+                "mbstring", "opcache", "soap", "zip", "intl",
+            ]),
+            PackageManager::Apt => (format!("php{}", version), &[
+                "fpm", "common", "mysql", "xml", "curl", "gd", "imagick", "cli", "dev", "imap",
+                "mbstring", "opcache", "soap", "zip", "intl",
+            ]),
+            PackageManager::Zypper => (format!("php{}", major(version)), &[
+                "fpm", "mysql", "curl", "gd", "devel", "mbstring", "opcache", "soap", "zip", "intl",
+            ]),
+        };
+
+        std::iter::once(prefix.clone())
+            .chain(extensions.iter().map(|extension| format!("{}-{}", prefix, extension)))
+            .collect()
+    }
+
+    /// Whether an installed package belongs to the given PHP version
+    pub fn is_php_package(&self, name: &str, version: &str) -> bool {
+        let prefix = match self {
+            PackageManager::Dnf => "php".to_string(),
+            PackageManager::Apt => format!("php{}", version),
+            PackageManager::Zypper => format!("php{}", major(version)),
+        };
+
+        name == prefix || name.starts_with(&format!("{}-", prefix))
+    }
+
+    /// The PHP command line of a version; apt installs one per version next to `php`
+    pub fn php_binary(&self, version: &str) -> String {
+        match self {
+            PackageManager::Apt => format!("php{}", version),
+            PackageManager::Dnf | PackageManager::Zypper => "php".to_string(),
+        }
+    }
+
+    /// The systemd unit of PHP-FPM; apt installs one per version
+    pub fn php_fpm_service(&self, version: &str) -> String {
+        match self {
+            PackageManager::Apt => format!("php{}-fpm", version),
+            PackageManager::Dnf | PackageManager::Zypper => "php-fpm".to_string(),
+        }
+    }
+
+    /// Where PHP-FPM reads pool configurations from; apt and zypper keep one per version
+    pub fn php_fpm_pool_dir(&self, version: &str) -> String {
+        match self {
+            PackageManager::Dnf => "/etc/php-fpm.d".to_string(),
+            PackageManager::Apt => format!("/etc/php/{}/fpm/pool.d", version),
+            PackageManager::Zypper => format!("/etc/php{}/fpm/php-fpm.d", major(version)),
+        }
+    }
+
+    /// The socket an application's pool listens on. openSUSE has no runtime directory for
+    /// PHP-FPM, so the socket goes straight into /run.
+    pub fn php_fpm_socket(&self, version: &str, app_name: &str) -> String {
+        match self {
+            PackageManager::Dnf => format!("/run/php-fpm/{}.sock", app_name),
+            PackageManager::Apt => format!("/run/php/php{}-fpm-{}.sock", version, app_name),
+            PackageManager::Zypper => format!("/run/php-fpm-{}.sock", app_name),
+        }
+    }
+
+    /// The MariaDB server and client
+    pub fn mariadb_packages(&self) -> [&'static str; 2] {
+        match self {
+            PackageManager::Dnf => ["mariadb-server", "mariadb"],
+            PackageManager::Apt => ["mariadb-server", "mariadb-client"],
+            PackageManager::Zypper => ["mariadb", "mariadb-client"],
+        }
+    }
+}
+
+fn major(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
+
+fn display_name(os: &OsRelease) -> &str {
+    if os.name.is_empty() { &os.id } else { &os.name }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_package_manager_by_family() {
+        let os = |id: &str, id_like: &[&str]| OsRelease {
+            id: id.to_string(),
+            id_like: id_like.iter().map(|like| like.to_string()).collect(),
+            ..OsRelease::default()
+        };
+
+        assert_eq!(PackageManager::for_os(&os("almalinux", &["rhel", "centos", "fedora"])), Ok(PackageManager::Dnf));
+        assert_eq!(PackageManager::for_os(&os("linuxmint", &["ubuntu", "debian"])), Ok(PackageManager::Apt));
+        assert_eq!(PackageManager::for_os(&os("opensuse-tumbleweed", &[])), Ok(PackageManager::Zypper));
+        assert!(PackageManager::for_os(&os("arch", &[])).is_err());
+    }
+
+    #[test]
+    fn dpkg_lists_only_installed_packages() {
+        let output = "php8.3-cli\t8.3.6-0ubuntu0.24.04.1\tii \nnginx\t1.24.0-2ubuntu7\tii \nphp8.1-fpm\t8.1.2\trc \n";
+        let packages = PackageManager::Apt.parse_installed(output);
+
+        let names: Vec<&str> = packages.iter().map(|package| package.name.as_str()).collect();
+        assert_eq!(names, ["nginx", "php8.3-cli"]);
+        assert_eq!(packages[1].version, "8.3.6-0ubuntu0.24.04.1");
+    }
+
+    #[test]
+    fn dnf_updates_skip_wrapped_lines() {
+        let output = "\nkernel.x86_64    5.14.0-427.el9    baseos\nvery-long-package-name.noarch\n    1.2-3.el9    appstream\n";
+        let updates = PackageManager::Dnf.parse_updates(output);
+
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].name, "kernel");
+        assert_eq!(updates[0].version, "5.14.0-427.el9");
+    }
+
+    #[test]
+    fn apt_and_zypper_updates() {
+        let apt = PackageManager::Apt.parse_updates("Listing...\nnginx/noble-updates 1.24.0-2ubuntu7.1 amd64 [upgradable from: 1.24.0-2ubuntu7]\n");
+        assert_eq!((apt[0].name.as_str(), apt[0].version.as_str()), ("nginx", "1.24.0-2ubuntu7.1"));
+
+        let zypper = PackageManager::Zypper.parse_updates("S | Repository | Name | Current Version | Available Version | Arch\n--+---\nv | Main | nginx | 1.25.3-1.1 | 1.25.4-1.1 | x86_64\n");
+        assert_eq!(zypper.len(), 1);
+        assert_eq!((zypper[0].name.as_str(), zypper[0].version.as_str()), ("nginx", "1.25.4-1.1"));
+    }
+
+    #[test]
+    fn php_naming_per_package_manager() {
+        assert_eq!(PackageManager::Apt.php_packages("8.3")[0], "php8.3");
+        assert!(PackageManager::Apt.php_packages("8.3").contains(&"php8.3-fpm".to_string()));
+        assert_eq!(PackageManager::Zypper.php_packages("8.3")[0], "php8");
+        assert!(PackageManager::Dnf.is_php_package("php-fpm", "8.3"));
+        assert!(PackageManager::Apt.is_php_package("php8.3-fpm", "8.3"));
+        assert!(!PackageManager::Apt.is_php_package("php8.30-fpm", "8.3"));
+        assert!(!PackageManager::Apt.is_php_package("phpmyadmin", "8.3"));
+    }
+
+    #[test]
+    fn php_fpm_locations() {
+        assert_eq!(PackageManager::Apt.php_fpm_service("8.3"), "php8.3-fpm");
+        assert_eq!(PackageManager::Apt.php_fpm_pool_dir("8.3"), "/etc/php/8.3/fpm/pool.d");
+        assert_eq!(PackageManager::Apt.php_fpm_socket("8.3", "shop"), "/run/php/php8.3-fpm-shop.sock");
+        assert_eq!(PackageManager::Dnf.php_fpm_service("8.3"), "php-fpm");
+        assert_eq!(PackageManager::Dnf.php_fpm_pool_dir("8.3"), "/etc/php-fpm.d");
+        assert_eq!(PackageManager::Dnf.php_fpm_socket("8.3", "shop"), "/run/php-fpm/shop.sock");
+        assert_eq!(PackageManager::Zypper.php_fpm_pool_dir("8.3"), "/etc/php8/fpm/php-fpm.d");
+    }
+}
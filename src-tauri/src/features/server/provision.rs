@@ -0,0 +1,218 @@
+use super::distro;
+use super::job::Job;
+use super::model::{CommandError, OsRelease, ProvisionStepEvent, StepProgress, StepState};
+use super::package::PackageManager;
+use super::service_manager::ServiceManager;
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
+
+/// What steps run with: the job they are part of and how the server is managed
+pub struct Context<'a> {
+    pub job: &'a Job,
+    pub os: OsRelease,
+    pub packages: PackageManager,
+    pub services: ServiceManager,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(job: &'a Job) -> Result<Self, CommandError> {
+        let os = distro::of(job.server_id())?;
+        let packages = PackageManager::for_os(&os)?;
+        let services = ServiceManager::of(job.server_id())?;
+
+        Ok(Context { job, os, packages, services })
+    }
+}
+
+type Check = Box<dyn Fn(&Context) -> Result<bool, CommandError>>;
+type Apply = Box<dyn Fn(&Context) -> Result<(), CommandError>>;
+
+/// A named step of a plan. `check` tells whether the server is already in the state `apply`
+/// brings it to, so running the step again changes nothing.
+pub struct Step {
+    pub id: String,
+    pub name: String,
+    pub optional: bool,
+    pub requires: Option<String>,
+    check: Check,
+    apply: Apply,
+}
+
+impl Step {
+    pub fn new<C, A>(id: &str, name: &str, check: C, apply: A) -> Self
+    where
+        C: Fn(&Context) -> Result<bool, CommandError> + 'static,
+        A: Fn(&Context) -> Result<(), CommandError> + 'static,
+    {
+        Step {
+            id: id.to_string(),
+            name: name.to_string(),
+            optional: false,
+            requires: None,
+            check: Box::new(check),
+            apply: Box::new(apply),
+        }
+    }
+
+    /// Let the user leave the step out
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    /// Leave the step out along with the one it needs
+    pub fn requires(mut self, step: &str) -> Self {
+        self.requires = Some(step.to_string());
+        self
+    }
+}
+
+/// Run the steps of `plan` in order, leaving out the optional ones in `skip`. If the last run
+/// of the plan didn't get to the end, the steps it finished are left alone and the run resumes
+/// from the one that failed.
+pub fn run(conn: &Connection, ctx: &Context, plan: &str, steps: &[Step], skip: &[String]) -> Result<Vec<String>, CommandError> {
+    let server_id = ctx.job.server_id();
+    let skipped = skipped_steps(steps, skip)?;
+
+    let previous: HashMap<String, StepState> = get_progress(conn, server_id, plan)?
+        .into_iter()
+        .map(|progress| (progress.step, progress.state))
+        .collect();
+
+    let resuming = previous.values().any(|state| !state.is_finished());
+    if resuming {
+        ctx.job.info(&format!("Resuming the previous run of {}", plan));
+    } else {
+        clear_progress(conn, server_id, plan)?;
+    }
+
+    let mut log = Vec::new();
+
+    for step in steps {
+        ctx.job.check_cancelled()?;
+
+        let update = |state: StepState, error: Option<String>| update(conn, ctx.job, plan, step, state, error);
+
+        if skipped.contains(step.id.as_str()) {
+            update(StepState::Skipped, None)?;
+            log.push(format!("{}: skipped", step.name));
+            continue;
+        }
+
+        // Skipped steps are chosen anew on each run
+        if let Some(state) = previous.get(&step.id).filter(|state| resuming && **state != StepState::Skipped && state.is_finished()) {
+            update(*state, None)?;
+            log.push(format!("{}: finished in the previous run", step.name));
+            continue;
+        }
+
+        ctx.job.info(&format!("{}...", step.name));
+        update(StepState::Checking, None)?;
+
+        let result = (step.check)(ctx).and_then(|satisfied| {
+            if satisfied {
+                return Ok(StepState::Satisfied);
+            }
+
+            update(StepState::Applying, None)?;
+            (step.apply)(ctx)?;
+            Ok(StepState::Done)
+        });
+
+        match result {
+            Ok(state) => {
+                update(state, None)?;
+                log.push(match state {
+                    StepState::Satisfied => format!("{}: already in place", step.name),
+                    _ => format!("{}: done", step.name),
+                });
+            }
+            Err(e) => {
+                update(StepState::Failed, Some(e.to_string()))?;
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(log)
+}
+
+/// The steps to leave out: those asked for, and the steps needing any of them. A step can
+/// only require one that comes before it.
+fn skipped_steps<'a>(steps: &'a [Step], skip: &[String]) -> Result<HashSet<&'a str>, String> {
+    for id in skip {
+        match steps.iter().find(|step| &step.id == id) {
+            Some(step) if step.optional => {}
+            Some(step) => return Err(format!("{} can't be skipped", step.name)),
+            None => return Err(format!("Unknown step: {}", id)),
+        }
+    }
+
+    let mut skipped = HashSet::new();
+    for step in steps {
+        let required_skipped = step.requires.as_deref().is_some_and(|required| skipped.contains(required));
+
+        if skip.contains(&step.id) || required_skipped {
+            skipped.insert(step.id.as_str());
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Store a step's state and tell the UI
+fn update(conn: &Connection, job: &Job, plan: &str, step: &Step, state: StepState, error: Option<String>) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO provision_progress (server_id, plan, step, state, error, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![job.server_id(), plan, step.id, state.as_str(), error, chrono::Local::now().to_rfc3339()],
+    ).map_err(|e| e.to_string())?;
+
+    job.emit("provision-step", ProvisionStepEvent {
+        job_id: job.id().to_string(),
+        server_id: job.server_id(),
+        plan: plan.to_string(),
+        step: step.id.clone(),
+        name: step.name.clone(),
+        state,
+        error,
+    });
+
+    Ok(())
+}
+
+/// Where each step got to in the last run of a plan on a server
+pub fn get_progress(conn: &Connection, server_id: i64, plan: &str) -> Result<Vec<StepProgress>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT step, state, error, updated_at FROM provision_progress WHERE server_id = ?1 AND plan = ?2"
+    ).map_err(|e| e.to_string())?;
+
+    let progress = stmt.query_map(params![server_id, plan], |row| {
+        Ok(StepProgress {
+            step: row.get(0)?,
+            state: StepState::parse(&row.get::<_, String>(1)?),
+            error: row.get(2)?,
+            updated_at: row.get(3)?,
+        })
+    }).map_err(|e| e.to_string())?;
+
+    progress.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Forget the last run of a plan, so the next one starts from the first step
+pub fn clear_progress(conn: &Connection, server_id: i64, plan: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM provision_progress WHERE server_id = ?1 AND plan = ?2",
+        params![server_id, plan],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+pub fn delete_server_progress(conn: &Connection, server_id: i64) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM provision_progress WHERE server_id = ?1",
+        params![server_id],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
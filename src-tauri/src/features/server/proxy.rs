@@ -0,0 +1,269 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rusqlite::Connection;
+use super::model::{ProxyConfig, ProxyKind, Server};
+use crate::common::crypto::MasterKey;
+use crate::features::setting;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Largest HTTP CONNECT response header accepted from a proxy
+const MAX_HTTP_RESPONSE: usize = 16 * 1024;
+
+/// The proxy a server should be reached through: its own `proxy` setting if present, otherwise
+/// the global one from the advanced settings.
+pub fn for_server(conn: &Connection, master_key: &MasterKey, server: &Server) -> Result<Option<ProxyConfig>, String> {
+    match parse_settings(&server.settings).get("proxy") {
+        None | Some(serde_json::Value::Null) => global(conn, master_key),
+        Some(serde_json::Value::String(value)) if value == "global" => global(conn, master_key),
+        Some(serde_json::Value::String(value)) if value == "none" => Ok(None),
+        Some(value) => {
+            let mut proxy: ProxyConfig = serde_json::from_value(value.clone())
+                .map_err(|e| format!("Invalid proxy settings for {}: {}", server.name, e))?;
+
+            proxy.password = match server.id {
+                Some(id) => setting::get_secret_setting(conn, master_key, &password_key(id))?,
+                None => None,
+            };
+
+            Ok(Some(proxy))
+        }
+    }
+}
+
+/// The secret setting holding a server's own proxy password, encrypted like the global one
+pub fn password_key(server_id: i64) -> String {
+    format!("servers/{}/proxy_password", server_id)
+}
+
+/// Proxy passwords are kept out of the server settings, which are stored in plain text
+pub fn check_settings(settings: &serde_json::Value) -> Result<(), String> {
+    match parse_settings(settings).pointer("/proxy/password") {
+        None | Some(serde_json::Value::Null) => Ok(()),
+        Some(_) => Err("A proxy password can't be stored in the server settings; set it in the server form".to_string()),
+    }
+}
+
+/// The settings form submits the JSON as a string, so it may be nested one level deep
+fn parse_settings(settings: &serde_json::Value) -> serde_json::Value {
+    match settings {
+        serde_json::Value::String(raw) => serde_json::from_str(raw).unwrap_or_default(),
+        settings => settings.clone(),
+    }
+}
+
+fn global(conn: &Connection, master_key: &MasterKey) -> Result<Option<ProxyConfig>, String> {
+    let Some(kind) = ProxyKind::parse(&setting::get_setting_or(conn, "advanced/proxy_type", "none")) else {
+        return Ok(None);
+    };
+
+    let host = setting::get_setting_or(conn, "advanced/proxy_host", "");
+    if host.is_empty() {
+        return Err("A proxy is enabled in the advanced settings, but no proxy host is set".to_string());
+    }
+
+    let default_port = match kind {
+        ProxyKind::Socks5 => "1080",
+        ProxyKind::Http => "8080",
+    };
+    let port = setting::get_setting_or(conn, "advanced/proxy_port", default_port);
+    let port = port.parse().map_err(|_| format!("Invalid proxy port: {}", port))?;
+
+    let username = Some(setting::get_setting_or(conn, "advanced/proxy_username", ""))
+        .filter(|username| !username.is_empty());
+    let password = setting::get_secret_setting(conn, master_key, "advanced/proxy_password")?;
+
+    Ok(Some(ProxyConfig { kind, host, port, username, password }))
+}
+
+/// Open a TCP stream to `host:port` tunneled through the proxy, ready for the SSH handshake.
+pub fn connect(proxy: &ProxyConfig, host: &str, port: u16, timeout: Duration) -> Result<TcpStream, String> {
+    let mut stream = connect_to_proxy(proxy, timeout)?;
+
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    match proxy.kind {
+        ProxyKind::Socks5 => socks5_connect(&mut stream, proxy, host, port)?,
+        ProxyKind::Http => http_connect(&mut stream, proxy, host, port)?,
+    }
+
+    stream.set_read_timeout(None).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(None).map_err(|e| e.to_string())?;
+
+    Ok(stream)
+}
+
+fn connect_to_proxy(proxy: &ProxyConfig, timeout: Duration) -> Result<TcpStream, String> {
+    let addrs = (proxy.host.as_str(), proxy.port).to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve proxy {}: {}", proxy.host, e))?;
+
+    let mut last_error = format!("No addresses found for proxy {}", proxy.host);
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(tcp) => return Ok(tcp),
+            Err(e) => last_error = format!("Failed to connect to proxy {}:{}: {}", proxy.host, proxy.port, e),
+        }
+    }
+
+    Err(last_error)
+}
+
+fn read_exact(stream: &mut TcpStream, buf: &mut [u8]) -> Result<(), String> {
+    stream.read_exact(buf).map_err(|e| format!("Proxy closed the connection: {}", e))
+}
+
+fn write_all(stream: &mut TcpStream, buf: &[u8]) -> Result<(), String> {
+    stream.write_all(buf).map_err(|e| format!("Failed to write to proxy: {}", e))
+}
+
+/// SOCKS5 (RFC 1928) CONNECT, with username/password authentication (RFC 1929) when configured.
+/// Hostnames are resolved by the proxy, since the target is often only resolvable from there.
+fn socks5_connect(stream: &mut TcpStream, proxy: &ProxyConfig, host: &str, port: u16) -> Result<(), String> {
+    let credentials = proxy.username.as_deref().map(|username| (username, proxy.password.as_deref().unwrap_or("")));
+
+    let greeting: &[u8] = match credentials {
+        Some(_) => &[0x05, 0x02, 0x00, 0x02],
+        None => &[0x05, 0x01, 0x00],
+    };
+    write_all(stream, greeting)?;
+
+    let mut choice = [0u8; 2];
+    read_exact(stream, &mut choice)?;
+    if choice[0] != 0x05 {
+        return Err("Proxy is not a SOCKS5 proxy".to_string());
+    }
+
+    match (choice[1], credentials) {
+        (0x00, _) => {}
+        (0x02, Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err("SOCKS5 username and password must be at most 255 bytes".to_string());
+            }
+
+            let mut auth = vec![0x01, username.len() as u8];
+            auth.extend_from_slice(username.as_bytes());
+            auth.push(password.len() as u8);
+            auth.extend_from_slice(password.as_bytes());
+            write_all(stream, &auth)?;
+
+            let mut status = [0u8; 2];
+            read_exact(stream, &mut status)?;
+            if status[1] != 0x00 {
+                return Err("SOCKS5 proxy rejected the username or password".to_string());
+            }
+        }
+        (0x02, None) => return Err("SOCKS5 proxy requires a username and password".to_string()),
+        _ => return Err("SOCKS5 proxy does not accept any of the offered authentication methods".to_string()),
+    }
+
+    let mut request = vec![0x05, 0x01, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(0x01);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(0x04);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                return Err(format!("Hostname is too long for SOCKS5: {}", host));
+            }
+            request.push(0x03);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    write_all(stream, &request)?;
+
+    let mut reply = [0u8; 4];
+    read_exact(stream, &mut reply)?;
+    if reply[1] != 0x00 {
+        return Err(format!("SOCKS5 proxy could not connect to {}:{}: {}", host, port, socks5_error(reply[1])));
+    }
+
+    // Skip the bound address, which we have no use for
+    let address_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut len = [0u8; 1];
+            read_exact(stream, &mut len)?;
+            len[0] as usize
+        }
+        other => return Err(format!("SOCKS5 proxy sent an unknown address type {}", other)),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    read_exact(stream, &mut bound)?;
+
+    Ok(())
+}
+
+fn socks5_error(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn http_connect(stream: &mut TcpStream, proxy: &ProxyConfig, host: &str, port: u16) -> Result<(), String> {
+    let authority = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => format!("[{}]:{}", host, port),
+        _ => format!("{}:{}", host, port),
+    };
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(username) = &proxy.username {
+        let credentials = format!("{}:{}", username, proxy.password.as_deref().unwrap_or(""));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", STANDARD.encode(credentials)));
+    }
+    request.push_str("\r\n");
+    write_all(stream, request.as_bytes())?;
+
+    // Read byte by byte so nothing after the header, which belongs to SSH, is consumed
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HTTP_RESPONSE {
+            return Err("HTTP proxy sent an oversized response".to_string());
+        }
+        read_exact(stream, &mut byte)?;
+        response.push(byte[0]);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+
+    match status {
+        "200" => Ok(()),
+        "407" => Err("HTTP proxy requires authentication, or rejected the username or password".to_string()),
+        _ => Err(format!("HTTP proxy could not connect to {}: {}", authority, status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn proxy_passwords_stay_out_of_settings() {
+        assert!(check_settings(&json!({})).is_ok());
+        assert!(check_settings(&json!({ "proxy": "none" })).is_ok());
+        assert!(check_settings(&json!({ "proxy": { "type": "socks5", "host": "proxy", "port": 1080, "username": "me" } })).is_ok());
+        assert!(check_settings(&json!({ "proxy": { "type": "socks5", "host": "proxy", "port": 1080, "password": "secret" } })).is_err());
+        assert!(check_settings(&json!(r#"{"proxy": {"type": "http", "host": "proxy", "port": 8080, "password": "secret"}}"#)).is_err());
+    }
+}
@@ -0,0 +1,813 @@
+use super::commands;
+use super::firewall::{self, FirewallRule};
+use super::model::{CommandError, ProvisionStep, ServiceAction};
+use super::package::Repository;
+use super::provision::{Context, Step};
+use super::remote::{self, quote, RemoteCommand};
+use super::service;
+use crate::features::setting;
+use rusqlite::Connection;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tauri::{AppHandle, Manager};
+
+/// Recipes shipped with the app, by name. A file of the same name in the recipes directory
+/// is used instead.
+const BUNDLED: [(&str, &str); 2] = [
+    ("setup", include_str!("recipes/setup.toml")),
+    ("application", include_str!("recipes/application.toml")),
+];
+
+/// A declarative description of how to provision a server: steps of packages, services,
+/// files, firewall rules and commands. Text in a recipe may refer to variables as
+/// `{{ name }}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Recipe {
+    /// The file name without `.toml`
+    #[serde(skip)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Defaults, which may refer to the variables the engine provides and to each other
+    #[serde(default)]
+    vars: BTreeMap<String, String>,
+    steps: Vec<RecipeStep>,
+}
+
+/// What a step brings the server to. It is applied in the order of the fields, and only the
+/// parts not already in place are.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeStep {
+    id: String,
+    name: String,
+    #[serde(default)]
+    optional: bool,
+    requires: Option<String>,
+    /// Upgrade every installed package
+    #[serde(default)]
+    upgrade: bool,
+    /// `epel`, `remi` or `ondrej_php`, left out on distributions that don't have them
+    #[serde(default)]
+    repositories: Vec<String>,
+    /// A variable holding several packages is split on whitespace
+    #[serde(default)]
+    packages: Vec<String>,
+    /// A PHP version, installed with common extensions from wherever the distribution gets it
+    php: Option<String>,
+    #[serde(default)]
+    files: Vec<RecipeFile>,
+    /// Started and enabled on boot
+    #[serde(default)]
+    services: Vec<String>,
+    /// `ssh`, `http`, `https` or a TCP port. SSH on the port of the current connection is
+    /// always allowed, so turning the firewall on can't lock the app out.
+    #[serde(default)]
+    firewall: Vec<String>,
+    #[serde(default)]
+    commands: Vec<RecipeCommand>,
+    /// Reloaded after the step was applied
+    #[serde(default)]
+    reload: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeFile {
+    path: String,
+    /// Written as root
+    content: Option<String>,
+    /// Created with its parents and owned recursively
+    #[serde(default)]
+    directory: bool,
+    /// `user` or `user:group`
+    owner: Option<String>,
+    /// Octal, e.g. `0644`
+    mode: Option<String>,
+}
+
+/// A shell command. Variables in it are shell-quoted, and every `sudo` in it follows the
+/// server's sudo mode.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeCommand {
+    run: String,
+    /// A check that succeeds if the command doesn't need to run
+    unless: Option<String>,
+    /// A check that must succeed for the command to run at all
+    only_if: Option<String>,
+    /// Run as this user instead of the one connected
+    user: Option<String>,
+    /// Run if the command failed, e.g. to take out what made it fail
+    on_failure: Option<String>,
+}
+
+impl Recipe {
+    fn parse(name: &str, contents: &str) -> Result<Self, String> {
+        let mut recipe: Recipe = toml::from_str(contents).map_err(|e| format!("Invalid recipe {}: {}", name, e))?;
+        recipe.name = name.to_string();
+
+        let mut ids = HashSet::new();
+        for step in &recipe.steps {
+            if !is_identifier(&step.id) {
+                return Err(format!("Invalid step id '{}' in recipe {}", step.id, name));
+            }
+
+            // Requiring only earlier steps keeps the order steps run in meaningful
+            if let Some(required) = &step.requires {
+                if !ids.contains(required.as_str()) {
+                    return Err(format!("Step {} of recipe {} requires {}, which doesn't come before it", step.id, name, required));
+                }
+            }
+
+            if !ids.insert(step.id.as_str()) {
+                return Err(format!("Step {} appears twice in recipe {}", step.id, name));
+            }
+        }
+
+        Ok(recipe)
+    }
+
+    /// The steps as offered before running, named with the recipe's defaults
+    pub fn describe(&self) -> Vec<ProvisionStep> {
+        let vars: HashMap<String, String> = self.vars.clone().into_iter().collect();
+
+        self.steps
+            .iter()
+            .map(|step| ProvisionStep {
+                id: step.id.clone(),
+                name: render(&step.name, &vars, false).unwrap_or_else(|_| step.name.clone()),
+                optional: step.optional,
+                requires: step.requires.clone(),
+            })
+            .collect()
+    }
+
+    /// The variables of a run: those the engine provides about the server, then those given,
+    /// then the recipe's defaults for the rest. PHP's service, binary and pool locations follow
+    /// `php_version`; the socket is that of the `app_name` pool.
+    pub fn resolve_vars(&self, ctx: &Context, given: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+        let mut vars = server_vars(ctx);
+        vars.extend(given.clone());
+
+        // Defaults can refer to each other in any order, so resolve those that can be until none are left
+        let mut pending: Vec<(&String, &String)> = self.vars.iter().filter(|(name, _)| !given.contains_key(*name)).collect();
+        while !pending.is_empty() {
+            let mut unresolved = Vec::new();
+            let mut error = None;
+
+            for (name, value) in &pending {
+                match render(value, &vars, false) {
+                    Ok(value) => {
+                        vars.insert(name.to_string(), value);
+                    }
+                    Err(e) => {
+                        error = Some(format!("Variable {} of recipe {}: {}", name, self.name, e));
+                        unresolved.push((*name, *value));
+                    }
+                }
+            }
+
+            if unresolved.len() == pending.len() {
+                return Err(error.unwrap_or_default());
+            }
+            pending = unresolved;
+        }
+
+        if let Some(version) = vars.get("php_version").cloned() {
+            vars.insert("php_fpm_service".to_string(), ctx.packages.php_fpm_service(&version));
+            vars.insert("php_binary".to_string(), ctx.packages.php_binary(&version));
+            vars.insert("php_fpm_pool_dir".to_string(), ctx.packages.php_fpm_pool_dir(&version));
+
+            if let Some(app_name) = vars.get("app_name").cloned() {
+                vars.insert("php_fpm_socket".to_string(), ctx.packages.php_fpm_socket(&version, &app_name));
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// The steps to run, with every template rendered for the server
+    pub fn steps(&self, ctx: &Context, vars: &HashMap<String, String>) -> Result<Vec<Step>, String> {
+        self.steps
+            .iter()
+            .map(|step| {
+                let actions = Rc::new(actions(ctx, step, vars).map_err(|e| format!("Step {} of recipe {}: {}", step.id, self.name, e))?);
+                let checked = actions.clone();
+
+                let mut built = Step::new(
+                    &step.id,
+                    &render(&step.name, vars, false)?,
+                    move |ctx| checked.satisfied(ctx),
+                    move |ctx| actions.apply(ctx),
+                );
+                if step.optional {
+                    built = built.optional();
+                }
+                if let Some(required) = &step.requires {
+                    built = built.requires(required);
+                }
+
+                Ok(built)
+            })
+            .collect()
+    }
+}
+
+/// Step ids and recipe names: letters, digits, `_` and `-`
+fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// What the engine knows about the server
+fn server_vars(ctx: &Context) -> HashMap<String, String> {
+    HashMap::from([
+        ("os_id".to_string(), ctx.os.id.clone()),
+        ("package_manager".to_string(), ctx.packages.name().to_string()),
+        ("web_group".to_string(), ctx.os.web_group().to_string()),
+        ("admin_group".to_string(), ctx.os.admin_group().to_string()),
+        ("web_root".to_string(), ctx.os.default_web_root().to_string()),
+        ("mariadb_packages".to_string(), ctx.packages.mariadb_packages().join(" ")),
+    ])
+}
+
+/// Replace every `{{ name }}` in `template` with the variable's value, shell-quoted in commands
+fn render(template: &str, vars: &HashMap<String, String>, quoted: bool) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| format!("Unclosed {{{{ in '{}'", template))? + start;
+        let name = rest[start + 2..end].trim();
+        let value = vars.get(name).ok_or_else(|| format!("Unknown variable '{}'", name))?;
+
+        output.push_str(&rest[..start]);
+        output.push_str(&if quoted { quote(value) } else { value.clone() });
+        rest = &rest[end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// One part of a step, rendered for the server
+enum Action {
+    Upgrade,
+    Repository(Repository),
+    Packages(Vec<String>),
+    Php(String),
+    Directory { path: String, owner: Option<String>, mode: Option<String> },
+    File { path: String, content: String, owner: Option<String>, mode: Option<String> },
+    Service(String),
+    /// The first rule is SSH
+    Firewall(Vec<FirewallRule>),
+    Command { run: String, unless: Option<String>, only_if: Option<String>, user: Option<String>, on_failure: Option<String> },
+}
+
+struct Actions {
+    actions: Vec<Action>,
+    reload: Vec<String>,
+}
+
+fn actions(ctx: &Context, step: &RecipeStep, vars: &HashMap<String, String>) -> Result<Actions, String> {
+    let text = |template: &str| render(template, vars, false);
+    let script = |template: &Option<String>| template.as_deref().map(|template| render(template, vars, true)).transpose();
+    let service_name = |template: &str| {
+        let name = text(template)?;
+        remote::validate_service_name(&name)?;
+        Ok::<_, String>(name)
+    };
+
+    let mut actions = Vec::new();
+
+    if step.upgrade {
+        actions.push(Action::Upgrade);
+    }
+
+    for name in &step.repositories {
+        let repository = Repository::parse(&text(name)?).ok_or_else(|| format!("Unknown repository '{}'", name))?;
+        actions.push(Action::Repository(repository));
+    }
+
+    let mut packages = Vec::new();
+    for package in &step.packages {
+        packages.extend(text(package)?.split_whitespace().map(str::to_string));
+    }
+    if !packages.is_empty() {
+        actions.push(Action::Packages(packages));
+    }
+
+    if let Some(version) = &step.php {
+        let version = text(version)?;
+        remote::validate_php_version(&version)?;
+        actions.push(Action::Php(version));
+    }
+
+    for file in &step.files {
+        let path = text(&file.path)?;
+        if !path.starts_with('/') {
+            return Err(format!("File paths must be absolute: {}", path));
+        }
+
+        let owner = file.owner.as_deref().map(text).transpose()?;
+        if let Some(owner) = &owner {
+            owner.split(':').try_for_each(remote::validate_username)?;
+        }
+
+        let mode = file.mode.as_deref().map(text).transpose()?;
+        if let Some(mode) = &mode {
+            if !(3..=4).contains(&mode.len()) || !mode.chars().all(|c| ('0'..='7').contains(&c)) {
+                return Err(format!("Invalid mode '{}' for {}: expected octal, e.g. 0644", mode, path));
+            }
+        }
+
+        actions.push(match (file.directory, &file.content) {
+            (true, None) => Action::Directory { path, owner, mode },
+            (false, Some(content)) => Action::File { path, content: text(content)?, owner, mode },
+            _ => return Err(format!("{} needs either content or directory = true", path)),
+        });
+    }
+
+    for name in &step.services {
+        actions.push(Action::Service(service_name(name)?));
+    }
+
+    if !step.firewall.is_empty() {
+        let mut rules = vec![FirewallRule::ssh(firewall::ssh_port(ctx.job.server_id()))];
+
+        for entry in &step.firewall {
+            let rule = match text(entry)?.as_str() {
+                "ssh" => continue,
+                "http" => FirewallRule::HTTP,
+                "https" => FirewallRule::HTTPS,
+                port => FirewallRule {
+                    service: None,
+                    port: port.parse().ok().filter(|port| *port > 0).ok_or_else(|| format!("Invalid firewall rule '{}'", port))?,
+                },
+            };
+
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+
+        actions.push(Action::Firewall(rules));
+    }
+
+    for command in &step.commands {
+        let user = command.user.as_deref().map(text).transpose()?;
+        if let Some(user) = &user {
+            remote::validate_username(user)?;
+        }
+
+        actions.push(Action::Command {
+            run: render(&command.run, vars, true)?,
+            unless: script(&command.unless)?,
+            only_if: script(&command.only_if)?,
+            user,
+            on_failure: script(&command.on_failure)?,
+        });
+    }
+
+    let reload = step.reload.iter().map(|name| service_name(name)).collect::<Result<_, _>>()?;
+
+    Ok(Actions { actions, reload })
+}
+
+impl Actions {
+    fn satisfied(&self, ctx: &Context) -> Result<bool, CommandError> {
+        for action in &self.actions {
+            if !action.satisfied(ctx)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn apply(&self, ctx: &Context) -> Result<(), CommandError> {
+        for action in &self.actions {
+            ctx.job.check_cancelled()?;
+
+            if !action.satisfied(ctx)? {
+                action.apply(ctx)?;
+            }
+        }
+
+        for name in &self.reload {
+            ctx.job.cmd(ctx.services.command(ServiceAction::Reload, name))
+                .map_err(|e| e.context(format!("Failed to reload {}", name)))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Action {
+    fn satisfied(&self, ctx: &Context) -> Result<bool, CommandError> {
+        let job = ctx.job;
+
+        match self {
+            Action::Upgrade => {
+                let output = job.query(ctx.packages.check_updates())?;
+                Ok(ctx.packages.parse_updates(&output.stdout).is_empty())
+            }
+            // Repositories the distribution doesn't have are left out
+            Action::Repository(repository) => match ctx.packages.add_repository(*repository, &ctx.os) {
+                Ok(_) => job.probe(ctx.packages.has_repository(*repository)),
+                Err(_) => Ok(true),
+            },
+            Action::Packages(packages) => {
+                let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+                job.probe(ctx.packages.is_installed(&packages))
+            }
+            Action::Php(version) => {
+                let binary = ctx.packages.php_binary(version);
+                if !job.probe(RemoteCommand::new("command").args(["-v", &binary]))? {
+                    return Ok(false);
+                }
+
+                let output = job.query(RemoteCommand::new(&binary).arg("-v"))?;
+                Ok(output.stdout.starts_with(&format!("PHP {}.", version)))
+            }
+            Action::Directory { path, owner, mode } => {
+                let output = job.query(RemoteCommand::shell(&format!("[ -d {0} ] && stat -c '%U:%G %a' {0} || true", quote(path))).elevated())?;
+                Ok(attributes_match(output.stdout.trim(), owner, mode))
+            }
+            Action::File { path, content, owner, mode } => {
+                let output = job.query(RemoteCommand::shell(&format!("stat -c '%U:%G %a' {0} 2>/dev/null && sha256sum {0} 2>/dev/null || true", quote(path))).elevated())?;
+
+                let mut lines = output.stdout.lines();
+                let attributes = lines.next().unwrap_or_default().trim();
+                let checksum = lines.next().and_then(|line| line.split_whitespace().next()).unwrap_or_default();
+
+                Ok(checksum == format!("{:x}", Sha256::digest(content.as_bytes())) && attributes_match(attributes, owner, mode))
+            }
+            Action::Service(name) => {
+                let status = ctx.services.status(job.server_id(), name)?;
+                Ok(status.active && status.enabled)
+            }
+            Action::Firewall(rules) => {
+                let firewall = firewall::detect(job.server_id(), &ctx.os)?;
+
+                if !job.probe(firewall.installed())? {
+                    return Ok(false);
+                }
+
+                if let Some(unit) = firewall.service() {
+                    let status = ctx.services.status(job.server_id(), unit)?;
+                    if !(status.active && status.enabled) {
+                        return Ok(false);
+                    }
+                }
+
+                for rule in rules {
+                    if !job.probe(firewall.allows(rule))? {
+                        return Ok(false);
+                    }
+                }
+
+                Ok(true)
+            }
+            // A command without `unless` can't tell, so it runs every time
+            Action::Command { unless, only_if, user, .. } => {
+                if let Some(only_if) = only_if {
+                    if !job.probe(shell(only_if, user))? {
+                        return Ok(true);
+                    }
+                }
+
+                match unless {
+                    Some(unless) => job.probe(shell(unless, user)),
+                    None => Ok(false),
+                }
+            }
+        }
+    }
+
+    fn apply(&self, ctx: &Context) -> Result<(), CommandError> {
+        let job = ctx.job;
+
+        match self {
+            Action::Upgrade => {
+                job.cmd_timeout(ctx.packages.upgrade(), service::INSTALL_TIMEOUT)
+                    .map_err(|e| e.context("Failed to update system packages"))?;
+            }
+            Action::Repository(repository) => {
+                job.cmd_timeout(ctx.packages.add_repository(*repository, &ctx.os)?, service::INSTALL_TIMEOUT)
+                    .map_err(|e| e.context(format!("Failed to install {} repository", repository.name())))?;
+            }
+            Action::Packages(packages) => {
+                job.cmd_timeout(ctx.packages.install(packages), service::INSTALL_TIMEOUT)
+                    .map_err(|e| e.context(format!("Failed to install {}", packages.join(", "))))?;
+            }
+            Action::Php(version) => {
+                commands::install_php(job, version)?;
+            }
+            Action::Directory { path, owner, mode } => {
+                job.cmd(RemoteCommand::sudo("mkdir").args(["-p", path]))
+                    .map_err(|e| e.context(format!("Failed to create {}", path)))?;
+                set_attributes(ctx, path, owner, mode, true)?;
+            }
+            Action::File { path, content, owner, mode } => {
+                job.cmd(remote::write_root_file(path, content))
+                    .map_err(|e| e.context(format!("Failed to write {}", path)))?;
+                set_attributes(ctx, path, owner, mode, false)?;
+            }
+            Action::Service(name) => {
+                job.cmd(ctx.services.command(ServiceAction::Start, name))
+                    .map_err(|e| e.context(format!("Failed to start {}", name)))?;
+                job.cmd(ctx.services.command(ServiceAction::Enable, name))
+                    .map_err(|e| e.context(format!("Failed to enable {}", name)))?;
+            }
+            Action::Firewall(rules) => configure_firewall(ctx, rules)?,
+            Action::Command { run, user, on_failure, .. } => {
+                if let Err(e) = job.cmd_timeout(shell(run, user), service::INSTALL_TIMEOUT) {
+                    if let Some(on_failure) = on_failure {
+                        let _ = job.cmd(shell(on_failure, user));
+                    }
+                    return Err(e.context(format!("Failed to run {}", run)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn shell(script: &str, user: &Option<String>) -> RemoteCommand {
+    match user {
+        Some(user) => RemoteCommand::sudo_as(user, "bash").args(["-c", script]),
+        None => RemoteCommand::shell(script).with_sudo(),
+    }
+}
+
+/// Compare `user:group mode` as printed by stat with what the recipe asks for
+fn attributes_match(attributes: &str, owner: &Option<String>, mode: &Option<String>) -> bool {
+    let Some((actual_owner, actual_mode)) = attributes.split_once(' ') else {
+        return false;
+    };
+
+    let owner_matches = owner.as_ref().is_none_or(|owner| match owner.contains(':') {
+        true => actual_owner == owner,
+        false => actual_owner.split(':').next() == Some(owner.as_str()),
+    });
+    let mode_matches = mode.as_ref().is_none_or(|mode| actual_mode == mode.trim_start_matches('0'));
+
+    owner_matches && mode_matches
+}
+
+fn set_attributes(ctx: &Context, path: &str, owner: &Option<String>, mode: &Option<String>, recursive: bool) -> Result<(), CommandError> {
+    if let Some(owner) = owner {
+        let chown = match recursive {
+            true => RemoteCommand::sudo("chown").args(["-R", owner, path]),
+            false => RemoteCommand::sudo("chown").args([owner, path]),
+        };
+        ctx.job.cmd(chown)
+            .map_err(|e| e.context(format!("Failed to set the owner of {}", path)))?;
+    }
+
+    if let Some(mode) = mode {
+        ctx.job.cmd(RemoteCommand::sudo("chmod").args([mode, path]))
+            .map_err(|e| e.context(format!("Failed to set the mode of {}", path)))?;
+    }
+
+    Ok(())
+}
+
+/// Configure the firewall the server has, or its distribution's default
+fn configure_firewall(ctx: &Context, rules: &[FirewallRule]) -> Result<(), CommandError> {
+    let job = ctx.job;
+    let firewall = firewall::detect(job.server_id(), &ctx.os)?;
+    let name = firewall.name();
+
+    if !job.probe(firewall.installed()).unwrap_or(false) {
+        job.cmd_timeout(ctx.packages.install([firewall.package()]), service::INSTALL_TIMEOUT)
+            .map_err(|e| e.context(format!("Failed to install {}", name)))?;
+    }
+
+    if let Some(unit) = firewall.service() {
+        job.cmd(ctx.services.command(ServiceAction::Start, unit))
+            .map_err(|e| e.context(format!("Failed to start {}", name)))?;
+        job.cmd(ctx.services.command(ServiceAction::Enable, unit))
+            .map_err(|e| e.context(format!("Failed to enable {}", name)))?;
+    }
+
+    let (ssh, others) = rules.split_first().ok_or("No firewall rules to apply")?;
+    job.cmd(firewall.activate(ssh))
+        .map_err(|e| e.context(format!("Failed to allow SSH in {}", name)))?;
+
+    for rule in others {
+        job.cmd(firewall.allow(rule))
+            .map_err(|e| e.context(format!("Failed to allow port {} in {}", rule.port, name)))?;
+    }
+
+    if let Some(apply) = firewall.apply() {
+        job.cmd(apply)
+            .map_err(|e| e.context(format!("Failed to apply {} rules", name)))?;
+    }
+
+    Ok(())
+}
+
+/// Where user recipes are read from: the `advanced/recipes_directory` setting, else `recipes`
+/// in the app's data directory
+pub fn directory(app_handle: &AppHandle, conn: &Connection) -> Result<PathBuf, String> {
+    let configured = setting::get_setting_or(conn, "advanced/recipes_directory", "");
+    let configured = configured.trim();
+
+    if let Some(relative) = configured.strip_prefix("~/") {
+        let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
+        return Ok(home_dir.join(relative));
+    }
+
+    if !configured.is_empty() {
+        return Ok(PathBuf::from(configured));
+    }
+
+    app_handle.path().app_data_dir().map(|dir| dir.join("recipes")).map_err(|e| e.to_string())
+}
+
+/// The recipe to run under `name`: the user's if the recipes directory has one, else the bundled one
+pub fn load(app_handle: &AppHandle, conn: &Connection, name: &str) -> Result<Recipe, String> {
+    // The name becomes part of a path
+    if !is_identifier(name) {
+        return Err(format!("Invalid recipe name '{}'", name));
+    }
+
+    let path = directory(app_handle, conn)?.join(format!("{}.toml", name));
+
+    if path.is_file() {
+        let contents = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        return Recipe::parse(name, &contents);
+    }
+
+    match BUNDLED.iter().find(|(bundled, _)| *bundled == name) {
+        Some((name, contents)) => Recipe::parse(name, contents),
+        None => Err(format!("No recipe named {}", name)),
+    }
+}
+
+/// Every recipe with the file it was loaded from, `None` for bundled ones. Files that don't
+/// parse are logged and left out, so one broken recipe doesn't hide the others.
+pub fn load_all(app_handle: &AppHandle, conn: &Connection) -> Result<Vec<(Recipe, Option<PathBuf>)>, String> {
+    let mut recipes: Vec<(Recipe, Option<PathBuf>)> = BUNDLED
+        .iter()
+        .map(|(name, contents)| Recipe::parse(name, contents).map(|recipe| (recipe, None)))
+        .collect::<Result<_, _>>()?;
+
+    let Ok(entries) = fs::read_dir(directory(app_handle, conn)?) else {
+        return Ok(recipes);
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else {
+            continue;
+        };
+
+        let recipe = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| Recipe::parse(&name, &contents));
+
+        match recipe {
+            Ok(recipe) => {
+                recipes.retain(|(existing, _)| existing.name != name);
+                recipes.push((recipe, Some(path)));
+            }
+            Err(e) => eprintln!("Skipping recipe {}: {}", path.display(), e),
+        }
+    }
+
+    Ok(recipes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    /// Every template of a step, with whether it is a shell command
+    fn templates(step: &RecipeStep) -> Vec<(&str, bool)> {
+        let mut templates: Vec<(&str, bool)> = vec![(&step.name, false)];
+        templates.extend(step.packages.iter().chain(&step.services).chain(&step.reload).chain(&step.firewall).map(|t| (t.as_str(), false)));
+        templates.extend(step.php.as_deref().map(|t| (t, false)));
+
+        for file in &step.files {
+            templates.push((&file.path, false));
+            templates.extend(file.content.as_deref().into_iter().chain(file.owner.as_deref()).map(|t| (t, false)));
+        }
+        for command in &step.commands {
+            templates.push((&command.run, true));
+            templates.extend([&command.unless, &command.only_if, &command.on_failure].into_iter().flatten().map(|t| (t.as_str(), true)));
+            templates.extend(command.user.as_deref().map(|t| (t, false)));
+        }
+
+        templates
+    }
+
+    #[test]
+    fn render_substitutes_variables() {
+        let vars = vars(&[("app_name", "shop"), ("web_group", "nginx")]);
+
+        assert_eq!(render("/etc/nginx/conf.d/{{ app_name }}.conf", &vars, false).unwrap(), "/etc/nginx/conf.d/shop.conf");
+        assert_eq!(render("{{app_name}}:{{  web_group  }}", &vars, false).unwrap(), "shop:nginx");
+        assert_eq!(render("no variables", &vars, false).unwrap(), "no variables");
+    }
+
+    #[test]
+    fn render_quotes_values_in_commands() {
+        let vars = vars(&[("name", "x'; rm -rf / #"), ("sub", "$(id)\nid")]);
+
+        assert_eq!(render("echo {{ name }}", &vars, true).unwrap(), format!("echo {}", quote("x'; rm -rf / #")));
+        assert_eq!(render("echo {{ sub }}", &vars, true).unwrap(), "echo '$(id)\nid'");
+        assert_eq!(render("echo {{ sub }}", &vars, false).unwrap(), "echo $(id)\nid");
+    }
+
+    #[test]
+    fn render_rejects_unknown_variables_and_unclosed_braces() {
+        let vars = vars(&[("app_name", "shop")]);
+
+        assert_eq!(render("{{ app_nmae }}", &vars, false).unwrap_err(), "Unknown variable 'app_nmae'");
+        assert!(render("{{ app_name", &vars, false).unwrap_err().starts_with("Unclosed"));
+    }
+
+    #[test]
+    fn parse_rejects_requiring_a_later_step() {
+        let error = Recipe::parse("test", r#"
+            [[steps]]
+            id = "php_fpm"
+            name = "PHP-FPM"
+            requires = "php"
+
+            [[steps]]
+            id = "php"
+            name = "PHP"
+        "#).unwrap_err();
+
+        assert!(error.contains("requires php, which doesn't come before it"), "{}", error);
+    }
+
+    #[test]
+    fn parse_rejects_bad_and_duplicate_ids() {
+        let step = |id: &str| format!("[[steps]]\nid = \"{}\"\nname = \"Step\"\n", id);
+
+        assert!(Recipe::parse("test", &step("../x")).unwrap_err().contains("Invalid step id"));
+        assert!(Recipe::parse("test", &step("")).unwrap_err().contains("Invalid step id"));
+        assert!(Recipe::parse("test", &format!("{}{}", step("a"), step("a"))).unwrap_err().contains("appears twice"));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_fields() {
+        let error = Recipe::parse("test", "[[steps]]\nid = \"a\"\nname = \"A\"\npackage = [\"nginx\"]\n").unwrap_err();
+
+        assert!(error.contains("unknown field"), "{}", error);
+    }
+
+    #[test]
+    fn recipe_names_are_identifiers() {
+        assert!(is_identifier("setup"));
+        assert!(is_identifier("laravel-app_2"));
+        for name in ["", "../etc/passwd", "a/b", "a.toml", "a b", "~root"] {
+            assert!(!is_identifier(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn bundled_recipes_only_use_known_variables() {
+        // What the engine provides, and what application creation passes in
+        let known = [
+            "os_id", "package_manager", "web_group", "admin_group", "web_root", "mariadb_packages",
+            "php_fpm_service", "php_binary", "php_fpm_pool_dir", "php_fpm_socket", "app_name", "username",
+        ];
+
+        for (name, contents) in BUNDLED {
+            let recipe = Recipe::parse(name, contents).unwrap();
+
+            let mut vars: HashMap<String, String> = known.iter().map(|name| (name.to_string(), "value".to_string())).collect();
+            vars.extend(recipe.vars.keys().map(|name| (name.clone(), "value".to_string())));
+
+            for value in recipe.vars.values() {
+                render(value, &vars, false).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            }
+            for step in &recipe.steps {
+                for (template, quoted) in templates(step) {
+                    render(template, &vars, quoted).unwrap_or_else(|e| panic!("{} step {}: {}", name, step.id, e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::fmt;
+
+/// A command to run on a server. Every argument is shell-quoted when rendered, and secrets or
+/// file contents go over stdin so they never appear in the command line or process list.
+#[derive(Debug, Clone, Default)]
+pub struct RemoteCommand {
+    words: Vec<String>,
+    stdin: Option<Vec<u8>>,
+    idempotent: bool,
+    sudo: bool,
+}
+
+impl RemoteCommand {
+    pub fn new(program: &str) -> Self {
+        RemoteCommand {
+            words: vec![quote(program)],
+            stdin: None,
+            idempotent: false,
+            sudo: false,
+        }
+    }
+
+    /// Run `program` through `sudo`
+    pub fn sudo(program: &str) -> Self {
+        RemoteCommand::new("sudo").arg(program).with_sudo()
+    }
+
+    /// Run `program` through `sudo -u user`
+    pub fn sudo_as(user: &str, program: &str) -> Self {
+        RemoteCommand::new("sudo").arg("-u").arg(user).arg(program).with_sudo()
+    }
+
+    /// A shell snippet used as-is. Only for fixed strings; anything interpolated must be
+    /// quoted with `quote` first.
+    pub fn shell(script: &str) -> Self {
+        RemoteCommand {
+            words: vec![script.to_string()],
+            stdin: None,
+            idempotent: false,
+            sudo: false,
+        }
+    }
+
+    /// Mark a shell snippet that calls `sudo` itself, so the server's sudo mode applies to it.
+    /// Commands built with `sudo`, `sudo_as` or `elevated` are marked already.
+    pub fn with_sudo(mut self) -> Self {
+        self.sudo = true;
+        self
+    }
+
+    pub fn uses_sudo(&self) -> bool {
+        self.sudo
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<str>) -> Self {
+        self.words.push(quote(arg.as_ref()));
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.words.extend(args.into_iter().map(|arg| quote(arg.as_ref())));
+        self
+    }
+
+    /// Run `next` only if this command succeeds. Only the first command of a line reads stdin,
+    /// so `next` can't have any.
+    pub fn and(self, next: RemoteCommand) -> Self {
+        self.join("&&", next)
+    }
+
+    /// Feed this command's stdout into `next`, which therefore can't have stdin of its own
+    pub fn pipe(self, next: RemoteCommand) -> Self {
+        self.join("|", next)
+    }
+
+    fn join(mut self, operator: &str, next: RemoteCommand) -> Self {
+        // Its stdin would go to the first command of the line instead, e.g. file content to `mkdir`
+        assert!(next.stdin.is_none(), "stdin of `{}` would be read by `{}`", next.render(), self.render());
+
+        self.words.push(operator.to_string());
+        self.words.extend(next.words);
+        self.idempotent = self.idempotent && next.idempotent;
+        self.sudo = self.sudo || next.sudo;
+        self
+    }
+
+    /// Data written to the command's stdin, followed by EOF
+    pub fn stdin(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(data.into());
+        self
+    }
+
+    pub fn stdin_data(&self) -> Option<&[u8]> {
+        self.stdin.as_deref()
+    }
+
+    /// Mark the command as safe to run again, e.g. a read-only query, so it is retried after
+    /// a reconnect if the connection drops while it runs.
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    pub fn is_idempotent(&self) -> bool {
+        self.idempotent
+    }
+
+    /// Run this command, which may be a chain, as root through a single `sudo`
+    pub fn elevated(self) -> Self {
+        let script = self.render();
+
+        RemoteCommand {
+            stdin: self.stdin,
+            idempotent: self.idempotent,
+            ..RemoteCommand::sudo("sh").arg("-c").arg(script)
+        }
+    }
+
+    /// The command line as sent to the server, without stdin
+    pub fn render(&self) -> String {
+        self.words.join(" ")
+    }
+}
+
+impl fmt::Display for RemoteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+impl From<&str> for RemoteCommand {
+    fn from(script: &str) -> Self {
+        RemoteCommand::shell(script)
+    }
+}
+
+impl From<&String> for RemoteCommand {
+    fn from(script: &String) -> Self {
+        RemoteCommand::shell(script)
+    }
+}
+
+impl From<&RemoteCommand> for RemoteCommand {
+    fn from(command: &RemoteCommand) -> Self {
+        command.clone()
+    }
+}
+
+/// Quote a word for a POSIX shell. Words made only of safe characters are left alone.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+
+    if !word.is_empty() && word.chars().all(safe) {
+        return word.to_string();
+    }
+
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Write `content` to a root-owned file. The content goes over stdin, so it needs no quoting.
+pub fn write_root_file(path: &str, content: &str) -> RemoteCommand {
+    RemoteCommand::sudo("tee").arg(path).stdin(content)
+}
+
+/// A Linux login name as accepted by `useradd` with its default settings.
+pub fn validate_username(username: &str) -> Result<(), String> {
+    let mut chars = username.chars();
+    let valid = username.len() <= 32
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid username '{}': use up to 32 lowercase letters, digits, '_' or '-', starting with a letter or '_'",
+            username
+        )),
+    }
+}
+
+/// A systemd unit or init script name, e.g. nginx, php8.3-fpm or getty@tty1
+pub fn validate_service_name(service: &str) -> Result<(), String> {
+    let mut chars = service.chars();
+    let valid = service.len() <= 255
+        && chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@' | ':'));
+
+    match valid {
+        true => Ok(()),
+        false => Err(format!("Invalid service name '{}'", service)),
+    }
+}
+
+/// Application names end up in file names under /etc and /var/log, so no slashes or leading dots.
+pub fn validate_app_name(app_name: &str) -> Result<(), String> {
+    let mut chars = app_name.chars();
+    let valid = app_name.len() <= 64
+        && chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-');
+
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid application name '{}': use up to 64 letters, digits, '.', '_' or '-', starting with a letter or digit",
+            app_name
+        )),
+    }
+}
+
+fn is_dotted_number(version: &str, max_parts: usize) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+
+    parts.len() <= max_parts
+        && parts.iter().all(|part| !part.is_empty() && part.len() <= 4 && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// A PHP version in `major.minor` form, e.g. 8.3
+pub fn validate_php_version(version: &str) -> Result<(), String> {
+    match version.split('.').count() == 2 && is_dotted_number(version, 2) {
+        true => Ok(()),
+        false => Err(format!("Invalid PHP version '{}': expected major.minor, e.g. 8.3", version)),
+    }
+}
+
+/// A Node.js version as understood by nvm: 20, v20.11.1, node, lts, or an lts alias like lts/iron
+pub fn validate_node_version(version: &str) -> Result<(), String> {
+    let valid = match version {
+        "node" | "lts" | "lts/*" => true,
+        _ => match version.strip_prefix("lts/") {
+            Some(codename) => !codename.is_empty() && codename.chars().all(|c| c.is_ascii_lowercase()),
+            None => is_dotted_number(version.strip_prefix('v').unwrap_or(version), 3),
+        },
+    };
+
+    match valid {
+        true => Ok(()),
+        false => Err(format!("Invalid Node.js version '{}': expected e.g. 20, v20.11.1, node or lts/*", version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `sh` makes of a quoted word
+    fn unquote(quoted: &str) -> String {
+        let output = std::process::Command::new("sh").arg("-c").arg(format!("printf %s {}", quoted)).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn quote_leaves_safe_words_alone() {
+        assert_eq!(quote("/etc/nginx/conf.d/app.conf"), "/etc/nginx/conf.d/app.conf");
+        assert_eq!(quote("user@host:22"), "user@host:22");
+    }
+
+    #[test]
+    fn quote_escapes_single_quotes() {
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(unquote(&quote("it's")), "it's");
+        assert_eq!(unquote(&quote("'")), "'");
+    }
+
+    #[test]
+    fn quote_keeps_substitutions_and_newlines_literal() {
+        for word in ["$(rm -rf /)", "`id`", "$HOME", "a\nb; echo pwned", "a b", "*", ""] {
+            assert_eq!(unquote(&quote(word)), word);
+        }
+    }
+
+    #[test]
+    fn commands_quote_every_argument() {
+        let command = RemoteCommand::new("grep").arg("a b").arg("$(x)").pipe(RemoteCommand::new("wc").arg("-l"));
+        assert_eq!(command.render(), "grep 'a b' '$(x)' | wc -l");
+    }
+
+    #[test]
+    fn stdin_stays_with_the_first_command() {
+        let command = RemoteCommand::new("tee").arg("/x").stdin("content").and(RemoteCommand::new("cat").arg("/x"));
+        assert_eq!(command.stdin_data(), Some(&b"content"[..]));
+    }
+
+    #[test]
+    #[should_panic(expected = "would be read by")]
+    fn stdin_of_a_later_command_is_refused() {
+        let _ = RemoteCommand::new("mkdir").arg("/x").and(write_root_file("/x/secret", "content"));
+    }
+
+    #[test]
+    fn sudo_is_marked_on_the_command_not_found_in_its_text() {
+        assert!(!RemoteCommand::new("echo").arg("sudo").uses_sudo());
+        assert!(!RemoteCommand::shell("sudo true").uses_sudo());
+        assert!(RemoteCommand::sudo("true").uses_sudo());
+        assert!(RemoteCommand::new("echo").pipe(RemoteCommand::sudo("tee").arg("/etc/x")).uses_sudo());
+        assert!(RemoteCommand::new("nft").arg("list").elevated().uses_sudo());
+    }
+
+    #[test]
+    fn elevated_runs_the_whole_chain_in_one_sudo() {
+        let chain = RemoteCommand::new("mkdir").arg("/x").and(RemoteCommand::new("touch").arg("/x/it's"));
+        let rendered = chain.clone().elevated().render();
+
+        let script = rendered.strip_prefix("sudo sh -c ").unwrap();
+        assert_eq!(unquote(script), chain.render());
+    }
+
+    #[test]
+    fn usernames() {
+        assert!(validate_username("deploy").is_ok());
+        assert!(validate_username("_svc-1").is_ok());
+        for name in ["", "Root", "1user", "a b", "user;id", "../etc", "user$(id)", &"a".repeat(33)] {
+            assert!(validate_username(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn service_names() {
+        assert!(validate_service_name("php8.3-fpm").is_ok());
+        assert!(validate_service_name("getty@tty1").is_ok());
+        for name in ["", "-nginx", "nginx; reboot", "nginx service", "../nginx"] {
+            assert!(validate_service_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn app_names() {
+        assert!(validate_app_name("example.com").is_ok());
+        for name in ["", ".hidden", "../etc/passwd", "a/b", "app name", "app\n", &"a".repeat(65)] {
+            assert!(validate_app_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn php_versions() {
+        assert!(validate_php_version("8.3").is_ok());
+        for version in ["8", "8.3.1", "8.", "8.x", "8.3; id", ""] {
+            assert!(validate_php_version(version).is_err(), "{}", version);
+        }
+    }
+
+    #[test]
+    fn node_versions() {
+        for version in ["20", "v20.11.1", "node", "lts", "lts/*", "lts/iron"] {
+            assert!(validate_node_version(version).is_ok(), "{}", version);
+        }
+        for version in ["", "lts/", "lts/Iron", "20.1.2.3", "v", "20 && id"] {
+            assert!(validate_node_version(version).is_err(), "{}", version);
+        }
+    }
+}
@@ -1,23 +1,73 @@
 use rusqlite::{params, Connection};
-use super::model::Server;
-use ssh2::{Session, DisconnectCode};
-use std::net::TcpStream;
-use std::path::Path;
-use std::sync::Mutex;
-use std::io::Read;
-use once_cell::sync::Lazy;
-
-static ACTIVE_SESSION: Lazy<Mutex<Option<Session>>> = Lazy::new(|| Mutex::new(None));
+use super::connect::Connector;
+use super::model::{AgentIdentity, AuthMethod, CommandError, CommandResult, ConnectionError, KnownHost, OsRelease, OutputStream, Server, ServerFacts, SessionInfo, SshConfigHost, SudoMode, TerminalInfo, Tunnel, TunnelStatus};
+use super::remote::RemoteCommand;
+use super::{agent, distro, facts, forwarding, known_hosts, provision, proxy, session, ssh_config, supervisor, terminal};
+use crate::common::crypto::MasterKey;
+use crate::common::utf8::Utf8Decoder;
+use crate::features::ssh_key;
+use ssh2::{ErrorCode, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+const SERVER_COLUMNS: &str = "id, name, hostname, ip_address, port, username, ssh_key_id, notes, settings, created_at, updated_at, auth_method, jump_host_id, sudo_mode, os_id, os_like, os_version, os_name, facts";
+
+/// Servers with the facts gathered from them, if any
+const SERVER_TABLES: &str = "servers LEFT JOIN server_facts ON server_facts.server_id = servers.id";
+
+fn server_from_row(row: &rusqlite::Row) -> rusqlite::Result<Server> {
+    let settings_str: String = row.get(8)?;
+    let settings = serde_json::from_str(&settings_str).unwrap_or_else(|_| serde_json::json!({}));
+    let auth_method: String = row.get(11)?;
+    let sudo_mode: String = row.get(13)?;
+    let os_id: Option<String> = row.get(14)?;
+    let os = match os_id {
+        Some(id) => Some(OsRelease {
+            id,
+            id_like: row.get::<_, Option<String>>(15)?
+                .map(|like| like.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            version_id: row.get::<_, Option<String>>(16)?.unwrap_or_default(),
+            name: row.get::<_, Option<String>>(17)?.unwrap_or_default(),
+        }),
+        None => None,
+    };
+    let facts = row.get::<_, Option<String>>(18)?
+        .and_then(|facts| serde_json::from_str::<ServerFacts>(&facts).ok());
+
+    Ok(Server {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        hostname: row.get(2)?,
+        ip_address: row.get(3)?,
+        port: row.get(4)?,
+        username: row.get(5)?,
+        ssh_key_id: row.get(6)?,
+        auth_method: AuthMethod::parse(&auth_method),
+        jump_host_id: row.get(12)?,
+        sudo_mode: SudoMode::parse(&sudo_mode),
+        os,
+        facts,
+        notes: row.get(7)?,
+        settings,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
 
 pub fn add_server(conn: &Connection, server: Server) -> Result<Server, String> {
+    proxy::check_settings(&server.settings)?;
+
     let now = chrono::Local::now().to_rfc3339();
     let created_at = server.created_at.unwrap_or(now.clone());
     let updated_at = server.updated_at.unwrap_or(now.clone());
     let settings_json = serde_json::to_string(&server.settings).unwrap_or_else(|_| "{}".to_string());
 
     conn.execute(
-        "INSERT INTO servers (name, hostname, ip_address, port, username, ssh_key_id, notes, settings, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        "INSERT INTO servers (name, hostname, ip_address, port, username, ssh_key_id, notes, settings, created_at, updated_at, auth_method, jump_host_id, sudo_mode)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
         params![
             server.name,
             server.hostname,
@@ -28,7 +78,10 @@ pub fn add_server(conn: &Connection, server: Server) -> Result<Server, String> {
             server.notes,
             settings_json,
             created_at,
-            updated_at
+            updated_at,
+            server.auth_method.as_str(),
+            server.jump_host_id,
+            server.sudo_mode.as_str()
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -38,54 +91,17 @@ pub fn add_server(conn: &Connection, server: Server) -> Result<Server, String> {
 
 pub fn get_server(conn: &Connection, id: i64) -> Result<Server, String> {
     conn.query_row(
-        "SELECT id, name, hostname, ip_address, port, username, ssh_key_id, notes, settings, created_at, updated_at
-         FROM servers WHERE id = ?1",
+        &format!("SELECT {} FROM {} WHERE id = ?1", SERVER_COLUMNS, SERVER_TABLES),
         params![id],
-        |row| {
-            let settings_str: String = row.get(8)?;
-            let settings = serde_json::from_str(&settings_str).unwrap_or_else(|_| serde_json::json!({}));
-
-            Ok(Server {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                hostname: row.get(2)?,
-                ip_address: row.get(3)?,
-                port: row.get(4)?,
-                username: row.get(5)?,
-                ssh_key_id: row.get(6)?,
-                notes: row.get(7)?,
-                settings,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        }
+        server_from_row
     ).map_err(|e| e.to_string())
 }
 
 pub fn get_servers(conn: &Connection) -> Result<Vec<Server>, String> {
-    let mut stmt = conn.prepare("
-        SELECT id, name, hostname, ip_address, port, username, ssh_key_id, notes, settings, created_at, updated_at
-        FROM servers
-    ").map_err(|e| e.to_string())?;
-
-    let server_iter = stmt.query_map([], |row| {
-        let settings_str: String = row.get(8)?;
-        let settings = serde_json::from_str(&settings_str).unwrap_or_else(|_| serde_json::json!({}));
-
-        Ok(Server {
-            id: Some(row.get(0)?),
-            name: row.get(1)?,
-            hostname: row.get(2)?,
-            ip_address: row.get(3)?,
-            port: row.get(4)?,
-            username: row.get(5)?,
-            ssh_key_id: row.get(6)?,
-            notes: row.get(7)?,
-            settings,
-            created_at: row.get(9)?,
-            updated_at: row.get(10)?,
-        })
-    }).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM {}", SERVER_COLUMNS, SERVER_TABLES))
+        .map_err(|e| e.to_string())?;
+
+    let server_iter = stmt.query_map([], server_from_row).map_err(|e| e.to_string())?;
 
     let mut servers = Vec::new();
     for server in server_iter {
@@ -98,8 +114,15 @@ pub fn get_servers(conn: &Connection) -> Result<Vec<Server>, String> {
 pub fn update_server(conn: &Connection, server: Server) -> Result<(), String> {
     let id = server.id.ok_or("Server ID is required for update")?;
 
+    if server.jump_host_id == Some(id) {
+        return Err("A server cannot be its own jump host".to_string());
+    }
+
+    proxy::check_settings(&server.settings)?;
+
     let now = chrono::Local::now().to_rfc3339();
     let updated_at = server.updated_at.unwrap_or(now.clone());
+    let settings_json = serde_json::to_string(&server.settings).unwrap_or_else(|_| "{}".to_string());
 
     conn.execute(
         "UPDATE servers SET
@@ -110,8 +133,12 @@ pub fn update_server(conn: &Connection, server: Server) -> Result<(), String> {
          username = ?5,
          ssh_key_id = ?6,
          notes = ?7,
-         updated_at = ?8
-         WHERE id = ?9",
+         updated_at = ?8,
+         auth_method = ?9,
+         jump_host_id = ?10,
+         sudo_mode = ?11,
+         settings = ?12
+         WHERE id = ?13",
         params![
             server.name,
             server.hostname,
@@ -121,59 +148,66 @@ pub fn update_server(conn: &Connection, server: Server) -> Result<(), String> {
             server.ssh_key_id,
             server.notes,
             updated_at,
+            server.auth_method.as_str(),
+            server.jump_host_id,
+            server.sudo_mode.as_str(),
+            settings_json,
             id
         ],
     ).map_err(|e| e.to_string())?;
 
+    // A connected server picks up the new mode right away
+    session::set_sudo_mode(id, server.sudo_mode);
+
     Ok(())
 }
 
-pub fn delete_server(conn: &Connection, id: i64) -> Result<(), String> {
+pub fn set_server_os(conn: &Connection, id: i64, os: &OsRelease) -> Result<(), String> {
     conn.execute(
-        "DELETE FROM servers WHERE id = ?1",
-        params![id],
+        "UPDATE servers SET os_id = ?1, os_like = ?2, os_version = ?3, os_name = ?4 WHERE id = ?5",
+        params![os.id, os.id_like.join(" "), os.version_id, os.name, id],
     ).map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
-pub fn connect_with_ssh_key(server: &Server, ssh_key_path: &str) -> Result<Session, String> {
-    let tcp = TcpStream::connect(format!("{}:{}", server.hostname, server.port))
-        .map_err(|e| format!("Failed to connect to server: {}", e))?;
+pub fn set_server_facts(conn: &Connection, id: i64, facts: &ServerFacts) -> Result<(), String> {
+    let facts_json = serde_json::to_string(facts).map_err(|e| e.to_string())?;
 
-    let mut sess = Session::new()
-        .map_err(|e| format!("Failed to create SSH session: {}", e))?;
-    sess.set_tcp_stream(tcp);
-    sess.handshake().map_err(|e| format!("SSH handshake failed: {}", e))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO server_facts (server_id, facts, gathered_at) VALUES (?1, ?2, ?3)",
+        params![id, facts_json, facts.gathered_at],
+    ).map_err(|e| e.to_string())?;
 
-    let path = Path::new(ssh_key_path);
-    sess.userauth_pubkey_file(&server.username, None, path, None)
-        .map_err(|e| format!("SSH key authentication failed: {}", e))?;
+    Ok(())
+}
 
-    if !sess.authenticated() {
-        return Err("Authentication failed".to_string());
-    }
+pub fn delete_server(conn: &Connection, id: i64) -> Result<(), String> {
+    known_hosts::delete_known_host(conn, id)?;
+    forwarding::delete_server_tunnels(conn, id)?;
+    provision::delete_server_progress(conn, id)?;
 
-    Ok(sess)
-}
+    conn.execute(
+        "DELETE FROM settings WHERE key = ?1",
+        params![proxy::password_key(id)],
+    ).map_err(|e| e.to_string())?;
 
-pub fn connect_with_password(server: &Server, password: &str) -> Result<Session, String> {
-    let tcp = TcpStream::connect(format!("{}:{}", server.hostname, server.port))
-        .map_err(|e| format!("Failed to connect to server: {}", e))?;
+    conn.execute(
+        "DELETE FROM server_facts WHERE server_id = ?1",
+        params![id],
+    ).map_err(|e| e.to_string())?;
 
-    let mut sess = Session::new()
-        .map_err(|e| format!("Failed to create SSH session: {}", e))?;
-    sess.set_tcp_stream(tcp);
-    sess.handshake().map_err(|e| format!("SSH handshake failed: {}", e))?;
+    conn.execute(
+        "UPDATE servers SET jump_host_id = NULL WHERE jump_host_id = ?1",
+        params![id],
+    ).map_err(|e| e.to_string())?;
 
-    sess.userauth_password(&server.username, password)
-        .map_err(|e| format!("Password authentication failed: {}", e))?;
+    conn.execute(
+        "DELETE FROM servers WHERE id = ?1",
+        params![id],
+    ).map_err(|e| e.to_string())?;
 
-    if !sess.authenticated() {
-        return Err("Authentication failed".to_string());
-    }
-    
-    Ok(sess)
+    Ok(())
 }
 
 pub fn get_ssh_key_path(conn: &Connection, ssh_key_id: i64) -> Result<String, String> {
@@ -184,99 +218,551 @@ pub fn get_ssh_key_path(conn: &Connection, ssh_key_id: i64) -> Result<String, St
     ).map_err(|e| format!("Failed to get SSH key path: {}", e))
 }
 
-pub fn try_connect_to_server(conn: &Connection, server: &Server) -> Result<(), String> {
-    let session_result = if let Some(ssh_key_id) = server.ssh_key_id {
-        match get_ssh_key_path(conn, ssh_key_id) {
-            Ok(ssh_key_path) => {
-                let substr = ".pub";
-                let private_key_path = ssh_key_path.replace(substr, "");
-                connect_with_ssh_key(server, &private_key_path)
-            },
-            Err(e) => Err(format!("Failed to get SSH key: {}", e))
+/// Connectors relay keyboard-interactive prompts to the frontend when given an app handle.
+fn connector<'a>(conn: &'a Connection, master_key: &'a MasterKey, prompts: Option<&'a AppHandle>) -> Connector<'a> {
+    let connector = Connector::new(conn, master_key);
+
+    match prompts {
+        Some(app_handle) => connector.with_prompts(app_handle),
+        None => connector,
+    }
+}
+
+pub fn try_connect_to_server(conn: &Connection, master_key: &MasterKey, server: &Server, prompts: Option<&AppHandle>) -> Result<(), ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to connect")?;
+
+    let session = connector(conn, master_key, prompts).connect(server)?;
+
+    session::insert(server_id, session, server.sudo_mode)?;
+    remember_facts(conn, server_id);
+    supervisor::connected(server_id, None, None);
+
+    Ok(())
+}
+
+/// Connect with a passphrase supplied by the user, optionally remembering it for the key.
+pub fn try_connect_with_key_passphrase(conn: &Connection, master_key: &MasterKey, server: &Server, passphrase: &str, remember: bool, prompts: Option<&AppHandle>) -> Result<(), ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to connect")?;
+
+    let session = connector(conn, master_key, prompts).connect_with_key_passphrase(server, passphrase)?;
+
+    if remember {
+        if let Some(ssh_key_id) = server.ssh_key_id {
+            ssh_key::store_ssh_key_passphrase(conn, master_key, ssh_key_id, passphrase)?;
         }
-    } else {
-        Err("No SSH key set for this server, and password flow not initiated from here.".to_string())
+    }
+
+    session::insert(server_id, session, server.sudo_mode)?;
+    remember_facts(conn, server_id);
+    supervisor::connected(server_id, None, Some(passphrase));
+
+    Ok(())
+}
+
+pub fn try_connect_with_password(conn: &Connection, master_key: &MasterKey, server: &Server, password: &str, prompts: Option<&AppHandle>) -> Result<(), ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to connect")?;
+
+    let session = connector(conn, master_key, prompts).connect_with_password(server, password)?;
+
+    session::insert(server_id, session, server.sudo_mode)?;
+
+    // The login password is usually the sudo password too
+    if server.sudo_mode == SudoMode::Password {
+        session::set_sudo_password(server_id, Some(password.to_string()))?;
+    }
+
+    remember_facts(conn, server_id);
+    supervisor::connected(server_id, Some(password), None);
+
+    Ok(())
+}
+
+/// Gather the facts of a server that was just connected to. Failing to doesn't fail the
+/// connection; package commands detect the distribution again when they need it.
+fn remember_facts(conn: &Connection, server_id: i64) {
+    if let Err(e) = refresh_server_facts(conn, server_id) {
+        eprintln!("Failed to gather the facts of server {}: {}", server_id, e);
+    }
+}
+
+/// Detect the distribution of a connected server and gather its facts, storing both.
+pub fn refresh_server_facts(conn: &Connection, server_id: i64) -> Result<ServerFacts, CommandError> {
+    let os = distro::detect(server_id)?;
+    set_server_os(conn, server_id, &os)?;
+
+    let facts = facts::gather(server_id)?;
+    set_server_facts(conn, server_id, &facts)?;
+
+    Ok(facts)
+}
+
+/// Connect without authenticating and replace the stored host key, but only if the server still
+/// presents the key the user was shown.
+pub fn accept_host_key(conn: &Connection, master_key: &MasterKey, server: &Server, fingerprint: &str) -> Result<KnownHost, ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to accept its host key")?;
+
+    let sess = Connector::new(conn, master_key).handshake(server)?;
+
+    let (key_type, key) = known_hosts::session_host_key(&sess)?;
+    let actual_fingerprint = known_hosts::fingerprint(&key);
+
+    let _ = sess.disconnect(None, "Host key accepted", None);
+
+    if actual_fingerprint != fingerprint {
+        return Err(format!("Host key changed again: expected {}, got {}", fingerprint, actual_fingerprint).into());
+    }
+
+    Ok(known_hosts::save_known_host(conn, server_id, &key_type, &key)?)
+}
+
+/// Open an interactive terminal on a new session of its own, authenticated with the stored
+/// credentials or, for password servers, the given password.
+pub fn open_terminal(app_handle: AppHandle, conn: &Connection, master_key: &MasterKey, server: &Server, cols: u32, rows: u32, password: Option<String>) -> Result<TerminalInfo, ConnectionError> {
+    let server_id = server.id.ok_or("Server ID is required to open a terminal")?;
+
+    let session = connect_dedicated(conn, master_key, server, password.as_deref(), Some(&app_handle))?;
+
+    Ok(terminal::open(app_handle, session, server_id, cols, rows)?)
+}
+
+/// A session of its own for long-lived channels such as terminals and tunnels, authenticated
+/// with the stored credentials or, for password servers, the given password.
+fn connect_dedicated(conn: &Connection, master_key: &MasterKey, server: &Server, password: Option<&str>, prompts: Option<&AppHandle>) -> Result<Session, ConnectionError> {
+    let connector = connector(conn, master_key, prompts);
+
+    match password {
+        Some(password) => connector.connect_with_password(server, password),
+        None => connector.connect(server),
+    }
+}
+
+pub fn create_tunnel(app_handle: AppHandle, conn: &Connection, master_key: &MasterKey, server: &Server, tunnel: Tunnel, password: Option<&str>) -> Result<TunnelStatus, ConnectionError> {
+    let tunnel = forwarding::add_tunnel(conn, tunnel)?;
+    let id = tunnel.id.ok_or("Failed to store tunnel")?;
+
+    // A tunnel that can't be started right away is not worth keeping
+    start_tunnel(app_handle, conn, master_key, server, tunnel, password, true).inspect_err(|_| {
+        let _ = forwarding::delete_tunnel(conn, id);
+    })
+}
+
+/// Interactive starts may prompt the user for keyboard-interactive answers; background
+/// restores can't, and fail on servers that need them.
+pub fn start_tunnel(app_handle: AppHandle, conn: &Connection, master_key: &MasterKey, server: &Server, tunnel: Tunnel, password: Option<&str>, interactive: bool) -> Result<TunnelStatus, ConnectionError> {
+    if tunnel.id.is_some_and(forwarding::is_running) {
+        return Ok(forwarding::status(tunnel));
+    }
+
+    let prompts = interactive.then_some(&app_handle);
+    let session = connect_dedicated(conn, master_key, server, password, prompts)?;
+
+    Ok(forwarding::start(app_handle, session, tunnel)?)
+}
+
+/// Bring back the server's auto-restore tunnels that are not running, after it was connected.
+/// Failures are only logged, since the connection itself succeeded.
+pub fn restore_tunnels(app_handle: &AppHandle, conn: &Connection, master_key: &MasterKey, server: &Server, password: Option<&str>) {
+    let Some(server_id) = server.id else {
+        return;
     };
 
-    match session_result {
-        Ok(session) => {
-            let mut active_session_guard = ACTIVE_SESSION.lock().map_err(|_| "Failed to acquire session lock for connect".to_string())?;
-            *active_session_guard = Some(session);
-            Ok(())
+    let tunnels = match forwarding::get_tunnels(conn, Some(server_id)) {
+        Ok(tunnels) => tunnels,
+        Err(e) => {
+            eprintln!("Failed to load tunnels of server {}: {}", server_id, e);
+            return;
+        }
+    };
+
+    for tunnel in tunnels.into_iter().filter(|tunnel| tunnel.auto_restore) {
+        let local_port = tunnel.local_port;
+        if let Err(e) = start_tunnel(app_handle.clone(), conn, master_key, server, tunnel, password, false) {
+            eprintln!("Failed to restore tunnel on local port {} for server {}: {}", local_port, server_id, e);
         }
-        Err(e) => Err(e)
     }
 }
 
-pub fn disconnect_from_server() -> Result<(), String> {
-    let mut active_session_guard = ACTIVE_SESSION.lock().map_err(|_| "Failed to acquire session lock for disconnect".to_string())?;
+pub fn list_agent_identities() -> Result<Vec<AgentIdentity>, String> {
+    agent::list_identities()
+}
+
+pub fn get_known_host(conn: &Connection, server_id: i64) -> Result<Option<KnownHost>, String> {
+    known_hosts::get_known_host(conn, server_id)
+}
+
+pub fn forget_host_key(conn: &Connection, server_id: i64) -> Result<(), String> {
+    known_hosts::delete_known_host(conn, server_id)
+}
+
+pub fn import_known_hosts(conn: &Connection) -> Result<Vec<KnownHost>, String> {
+    let servers = get_servers(conn)?;
+
+    known_hosts::import_known_hosts(conn, &servers)
+}
+
+pub fn preview_ssh_config(conn: &Connection) -> Result<Vec<SshConfigHost>, String> {
+    let servers = get_servers(conn)?;
+
+    ssh_config::preview(conn, &servers)
+}
+
+pub fn import_ssh_config(conn: &Connection, aliases: &[String]) -> Result<Vec<Server>, String> {
+    let servers = get_servers(conn)?;
+
+    ssh_config::import(conn, aliases, servers)
+}
+
+pub fn disconnect_from_server(server_id: i64) -> Result<(), String> {
+    supervisor::disconnected(server_id);
+
+    session::close(server_id)
+}
+
+pub fn disconnect_all_servers() -> Result<Vec<i64>, String> {
+    let closed = session::close_all()?;
+
+    for server_id in &closed {
+        supervisor::disconnected(*server_id);
+    }
+
+    Ok(closed)
+}
+
+pub fn list_sessions() -> Result<Vec<SessionInfo>, String> {
+    session::list()
+}
+
+pub fn evict_idle_sessions(max_idle_seconds: u64) -> Result<Vec<i64>, String> {
+    let evicted = session::evict_idle(Duration::from_secs(max_idle_seconds))?;
 
-    if let Some(session) = active_session_guard.take() { 
-        match session.disconnect(Some(DisconnectCode::ByApplication), "User initiated disconnect", Some("")) {
-            Ok(_) => {
-                Ok(())
-            }
-            Err(e) => {
-                eprintln!("Failed to gracefully disconnect SSH session: {}. Session cleared.", e);
-                Err(format!("SSH disconnect call failed: {}", e))
+    for server_id in &evicted {
+        supervisor::disconnected(*server_id);
+    }
+
+    Ok(evicted)
+}
+
+/// Remember the sudo password for the server's current session, once sudo has accepted it.
+pub fn set_sudo_password(server_id: i64, password: &str) -> Result<(), CommandError> {
+    session::set_sudo_password(server_id, Some(password.to_string()))?;
+
+    if let Err(e) = query(server_id, RemoteCommand::sudo("true")) {
+        session::set_sudo_password(server_id, None)?;
+        return Err(e.context("sudo did not accept the password"));
+    }
+
+    Ok(())
+}
+
+/// Limit for quick lookups that only read state from the server
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+/// Limit for commands that change configuration or restart services
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Limit for package installs and updates, which can take a long time on slow mirrors
+pub const INSTALL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+pub fn cmd(server_id: i64, command: impl Into<RemoteCommand>) -> Result<CommandResult, CommandError> {
+    cmd_timeout(server_id, command, DEFAULT_TIMEOUT)
+}
+
+pub fn cmd_timeout(server_id: i64, command: impl Into<RemoteCommand>, timeout: Duration) -> Result<CommandResult, CommandError> {
+    cmd_streaming(server_id, &command.into(), timeout, &AtomicBool::new(false), |_, _| {})
+}
+
+/// Run a quick read-only command. It is retried once if the connection drops while it runs.
+pub fn query(server_id: i64, command: impl Into<RemoteCommand>) -> Result<CommandResult, CommandError> {
+    cmd_timeout(server_id, command.into().idempotent(), QUERY_TIMEOUT)
+}
+
+/// Run a read-only check such as `test -f` or `id -u`, telling a non-zero exit apart from
+/// failing to run the command at all.
+pub fn probe(server_id: i64, command: impl Into<RemoteCommand>) -> Result<bool, CommandError> {
+    exit_ok(query(server_id, command))
+}
+
+/// Whether a command exited with status zero; other failures are passed on.
+pub fn exit_ok(result: Result<CommandResult, CommandError>) -> Result<bool, CommandError> {
+    match result {
+        Ok(_) => Ok(true),
+        Err(CommandError::Exited { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Run a command, feeding it its stdin and passing stdout and stderr to `on_output` as they arrive.
+/// Resolves like `cmd` once the command exits. If it runs longer than `timeout` or `cancelled`
+/// is set, the channel is closed so the session is free for other callers again.
+/// Privileged commands follow the server's sudo mode. If the connection turns out to be lost,
+/// the server is reconnected and the command run again, provided it never started or is idempotent.
+pub fn cmd_streaming<F>(server_id: i64, command: &RemoteCommand, timeout: Duration, cancelled: &AtomicBool, mut on_output: F) -> Result<CommandResult, CommandError>
+where
+    F: FnMut(OutputStream, &str),
+{
+    let result = sudo_streaming(server_id, command, timeout, cancelled, &mut on_output);
+
+    match result {
+        Err(CommandError::Disconnected { result: ref partial, .. }) if partial.is_none() || command.is_idempotent() => {
+            if let Err(e) = supervisor::reconnect(server_id) {
+                eprintln!("Not retrying '{}' on server {}: {}", command, server_id, e);
+                return result;
             }
+
+            sudo_streaming(server_id, command, timeout, cancelled, &mut on_output)
         }
-    } else {
-        Err("No active session to disconnect.".to_string())
+        result => result,
     }
 }
 
-pub fn cmd(command: &str) -> Result<String, String> {
-    let mut active_session_guard = ACTIVE_SESSION.lock().map_err(|_| "Failed to acquire session lock for command execution".to_string())?;
+fn sudo_streaming<F>(server_id: i64, command: &RemoteCommand, timeout: Duration, cancelled: &AtomicBool, on_output: F) -> Result<CommandResult, CommandError>
+where
+    F: FnMut(OutputStream, &str),
+{
+    let command_line = command.render();
+
+    if !command.uses_sudo() {
+        return exec_streaming(server_id, &command_line, &command_line, command.stdin_data(), timeout, cancelled, on_output);
+    }
+
+    let sudo = session::sudo(server_id)?;
+    let exec_line = format!("{} {}", sudo_wrapper(sudo.mode), command_line);
+
+    if sudo.mode != SudoMode::Password {
+        return exec_streaming(server_id, &command_line, &exec_line, command.stdin_data(), timeout, cancelled, on_output);
+    }
+
+    let Some(password) = sudo.password else {
+        return Err(CommandError::SudoPasswordRequired {
+            message: "A sudo password is required to run this command".to_string(),
+            server_id,
+        });
+    };
+
+    // The wrapper reads the password up to the newline and leaves the rest for the command
+    let mut stdin = format!("{}\n", password).into_bytes();
+    stdin.extend_from_slice(command.stdin_data().unwrap_or_default());
+
+    let result = exec_streaming(server_id, &command_line, &exec_line, Some(&stdin), timeout, cancelled, on_output);
+
+    match result {
+        Err(CommandError::Exited { result, .. }) if is_sudo_password_rejected(&result.stderr) => {
+            session::set_sudo_password(server_id, None)?;
+
+            Err(CommandError::SudoPasswordRequired {
+                message: "The sudo password was rejected".to_string(),
+                server_id,
+            })
+        }
+        result => result,
+    }
+}
+
+/// A shell function shadowing `sudo` for the rest of the command line, so every privileged
+/// command in it follows the sudo mode. With a password, the first line of stdin is kept, and
+/// each `sudo` in the chain or a pipeline gets it ahead of its own stdin. `-k` makes sudo always
+/// read it rather than pass it on to the command when credentials happen to be cached.
+fn sudo_wrapper(mode: SudoMode) -> &'static str {
+    match mode {
+        SudoMode::Passwordless => "sudo() { command sudo -n \"$@\"; };",
+        SudoMode::Password => "IFS= read -r sudo_password; \
+            sudo() { { printf '%s\\n' \"$sudo_password\"; cat; } | command sudo -S -k -p '' \"$@\"; };",
+        SudoMode::Root => "sudo() { if [ \"$1\" = -u ]; then command sudo \"$@\"; else \"$@\"; fi; };",
+    }
+}
+
+fn is_sudo_password_rejected(stderr: &str) -> bool {
+    stderr.contains("Sorry, try again") || stderr.contains("incorrect password attempt")
+}
+
+/// Run `exec_line` with `stdin`, reporting it as `command_line` so wrappers and secrets stay out
+/// of results and error messages. The session is locked one poll at a time, so other commands
+/// can run on it in between.
+fn exec_streaming<F>(
+    server_id: i64,
+    command_line: &str,
+    exec_line: &str,
+    stdin: Option<&[u8]>,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+    mut on_output: F,
+) -> Result<CommandResult, CommandError>
+where
+    F: FnMut(OutputStream, &str),
+{
+    let mut stdin_open = stdin.is_some();
+    let mut stdin = stdin.unwrap_or_default();
+
+    let disconnected = |message: String, result: Option<CommandResult>| CommandError::Disconnected { message, server_id, result };
+
+    let shared = session::shared(server_id).map_err(|e| disconnected(e, None))?;
+
+    let opened = {
+        let session = shared.lock()?;
 
-    if let Some(session) = active_session_guard.as_mut() {
         if !session.authenticated() {
-            return Err("Session is not authenticated.".to_string());
+            return Err("Session is not authenticated.".into());
         }
 
-        let mut channel = match session.channel_session() {
-            Ok(ch) => ch,
-            Err(e) => return Err(format!("Failed to open SSH channel: {}", e)),
-        };
+        session.channel_session().and_then(|mut channel| channel.exec(exec_line).map(|_| channel))
+    };
 
-        if let Err(e) = channel.exec(command) {
-            return Err(format!("Failed to execute command '{}': {}", command, e));
+    let mut channel = match opened {
+        Ok(channel) => channel,
+        Err(e) if !shared.is_alive() => return Err(disconnected(format!("Connection to server {} was lost: {}", server_id, e), None)),
+        Err(e) => return Err(format!("Failed to execute command '{}': {}", command_line, e).into()),
+    };
+
+    let mut stdout_decoder = Utf8Decoder::default();
+    let mut stderr_decoder = Utf8Decoder::default();
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    let mut buf = [0u8; 8192];
+    let started = Instant::now();
+
+    let read_result = loop {
+        if cancelled.load(Ordering::Relaxed) {
+            break Err(Interrupted::Cancelled);
         }
+        if started.elapsed() > timeout {
+            break Err(Interrupted::TimedOut);
+        }
+
+        let session = match shared.lock() {
+            Ok(session) => session,
+            Err(e) => break Err(Interrupted::Failed(std::io::Error::other(e))),
+        };
 
-        let mut output = String::new();
-        if let Err(e) = channel.read_to_string(&mut output) {
-            eprintln!("Warning: Failed to read command output: {}", e);
+        // Poll both streams so a command filling up stderr can't stall while we wait on stdout
+        session.set_blocking(false);
+
+        let mut progressed = false;
+
+        let step: Result<(), std::io::Error> = (|| {
+            if !stdin.is_empty() {
+                match channel.write(stdin) {
+                    Ok(n) => {
+                        stdin = &stdin[n..];
+                        progressed = true;
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            } else if stdin_open {
+                match channel.send_eof() {
+                    Ok(()) => stdin_open = false,
+                    Err(e) if is_would_block(&e) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            match channel.read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    let chunk = stdout_decoder.decode(&buf[..n]);
+                    on_output(OutputStream::Stdout, &chunk);
+                    stdout.push_str(&chunk);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+
+            match channel.stderr().read(&mut buf) {
+                Ok(0) => {}
+                Ok(n) => {
+                    let chunk = stderr_decoder.decode(&buf[..n]);
+                    on_output(OutputStream::Stderr, &chunk);
+                    stderr.push_str(&chunk);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+
+            Ok(())
+        })();
+
+        session.set_blocking(true);
+        drop(session);
+
+        if let Err(e) = step {
+            break Err(Interrupted::Failed(e));
         }
 
-        let mut stderr_output = String::new();
-        if let Err(e) = channel.stderr().read_to_string(&mut stderr_output) {
-            eprintln!("Warning: Failed to read command stderr: {}", e);
+        if channel.eof() && !progressed {
+            break Ok(());
         }
 
-        if !stderr_output.is_empty() {
-            output.push_str("\n--- STDERR ---\n");
-            output.push_str(&stderr_output);
+        if !progressed {
+            std::thread::sleep(Duration::from_millis(10));
         }
+    };
+
+    stdout.push_str(&stdout_decoder.finish());
+    stderr.push_str(&stderr_decoder.finish());
+
+    let mut result = CommandResult {
+        command: command_line.to_string(),
+        stdout,
+        stderr,
+        exit_code: None,
+        duration: started.elapsed().as_millis() as u64,
+    };
 
-        match channel.wait_close() {
-            Ok(_) => {},
-            Err(e) => eprintln!("Warning: Error during channel close: {}", e),
+    if let Err(Interrupted::Failed(e)) = &read_result {
+        if !shared.is_alive() {
+            return Err(disconnected(format!("Connection lost while running '{}': {}", command_line, e), Some(result)));
         }
+    }
+
+    let _session = shared.lock()?;
 
-        let exit_status = match channel.exit_status() {
-            Ok(status) => status,
-            Err(e) => {
-                return Err(format!("Failed to get command exit status: {}", e));
+    match read_result {
+        Ok(()) => {}
+        Err(Interrupted::Failed(e)) => eprintln!("Warning: Failed to read command output: {}", e),
+        Err(interrupted) => {
+            // Closing the channel makes sshd hang up on the remote process
+            if let Err(e) = channel.close() {
+                eprintln!("Warning: Failed to close channel of interrupted command: {}", e);
             }
-        };
 
-        if exit_status == 0 {
-            Ok(output)
-        } else {
-            Err(format!("Command '{}' exited with status {}.\nOutput:\n{}\nStderr:\n{}", command, exit_status, output, stderr_output))
+            return Err(match interrupted {
+                Interrupted::TimedOut => CommandError::TimedOut {
+                    message: format!("Command '{}' timed out after {}s", command_line, timeout.as_secs()),
+                    result,
+                },
+                _ => CommandError::Cancelled {
+                    message: format!("Command '{}' was cancelled", command_line),
+                    result,
+                },
+            });
         }
+    }
+
+    if let Err(e) = channel.wait_close() {
+        eprintln!("Warning: Error during channel close: {}", e);
+    }
 
+    let exit_code = channel.exit_status()
+        .map_err(|e| format!("Failed to get command exit status: {}", e))?;
+    result.exit_code = Some(exit_code);
+
+    if exit_code == 0 {
+        Ok(result)
     } else {
-        Err("No active SSH session found.".to_string())
+        Err(CommandError::Exited {
+            message: format!("Command '{}' exited with status {}", command_line, exit_code),
+            result,
+        })
     }
 }
+
+/// libssh2 reports a non-blocking call that has to be retried as `LIBSSH2_ERROR_EAGAIN`
+fn is_would_block(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::Session(-37)
+}
+
+enum Interrupted {
+    Cancelled,
+    TimedOut,
+    Failed(std::io::Error),
+}
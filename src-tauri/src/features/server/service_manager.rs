@@ -0,0 +1,88 @@
+use super::model::{CommandError, ServiceAction, ServiceStatus};
+use super::remote::{quote, RemoteCommand};
+use super::service;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How services are started and enabled on a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceManager {
+    Systemd,
+    /// Init scripts run through `service`, for containers and older systems without systemd
+    SysV,
+}
+
+/// Service managers detected per server. The init system doesn't change while connected.
+static MANAGERS: Lazy<Mutex<HashMap<i64, ServiceManager>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl ServiceManager {
+    /// The service manager of a connected server. systemd is running if this directory exists,
+    /// as `sd_booted()` checks.
+    pub fn of(server_id: i64) -> Result<Self, CommandError> {
+        if let Some(manager) = MANAGERS.lock().ok().and_then(|managers| managers.get(&server_id).copied()) {
+            return Ok(manager);
+        }
+
+        let manager = if service::probe(server_id, "test -d /run/systemd/system")? {
+            ServiceManager::Systemd
+        } else if service::probe(server_id, "command -v service")? {
+            ServiceManager::SysV
+        } else {
+            return Err("The server has neither systemd nor the service command".into());
+        };
+
+        if let Ok(mut managers) = MANAGERS.lock() {
+            managers.insert(server_id, manager);
+        }
+
+        Ok(manager)
+    }
+
+    /// The command performing `action` on `service`
+    pub fn command(&self, action: ServiceAction, service: &str) -> RemoteCommand {
+        match (self, action) {
+            (ServiceManager::Systemd, action) => RemoteCommand::sudo("systemctl").arg(action.as_str()).arg(service),
+            (ServiceManager::SysV, ServiceAction::Enable) => RemoteCommand::shell(&format!(
+                "if command -v update-rc.d >/dev/null; then update-rc.d {0} defaults; else chkconfig {0} on; fi",
+                quote(service)
+            )).elevated(),
+            (ServiceManager::SysV, ServiceAction::Disable) => RemoteCommand::shell(&format!(
+                "if command -v update-rc.d >/dev/null; then update-rc.d {0} disable; else chkconfig {0} off; fi",
+                quote(service)
+            )).elevated(),
+            (ServiceManager::SysV, action) => RemoteCommand::sudo("service").arg(service).arg(action.as_str()),
+        }
+    }
+
+    /// Whether `service` is running and starts on boot
+    pub fn status(&self, server_id: i64, service: &str) -> Result<ServiceStatus, CommandError> {
+        let name = quote(service);
+
+        // Both checks exit non-zero for the negative answer, which is still an answer
+        let script = match self {
+            ServiceManager::Systemd => format!(
+                "systemctl is-active {0}; systemctl is-enabled {0} 2>/dev/null || true",
+                name
+            ),
+            ServiceManager::SysV => format!(
+                "service {0} status >/dev/null 2>&1 && echo active || echo inactive; ls /etc/rc[2-5].d/S*{0} >/dev/null 2>&1 && echo enabled || echo disabled",
+                name
+            ),
+        };
+
+        let output = service::query(server_id, &script)
+            .map_err(|e| e.context(format!("Failed to get the status of {}", service)))?;
+
+        let mut lines = output.stdout.lines().map(str::trim);
+        let state = lines.next().filter(|state| !state.is_empty()).unwrap_or("unknown").to_string();
+        let enabled = lines.next() == Some("enabled");
+
+        Ok(ServiceStatus {
+            name: service.to_string(),
+            active: state == "active",
+            state,
+            enabled,
+        })
+    }
+}
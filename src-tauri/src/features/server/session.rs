@@ -0,0 +1,293 @@
+use super::model::{SessionInfo, SudoMode};
+use super::supervisor;
+use ssh2::{Session, DisconnectCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+
+struct PooledSession {
+    session: Mutex<Session>,
+    connected_at: String,
+    last_used: Mutex<Instant>,
+    sudo: Mutex<Sudo>,
+    /// Commands with a channel open on the session, see `Shared`
+    commands: AtomicUsize,
+}
+
+/// Sudo settings of a session. The password is only ever kept in memory, for as long as the
+/// session lives.
+#[derive(Clone)]
+pub struct Sudo {
+    pub mode: SudoMode,
+    pub password: Option<String>,
+}
+
+impl PooledSession {
+    fn idle_for(&self) -> Duration {
+        self.last_used.lock().map(|t| t.elapsed()).unwrap_or_default()
+    }
+
+    fn is_busy(&self) -> bool {
+        self.commands.load(Ordering::Relaxed) > 0 || self.session.try_lock().is_err()
+    }
+
+    fn lock(&self, server_id: i64) -> Result<MutexGuard<'_, Session>, String> {
+        let session = self.session.lock()
+            .map_err(|_| format!("Failed to acquire session lock for server {}", server_id))?;
+
+        if let Ok(mut last_used) = self.last_used.lock() {
+            *last_used = Instant::now();
+        }
+
+        Ok(session)
+    }
+
+    fn close(&self, server_id: i64) -> Result<(), String> {
+        let session = self.session.lock()
+            .map_err(|_| format!("Failed to acquire session lock for server {}", server_id))?;
+
+        session.disconnect(Some(DisconnectCode::ByApplication), "User initiated disconnect", Some(""))
+            .map_err(|e| {
+                eprintln!("Failed to gracefully disconnect SSH session for server {}: {}. Session cleared.", server_id, e);
+                format!("SSH disconnect call failed: {}", e)
+            })
+    }
+}
+
+static SESSIONS: Lazy<Mutex<HashMap<i64, Arc<PooledSession>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static KEEPALIVE: Once = Once::new();
+
+fn sessions() -> Result<MutexGuard<'static, HashMap<i64, Arc<PooledSession>>>, String> {
+    SESSIONS.lock().map_err(|_| "Failed to acquire session pool lock".to_string())
+}
+
+/// Register an authenticated session for a server, closing any session it replaces.
+pub fn insert(server_id: i64, session: Session, sudo_mode: SudoMode) -> Result<(), String> {
+    let pooled = PooledSession {
+        session: Mutex::new(session),
+        connected_at: chrono::Local::now().to_rfc3339(),
+        last_used: Mutex::new(Instant::now()),
+        sudo: Mutex::new(Sudo { mode: sudo_mode, password: None }),
+        commands: AtomicUsize::new(0),
+    };
+
+    let previous = sessions()?.insert(server_id, Arc::new(pooled));
+
+    if let Some(previous) = previous {
+        let _ = previous.close(server_id);
+    }
+
+    KEEPALIVE.call_once(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(Duration::from_secs(1));
+            send_keepalives();
+        });
+    });
+
+    Ok(())
+}
+
+/// libssh2 only sends keepalives when asked to, so idle sessions are polled here.
+/// Sessions that are busy running a command are skipped, and dead ones are dropped.
+fn send_keepalives() {
+    let pooled: Vec<(i64, Arc<PooledSession>)> = match sessions() {
+        Ok(pool) => pool.iter().map(|(id, pooled)| (*id, pooled.clone())).collect(),
+        Err(_) => return,
+    };
+
+    for (server_id, pooled) in pooled {
+        if pooled.commands.load(Ordering::Relaxed) > 0 {
+            continue;
+        }
+
+        let result = match pooled.session.try_lock() {
+            Ok(session) => session.keepalive_send(),
+            Err(_) => continue,
+        };
+
+        if let Err(e) = result {
+            drop_dead(server_id, &pooled, format!("Keepalive failed: {}", e));
+        }
+    }
+}
+
+/// Drop a session whose connection is gone, unless it was replaced already, and let the
+/// supervisor reconnect it.
+fn drop_dead(server_id: i64, pooled: &Arc<PooledSession>, error: String) {
+    eprintln!("Lost session of server {}: {}. Dropping session.", server_id, error);
+
+    let removed = match sessions() {
+        Ok(mut pool) if pool.get(&server_id).is_some_and(|current| Arc::ptr_eq(current, pooled)) => pool.remove(&server_id).is_some(),
+        _ => false,
+    };
+
+    if removed {
+        let sudo_password = pooled.sudo.lock().ok().and_then(|sudo| sudo.password.clone());
+        supervisor::lost(server_id, error, sudo_password);
+    }
+}
+
+/// Run `f` against the session of a server. Only that server's session is locked while `f` runs.
+pub fn with_session<T, E, F>(server_id: i64, f: F) -> Result<T, E>
+where
+    E: From<String>,
+    F: FnOnce(&mut Session) -> Result<T, E>,
+{
+    let pooled = pooled(server_id)?;
+    let mut session = pooled.lock(server_id)?;
+
+    f(&mut session)
+}
+
+/// A server's session shared with other commands. Each step of a command locks the session only
+/// briefly, so several commands can have a channel open on the same connection at once.
+pub struct Shared {
+    server_id: i64,
+    pooled: Arc<PooledSession>,
+}
+
+impl Shared {
+    pub fn lock(&self) -> Result<MutexGuard<'_, Session>, String> {
+        self.pooled.lock(self.server_id)
+    }
+
+    /// After a channel error, tell a dead connection apart from a failing command. A dead
+    /// session is dropped from the pool.
+    pub fn is_alive(&self) -> bool {
+        let result = match self.lock() {
+            Ok(session) => session.keepalive_send(),
+            Err(_) => return false,
+        };
+
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                drop_dead(self.server_id, &self.pooled, format!("Connection lost: {}", e));
+                false
+            }
+        }
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        self.pooled.commands.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Share the session of a server for running a command. It counts as busy until dropped.
+pub fn shared(server_id: i64) -> Result<Shared, String> {
+    let pooled = pooled(server_id)?;
+    pooled.commands.fetch_add(1, Ordering::Relaxed);
+
+    Ok(Shared { server_id, pooled })
+}
+
+pub fn is_connected(server_id: i64) -> bool {
+    pooled(server_id).is_ok()
+}
+
+fn pooled(server_id: i64) -> Result<Arc<PooledSession>, String> {
+    sessions()?
+        .get(&server_id)
+        .cloned()
+        .ok_or_else(|| format!("No active SSH session found for server {}.", server_id))
+}
+
+pub fn sudo(server_id: i64) -> Result<Sudo, String> {
+    let pooled = pooled(server_id)?;
+    let sudo = pooled.sudo.lock()
+        .map_err(|_| format!("Failed to acquire sudo settings lock for server {}", server_id))?;
+
+    Ok(sudo.clone())
+}
+
+/// Switch the sudo mode of a connected server. Does nothing if it isn't connected.
+pub fn set_sudo_mode(server_id: i64, mode: SudoMode) {
+    let Ok(pooled) = pooled(server_id) else {
+        return;
+    };
+
+    let Ok(mut sudo) = pooled.sudo.lock() else {
+        return;
+    };
+
+    if sudo.mode != mode {
+        *sudo = Sudo { mode, password: None };
+    }
+}
+
+pub fn set_sudo_password(server_id: i64, password: Option<String>) -> Result<(), String> {
+    let pooled = pooled(server_id)?;
+    let mut sudo = pooled.sudo.lock()
+        .map_err(|_| format!("Failed to acquire sudo settings lock for server {}", server_id))?;
+
+    sudo.password = password;
+
+    Ok(())
+}
+
+pub fn list() -> Result<Vec<SessionInfo>, String> {
+    let mut infos: Vec<SessionInfo> = sessions()?
+        .iter()
+        .map(|(server_id, pooled)| SessionInfo {
+            server_id: *server_id,
+            connected_at: pooled.connected_at.clone(),
+            idle_seconds: pooled.idle_for().as_secs(),
+            busy: pooled.is_busy(),
+        })
+        .collect();
+
+    infos.sort_by_key(|info| info.server_id);
+
+    Ok(infos)
+}
+
+pub fn close(server_id: i64) -> Result<(), String> {
+    let pooled = sessions()?
+        .remove(&server_id)
+        .ok_or_else(|| format!("No active session to disconnect for server {}.", server_id))?;
+
+    pooled.close(server_id)
+}
+
+/// Close every open session, returning the ids of the servers that were disconnected.
+pub fn close_all() -> Result<Vec<i64>, String> {
+    let drained: Vec<(i64, Arc<PooledSession>)> = sessions()?.drain().collect();
+
+    let mut closed = Vec::new();
+    for (server_id, pooled) in drained {
+        let _ = pooled.close(server_id);
+        closed.push(server_id);
+    }
+
+    closed.sort();
+
+    Ok(closed)
+}
+
+/// Close sessions that have not run anything for longer than `max_idle`. Busy sessions are kept.
+pub fn evict_idle(max_idle: Duration) -> Result<Vec<i64>, String> {
+    let mut evicted = Vec::new();
+
+    sessions()?.retain(|server_id, pooled| {
+        let idle = !pooled.is_busy() && pooled.idle_for() > max_idle;
+        if idle {
+            evicted.push((*server_id, pooled.clone()));
+        }
+        !idle
+    });
+
+    let mut closed = Vec::new();
+    for (server_id, pooled) in evicted {
+        let _ = pooled.close(server_id);
+        closed.push(server_id);
+    }
+
+    closed.sort();
+
+    Ok(closed)
+}
@@ -0,0 +1,305 @@
+use super::job::Job;
+use super::model::{RemoteFile, TransferDirection, TransferProgress};
+use super::{service, session};
+use ssh2::{FileStat, OpenFlags, OpenType, Sftp};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Transfers are split into chunks so other commands can use the session in between,
+/// and so an interrupted transfer can pick up at the last completed chunk.
+const CHUNK_SIZE: u64 = 2 * 1024 * 1024;
+/// Largest file that can be opened for editing in the app
+const MAX_TEXT_FILE: u64 = 1024 * 1024;
+
+fn with_sftp<T, F>(server_id: i64, f: F) -> Result<T, String>
+where
+    F: FnOnce(&Sftp) -> Result<T, String>,
+{
+    session::with_session(server_id, |session| {
+        let sftp = session.sftp().map_err(|e| format!("Failed to start SFTP: {}", e))?;
+        f(&sftp)
+    })
+}
+
+/// `ls -l` style permission string for a mode including its file type bits.
+fn permissions(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        _ => '-',
+    };
+
+    let mut permissions = String::from(kind);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        permissions.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+
+    permissions
+}
+
+/// Map of numeric id to name from `getent passwd` or `getent group`. SFTP only reports ids.
+fn id_names(server_id: i64, database: &str) -> HashMap<u32, String> {
+    let command = format!("getent {} | cut -d: -f1,3", database);
+
+    service::query(server_id, &command)
+        .map(|result| result.stdout)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let (name, id) = line.split_once(':')?;
+            Some((id.trim().parse().ok()?, name.to_string()))
+        })
+        .collect()
+}
+
+pub fn list_dir(server_id: i64, path: &str) -> Result<Vec<RemoteFile>, String> {
+    let entries = with_sftp(server_id, |sftp| {
+        let entries = sftp.readdir(Path::new(path))
+            .map_err(|e| format!("Failed to list {}: {}", path, e))?;
+
+        // Show symlinks to directories as directories, like a file manager would
+        Ok(entries
+            .into_iter()
+            .map(|(entry_path, stat)| {
+                let is_symlink = stat.file_type().is_symlink();
+                let is_dir = match is_symlink {
+                    true => sftp.stat(&entry_path).map(|target| target.is_dir()).unwrap_or(false),
+                    false => stat.is_dir(),
+                };
+                (entry_path, stat, is_dir, is_symlink)
+            })
+            .collect::<Vec<_>>())
+    })?;
+
+    let users = id_names(server_id, "passwd");
+    let groups = id_names(server_id, "group");
+
+    let mut files: Vec<RemoteFile> = entries
+        .into_iter()
+        .filter_map(|(entry_path, stat, is_dir, is_symlink)| {
+            let name = entry_path.file_name()?.to_string_lossy().into_owned();
+            let mode = stat.perm.unwrap_or(0);
+
+            Some(RemoteFile {
+                name,
+                path: entry_path.to_string_lossy().into_owned(),
+                is_dir,
+                is_symlink,
+                size: stat.size.unwrap_or(0),
+                mode: mode & 0o7777,
+                permissions: permissions(mode),
+                uid: stat.uid,
+                gid: stat.gid,
+                owner: stat.uid.and_then(|uid| users.get(&uid).cloned()),
+                group: stat.gid.and_then(|gid| groups.get(&gid).cloned()),
+                modified_at: stat.mtime
+                    .and_then(|mtime| chrono::DateTime::from_timestamp(mtime as i64, 0))
+                    .map(|mtime| mtime.with_timezone(&chrono::Local).to_rfc3339()),
+            })
+        })
+        .collect();
+
+    files.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(files)
+}
+
+pub fn rename(server_id: i64, from: &str, to: &str) -> Result<(), String> {
+    with_sftp(server_id, |sftp| {
+        sftp.rename(Path::new(from), Path::new(to), None)
+            .map_err(|e| format!("Failed to rename {} to {}: {}", from, to, e))
+    })
+}
+
+fn delete_recursive(sftp: &Sftp, path: &Path) -> Result<(), String> {
+    let stat = sftp.lstat(path).map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+
+    if !stat.is_dir() {
+        return sftp.unlink(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e));
+    }
+
+    let entries = sftp.readdir(path).map_err(|e| format!("Failed to list {}: {}", path.display(), e))?;
+    for (entry_path, _) in entries {
+        delete_recursive(sftp, &entry_path)?;
+    }
+
+    sftp.rmdir(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
+
+/// Delete a file or directory. Non-empty directories are only deleted when `recursive` is set.
+pub fn delete(server_id: i64, path: &str, recursive: bool) -> Result<(), String> {
+    with_sftp(server_id, |sftp| {
+        let path = Path::new(path);
+
+        if recursive {
+            return delete_recursive(sftp, path);
+        }
+
+        let stat = sftp.lstat(path).map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+        match stat.is_dir() {
+            true => sftp.rmdir(path),
+            false => sftp.unlink(path),
+        }.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+    })
+}
+
+pub fn mkdir(server_id: i64, path: &str, mode: Option<u32>) -> Result<(), String> {
+    with_sftp(server_id, |sftp| {
+        sftp.mkdir(Path::new(path), mode.unwrap_or(0o755) as i32)
+            .map_err(|e| format!("Failed to create directory {}: {}", path, e))
+    })
+}
+
+pub fn chmod(server_id: i64, path: &str, mode: u32) -> Result<(), String> {
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: Some(mode & 0o7777),
+        atime: None,
+        mtime: None,
+    };
+
+    with_sftp(server_id, |sftp| {
+        sftp.setstat(Path::new(path), stat)
+            .map_err(|e| format!("Failed to change mode of {}: {}", path, e))
+    })
+}
+
+pub fn read_text(server_id: i64, path: &str) -> Result<String, String> {
+    with_sftp(server_id, |sftp| {
+        let stat = sftp.stat(Path::new(path)).map_err(|e| format!("Failed to stat {}: {}", path, e))?;
+        let size = stat.size.unwrap_or(0);
+        if size > MAX_TEXT_FILE {
+            return Err(format!("{} is too large to edit ({} bytes, at most {})", path, size, MAX_TEXT_FILE));
+        }
+
+        let mut file = sftp.open(Path::new(path)).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+        String::from_utf8(content).map_err(|_| format!("{} is not a text file", path))
+    })
+}
+
+pub fn write_text(server_id: i64, path: &str, content: &str) -> Result<(), String> {
+    if content.len() as u64 > MAX_TEXT_FILE {
+        return Err(format!("Content is too large to save ({} bytes, at most {})", content.len(), MAX_TEXT_FILE));
+    }
+
+    with_sftp(server_id, |sftp| {
+        let flags = OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE;
+        let mut file = sftp.open_mode(Path::new(path), flags, 0o644, OpenType::File)
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+
+        file.write_all(content.as_bytes()).map_err(|e| format!("Failed to write {}: {}", path, e))
+    })
+}
+
+fn remote_size(server_id: i64, path: &str) -> Result<Option<u64>, String> {
+    with_sftp(server_id, |sftp| Ok(sftp.stat(Path::new(path)).ok().and_then(|stat| stat.size)))
+}
+
+fn emit_progress(job: &Job, direction: TransferDirection, local_path: &str, remote_path: &str, transferred: u64, total: u64) {
+    job.emit("transfer-progress", TransferProgress {
+        job_id: job.id().to_string(),
+        server_id: job.server_id(),
+        direction,
+        local_path: local_path.to_string(),
+        remote_path: remote_path.to_string(),
+        transferred,
+        total,
+    });
+}
+
+/// Upload a local file. With `resume`, a partial remote file is continued from its current size.
+pub fn upload(job: &Job, local_path: &str, remote_path: &str, resume: bool) -> Result<String, String> {
+    let mut local = fs::File::open(local_path).map_err(|e| format!("Failed to open {}: {}", local_path, e))?;
+    let total = local.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut offset = match resume {
+        true => remote_size(job.server_id(), remote_path)?.filter(|&size| size <= total).unwrap_or(0),
+        false => 0,
+    };
+
+    // The first chunk truncates unless resuming, so an empty file still gets created
+    let mut first = true;
+    while first || offset < total {
+        job.check_cancelled()?;
+
+        let chunk_len = u64::min(CHUNK_SIZE, total - offset);
+        let mut chunk = vec![0u8; chunk_len as usize];
+        local.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        local.read_exact(&mut chunk).map_err(|e| format!("Failed to read {}: {}", local_path, e))?;
+
+        let mut flags = OpenFlags::WRITE | OpenFlags::CREATE;
+        if first && offset == 0 {
+            flags |= OpenFlags::TRUNCATE;
+        }
+
+        with_sftp(job.server_id(), |sftp| {
+            let mut remote = sftp.open_mode(Path::new(remote_path), flags, 0o644, OpenType::File)
+                .map_err(|e| format!("Failed to open {}: {}", remote_path, e))?;
+            remote.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+            remote.write_all(&chunk).map_err(|e| format!("Failed to write {}: {}", remote_path, e))
+        })?;
+
+        offset += chunk_len;
+        first = false;
+        emit_progress(job, TransferDirection::Upload, local_path, remote_path, offset, total);
+    }
+
+    Ok(format!("Uploaded {} to {} ({} bytes)", local_path, remote_path, total))
+}
+
+/// Download a remote file. With `resume`, a partial local file is continued from its current size.
+pub fn download(job: &Job, remote_path: &str, local_path: &str, resume: bool) -> Result<String, String> {
+    let total = remote_size(job.server_id(), remote_path)?
+        .ok_or_else(|| format!("Failed to stat {}", remote_path))?;
+
+    let existing = fs::metadata(local_path).map(|metadata| metadata.len()).ok();
+    let mut offset = match resume {
+        true => existing.filter(|&size| size <= total).unwrap_or(0),
+        false => 0,
+    };
+
+    let mut local = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(offset == 0)
+        .open(local_path)
+        .map_err(|e| format!("Failed to open {}: {}", local_path, e))?;
+    local.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+
+    while offset < total {
+        job.check_cancelled()?;
+
+        let chunk_len = u64::min(CHUNK_SIZE, total - offset);
+        let chunk = with_sftp(job.server_id(), |sftp| {
+            let mut remote = sftp.open(Path::new(remote_path))
+                .map_err(|e| format!("Failed to open {}: {}", remote_path, e))?;
+            remote.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+
+            let mut chunk = Vec::with_capacity(chunk_len as usize);
+            remote.take(chunk_len).read_to_end(&mut chunk)
+                .map_err(|e| format!("Failed to read {}: {}", remote_path, e))?;
+            Ok(chunk)
+        })?;
+
+        if chunk.is_empty() {
+            return Err(format!("{} got shorter during the download", remote_path));
+        }
+
+        local.write_all(&chunk).map_err(|e| format!("Failed to write {}: {}", local_path, e))?;
+
+        offset += chunk.len() as u64;
+        emit_progress(job, TransferDirection::Download, local_path, remote_path, offset, total);
+    }
+
+    Ok(format!("Downloaded {} to {} ({} bytes)", remote_path, local_path, total))
+}
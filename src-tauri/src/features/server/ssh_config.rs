@@ -0,0 +1,529 @@
+use rusqlite::Connection;
+use super::model::{AuthMethod, Server, SshConfigHost, SudoMode};
+use super::service;
+use crate::features::ssh_key;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+/// OpenSSH refuses configs nested deeper than this, which also stops Include cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A concrete host from the OpenSSH client config, with every matching block applied
+#[derive(Debug, Clone, Default)]
+pub struct HostEntry {
+    pub alias: String,
+    pub hostname: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+}
+
+/// A `Host` block, or the global section before the first one. `Match` blocks are kept with
+/// no patterns so their directives never apply.
+struct Block {
+    patterns: Option<Vec<String>>,
+    directives: Vec<(String, String)>,
+}
+
+impl Block {
+    fn matches(&self, alias: &str) -> bool {
+        let Some(patterns) = &self.patterns else {
+            return false;
+        };
+
+        let mut matched = false;
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if wildcard_match(negated, alias) => return false,
+                Some(_) => {}
+                None => matched |= wildcard_match(pattern, alias),
+            }
+        }
+
+        matched
+    }
+}
+
+/// Hosts from `~/.ssh/config` that aren't servers yet. A host already exists if a server has its
+/// alias as name, or the same hostname, port and user.
+pub fn preview(conn: &Connection, servers: &[Server]) -> Result<Vec<SshConfigHost>, String> {
+    let mut hosts = Vec::new();
+
+    for entry in read_user_config()? {
+        let host = to_host(conn, &entry, servers)?;
+
+        let exists = servers.iter().any(|existing| {
+            existing.name == host.server.name
+                || (existing.hostname == host.server.hostname && existing.port == host.server.port && existing.username == host.server.username)
+        });
+
+        if !exists {
+            hosts.push(host);
+        }
+    }
+
+    Ok(hosts)
+}
+
+/// Add the hosts with the given aliases as servers. Jump hosts being imported alongside are
+/// added first, so ProxyJump can point at them.
+pub fn import(conn: &Connection, aliases: &[String], servers: Vec<Server>) -> Result<Vec<Server>, String> {
+    let hosts = preview(conn, &servers)?;
+    let selected: Vec<&SshConfigHost> = hosts.iter().filter(|host| aliases.contains(&host.alias)).collect();
+
+    let mut servers = servers;
+    let mut imported = Vec::new();
+
+    for host in &selected {
+        import_host(conn, host, &selected, &mut servers, &mut imported, &mut Vec::new())?;
+    }
+
+    Ok(imported)
+}
+
+fn import_host(
+    conn: &Connection,
+    host: &SshConfigHost,
+    selected: &[&SshConfigHost],
+    servers: &mut Vec<Server>,
+    imported: &mut Vec<Server>,
+    chain: &mut Vec<String>,
+) -> Result<(), String> {
+    if find_server(servers, &host.alias).is_some() || chain.contains(&host.alias) {
+        return Ok(());
+    }
+
+    let mut server = host.server.clone();
+
+    if let Some((_, jump, _)) = host.proxy_jump.as_deref().and_then(last_jump) {
+        if let Some(jump_host) = selected.iter().find(|selected| selected.alias == jump) {
+            chain.push(host.alias.clone());
+            import_host(conn, jump_host, selected, servers, imported, chain)?;
+            chain.pop();
+        }
+
+        server.jump_host_id = find_server(servers, &jump).and_then(|jump_host| jump_host.id);
+    }
+
+    if let Some(identity_file) = &host.identity_file {
+        server.ssh_key_id = ssh_key::link_ssh_key(conn, identity_file)?;
+    }
+
+    let added = service::add_server(conn, server)?;
+    servers.push(added.clone());
+    imported.push(added);
+
+    Ok(())
+}
+
+fn find_server<'a>(servers: &'a [Server], host: &str) -> Option<&'a Server> {
+    servers.iter().find(|server| server.name == host || server.hostname == host)
+}
+
+fn to_host(conn: &Connection, entry: &HostEntry, servers: &[Server]) -> Result<SshConfigHost, String> {
+    let username = entry.user.clone().unwrap_or_else(local_username);
+    let hostname = entry.hostname.clone().unwrap_or_else(|| entry.alias.clone());
+
+    let mut identity_file = None;
+    for path in &entry.identity_files {
+        let path = expand_identity_file(path, entry, &username)?;
+        if Path::new(&path).is_file() {
+            identity_file = Some(path);
+            break;
+        }
+    }
+
+    let ssh_key_id = match &identity_file {
+        Some(path) => ssh_key::find_ssh_key_by_path(conn, path)?,
+        None => None,
+    };
+
+    let jump_host_id = entry.proxy_jump.as_deref()
+        .and_then(last_jump)
+        .and_then(|(_, jump, _)| find_server(servers, &jump).and_then(|jump_host| jump_host.id));
+
+    let server = Server {
+        id: None,
+        name: entry.alias.clone(),
+        ip_address: match hostname.parse::<IpAddr>() {
+            Ok(_) => hostname.clone(),
+            Err(_) => String::new(),
+        },
+        hostname,
+        port: entry.port.unwrap_or(22).into(),
+        username,
+        ssh_key_id,
+        // Without an IdentityFile, ssh falls back on the agent
+        auth_method: match identity_file {
+            Some(_) => AuthMethod::Key,
+            None => AuthMethod::Agent,
+        },
+        jump_host_id,
+        sudo_mode: SudoMode::default(),
+        os: None,
+        facts: None,
+        notes: Some(format!("Imported from ~/.ssh/config (Host {})", entry.alias)),
+        settings: serde_json::json!({}),
+        created_at: None,
+        updated_at: None,
+    };
+
+    Ok(SshConfigHost {
+        alias: entry.alias.clone(),
+        server,
+        identity_file,
+        proxy_jump: entry.proxy_jump.clone(),
+    })
+}
+
+/// ssh logs in as the local user when no User is given
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "root".to_string())
+}
+
+/// Read `~/.ssh/config` and everything it includes. A missing file has no hosts.
+fn read_user_config() -> Result<Vec<HostEntry>, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
+    let config_path = home_dir.join(".ssh").join("config");
+
+    if !config_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut lines = Vec::new();
+    read_lines(&config_path, &home_dir, 0, &mut lines)?;
+
+    Ok(resolve(&parse_blocks(&lines)))
+}
+
+/// Append the lines of `path` to `lines`, replacing every `Include` with the files it names.
+fn read_lines(path: &Path, home_dir: &Path, depth: usize, lines: &mut Vec<String>) -> Result<(), String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("Includes nested too deeply at {}", path.display()));
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    for line in contents.lines() {
+        let Some((keyword, args)) = split_line(line) else {
+            continue;
+        };
+
+        if keyword != "include" {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        for pattern in args {
+            for included in expand_include(&pattern, home_dir) {
+                read_lines(&included, home_dir, depth + 1, lines)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Relative includes are looked up in `~/.ssh`. Wildcards are only expanded in the file name.
+fn expand_include(pattern: &str, home_dir: &Path) -> Vec<PathBuf> {
+    let path = expand_home(pattern, home_dir);
+    let path = match path.is_absolute() {
+        true => path,
+        false => home_dir.join(".ssh").join(path),
+    };
+
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+    if !file_name.contains(['*', '?']) {
+        return match path.is_file() {
+            true => vec![path],
+            false => Vec::new(),
+        };
+    }
+
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .filter(|path| path.file_name().is_some_and(|name| wildcard_match(&file_name, &name.to_string_lossy())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // OpenSSH includes glob matches in lexical order
+    matches.sort();
+    matches
+}
+
+fn expand_home(path: &str, home_dir: &Path) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir.join(rest),
+        None if path == "~" => home_dir.to_path_buf(),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Split a config line into its lowercased keyword and arguments. Keywords and arguments may be
+/// separated by whitespace or `=`, and arguments may be double-quoted.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(line.len());
+    let keyword = line[..end].to_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+
+    Some((keyword, args))
+}
+
+fn parse_blocks(lines: &[String]) -> Vec<Block> {
+    let mut blocks = vec![Block { patterns: Some(vec!["*".to_string()]), directives: Vec::new() }];
+
+    for line in lines {
+        let Some((keyword, args)) = split_line(line) else {
+            continue;
+        };
+
+        match keyword.as_str() {
+            "host" => blocks.push(Block { patterns: Some(args), directives: Vec::new() }),
+            "match" => blocks.push(Block { patterns: None, directives: Vec::new() }),
+            _ => {
+                if let (Some(block), Some(value)) = (blocks.last_mut(), args.into_iter().next()) {
+                    block.directives.push((keyword, value));
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Every alias named without wildcards, with its settings. As in OpenSSH, the first value
+/// found for a keyword wins, except IdentityFile which accumulates.
+fn resolve(blocks: &[Block]) -> Vec<HostEntry> {
+    let mut aliases: Vec<&str> = Vec::new();
+    for pattern in blocks.iter().filter_map(|block| block.patterns.as_ref()).flatten() {
+        if !pattern.contains(['*', '?', '!']) && !aliases.contains(&pattern.as_str()) {
+            aliases.push(pattern);
+        }
+    }
+
+    aliases
+        .into_iter()
+        .map(|alias| {
+            let mut entry = HostEntry { alias: alias.to_string(), ..HostEntry::default() };
+
+            for (keyword, value) in blocks.iter().filter(|block| block.matches(alias)).flat_map(|block| &block.directives) {
+                match keyword.as_str() {
+                    "hostname" if entry.hostname.is_none() => entry.hostname = Some(value.replace("%h", alias)),
+                    "port" if entry.port.is_none() => entry.port = value.parse().ok(),
+                    "user" if entry.user.is_none() => entry.user = Some(value.clone()),
+                    "proxyjump" if entry.proxy_jump.is_none() => entry.proxy_jump = Some(value.clone()),
+                    "identityfile" => entry.identity_files.push(value.clone()),
+                    _ => {}
+                }
+            }
+
+            entry
+        })
+        .collect()
+}
+
+/// The identity file path with `~` and the common tokens expanded
+fn expand_identity_file(path: &str, entry: &HostEntry, user: &str) -> Result<String, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
+
+    let expanded = path
+        .replace("%d", &home_dir.to_string_lossy())
+        .replace("%h", entry.hostname.as_deref().unwrap_or(&entry.alias))
+        .replace("%r", user)
+        .replace("%%", "%");
+
+    Ok(expand_home(&expanded, &home_dir).to_string_lossy().to_string())
+}
+
+/// The last hop of a ProxyJump value like `bastion` or `user@bastion:2222,inner`, as
+/// (user, host, port). `none` disables jumping.
+fn last_jump(proxy_jump: &str) -> Option<(Option<String>, String, Option<u16>)> {
+    if proxy_jump.eq_ignore_ascii_case("none") {
+        return None;
+    }
+
+    let hop = proxy_jump.rsplit(',').next()?.trim();
+    let hop = hop.strip_prefix("ssh://").unwrap_or(hop);
+
+    let (user, host) = match hop.rsplit_once('@') {
+        Some((user, host)) => (Some(user.to_string()), host),
+        None => (None, hop),
+    };
+
+    let (host, port) = match host.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, port.parse().ok()),
+        _ => (host, None),
+    };
+
+    Some((user, host.to_string(), port))
+}
+
+/// Glob matching with `*` and `?`, as used by Host patterns and Include
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(config: &str) -> Vec<HostEntry> {
+        let lines: Vec<String> = config.lines().map(str::to_string).collect();
+        resolve(&parse_blocks(&lines))
+    }
+
+    #[test]
+    fn split_line_handles_equals_and_quotes() {
+        assert_eq!(split_line("  HostName=example.com"), Some(("hostname".to_string(), vec!["example.com".to_string()])));
+        assert_eq!(split_line("Port = 2222"), Some(("port".to_string(), vec!["2222".to_string()])));
+        assert_eq!(
+            split_line("IdentityFile \"~/My Keys/id_ed25519\" other"),
+            Some(("identityfile".to_string(), vec!["~/My Keys/id_ed25519".to_string(), "other".to_string()]))
+        );
+        assert_eq!(split_line("# comment"), None);
+        assert_eq!(split_line("   "), None);
+    }
+
+    #[test]
+    fn first_value_wins_and_identity_files_accumulate() {
+        let hosts = entries(
+            "User global\n\
+             Host web\n  HostName 203.0.113.10\n  Port 2222\n  IdentityFile ~/.ssh/web\n\
+             Host *\n  User fallback\n  Port 22\n  IdentityFile ~/.ssh/id_ed25519\n",
+        );
+
+        assert_eq!(hosts.len(), 1);
+        let web = &hosts[0];
+        assert_eq!(web.alias, "web");
+        assert_eq!(web.hostname.as_deref(), Some("203.0.113.10"));
+        assert_eq!(web.port, Some(2222));
+        assert_eq!(web.user.as_deref(), Some("global"));
+        assert_eq!(web.identity_files, ["~/.ssh/web", "~/.ssh/id_ed25519"]);
+    }
+
+    #[test]
+    fn wildcard_and_negated_patterns_are_not_hosts() {
+        let hosts = entries("Host *.internal !db.internal app\n  User deploy\nHost db?\n  User dba\n");
+
+        let aliases: Vec<&str> = hosts.iter().map(|host| host.alias.as_str()).collect();
+        assert_eq!(aliases, ["app"]);
+    }
+
+    #[test]
+    fn negated_pattern_excludes_host() {
+        let hosts = entries("Host app db\nHost * !db\n  User deploy\n");
+
+        assert_eq!(hosts.iter().find(|host| host.alias == "app").unwrap().user.as_deref(), Some("deploy"));
+        assert_eq!(hosts.iter().find(|host| host.alias == "db").unwrap().user, None);
+    }
+
+    #[test]
+    fn match_blocks_are_ignored() {
+        let hosts = entries("Host app\nMatch host app\n  User wrong\n");
+
+        assert_eq!(hosts[0].user, None);
+    }
+
+    #[test]
+    fn hostname_expands_alias_token() {
+        let hosts = entries("Host app\n  HostName %h.example.com\n");
+
+        assert_eq!(hosts[0].hostname.as_deref(), Some("app.example.com"));
+    }
+
+    #[test]
+    fn last_jump_takes_the_final_hop() {
+        assert_eq!(last_jump("bastion"), Some((None, "bastion".to_string(), None)));
+        assert_eq!(
+            last_jump("first,ops@bastion:2222"),
+            Some((Some("ops".to_string()), "bastion".to_string(), Some(2222)))
+        );
+        assert_eq!(last_jump("ssh://ops@bastion"), Some((Some("ops".to_string()), "bastion".to_string(), None)));
+        assert_eq!(last_jump("None"), None);
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("web-*", "web-1"));
+        assert!(wildcard_match("db?", "db1"));
+        assert!(wildcard_match("*.example.*", "a.example.com"));
+        assert!(!wildcard_match("db?", "db10"));
+        assert!(!wildcard_match("web-*", "api-1"));
+    }
+}
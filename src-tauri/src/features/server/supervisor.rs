@@ -0,0 +1,182 @@
+use super::model::{ConnectionEvent, ConnectionState};
+use super::{service, session};
+use crate::common::crypto;
+use crate::database::connection;
+use once_cell::sync::{Lazy, OnceCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Pauses between reconnect attempts after a session is lost, in seconds. A rebooting VPS is
+/// usually back within a minute or two.
+const RETRY_DELAYS: [u64; 6] = [1, 2, 5, 10, 30, 60];
+
+static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
+
+/// Servers the user connected to and hasn't disconnected from, with what it takes to connect
+/// to them again
+static WANTED: Lazy<Mutex<HashMap<i64, Credentials>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Secrets the user entered for a session, kept in memory only. A new session starts without
+/// the sudo password, so the one the lost session had is carried over.
+#[derive(Clone)]
+struct Credentials {
+    password: Option<String>,
+    passphrase: Option<String>,
+    sudo_password: Option<String>,
+}
+
+/// One reconnect attempt at a time per server, whether from the retry loop or a command
+static ATTEMPTS: Lazy<Mutex<HashMap<i64, Arc<Mutex<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Servers with a retry loop running
+static RETRYING: Lazy<Mutex<HashSet<i64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Start emitting `connection-state` events. Reconnecting needs the app handle for the database
+/// and the master key.
+pub fn init(app_handle: AppHandle) {
+    let _ = APP_HANDLE.set(app_handle);
+}
+
+fn emit(server_id: i64, state: ConnectionState, attempt: Option<u32>, error: Option<String>) {
+    let Some(app_handle) = APP_HANDLE.get() else {
+        return;
+    };
+
+    let event = ConnectionEvent { server_id, state, attempt, error };
+
+    if let Err(e) = app_handle.emit("connection-state", event) {
+        eprintln!("Failed to emit connection state of server {}: {}", server_id, e);
+    }
+}
+
+fn wanted(server_id: i64) -> Option<Credentials> {
+    WANTED.lock().ok()?.get(&server_id).cloned()
+}
+
+/// A session was established for the server, remembering the login password or key
+/// passphrase it took for reconnecting.
+pub fn connected(server_id: i64, password: Option<&str>, passphrase: Option<&str>) {
+    if let Ok(mut wanted) = WANTED.lock() {
+        wanted.insert(server_id, Credentials {
+            password: password.map(str::to_string),
+            passphrase: passphrase.map(str::to_string),
+            sudo_password: None,
+        });
+    }
+
+    emit(server_id, ConnectionState::Connected, None, None);
+}
+
+/// The user closed the server's session, or it was evicted. It won't be reconnected.
+pub fn disconnected(server_id: i64) {
+    if let Ok(mut wanted) = WANTED.lock() {
+        wanted.remove(&server_id);
+    }
+
+    emit(server_id, ConnectionState::Disconnected, None, None);
+}
+
+/// The server's session died, with the sudo password it had. Reconnect in the background,
+/// backing off between attempts, until it is back, the user disconnects, or the attempts run out.
+pub fn lost(server_id: i64, error: String, sudo_password: Option<String>) {
+    emit(server_id, ConnectionState::Disconnected, None, Some(error));
+
+    let still_wanted = WANTED.lock().is_ok_and(|mut wanted| match wanted.get_mut(&server_id) {
+        Some(credentials) => {
+            credentials.sudo_password = sudo_password;
+            true
+        }
+        None => false,
+    });
+    if !still_wanted {
+        return;
+    }
+
+    let started = RETRYING.lock().is_ok_and(|mut retrying| retrying.insert(server_id));
+    if !started {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        for (i, delay) in RETRY_DELAYS.iter().enumerate() {
+            std::thread::sleep(Duration::from_secs(*delay));
+
+            match attempt(server_id, i as u32 + 1) {
+                Ok(()) => break,
+                Err(e) if wanted(server_id).is_none() => {
+                    eprintln!("Stopped reconnecting to server {}: {}", server_id, e);
+                    break;
+                }
+                Err(e) if i + 1 == RETRY_DELAYS.len() => {
+                    if let Ok(mut wanted) = WANTED.lock() {
+                        wanted.remove(&server_id);
+                    }
+                    emit(server_id, ConnectionState::Disconnected, None, Some(format!("Gave up reconnecting: {}", e)));
+                }
+                Err(_) => {}
+            }
+        }
+
+        if let Ok(mut retrying) = RETRYING.lock() {
+            retrying.remove(&server_id);
+        }
+    });
+}
+
+/// Reconnect a server whose session was lost, right away. Used to retry commands that failed
+/// because of it; does nothing if another caller already reconnected it.
+pub fn reconnect(server_id: i64) -> Result<(), String> {
+    attempt(server_id, 1)
+}
+
+fn attempt(server_id: i64, attempt: u32) -> Result<(), String> {
+    let lock = ATTEMPTS.lock()
+        .map_err(|_| "Failed to acquire reconnect lock".to_string())?
+        .entry(server_id)
+        .or_default()
+        .clone();
+    let _attempt = lock.lock().map_err(|_| "Failed to acquire reconnect lock".to_string())?;
+
+    if session::is_connected(server_id) {
+        return Ok(());
+    }
+
+    let Some(credentials) = wanted(server_id) else {
+        return Err(format!("Server {} is not connected", server_id));
+    };
+
+    emit(server_id, ConnectionState::Reconnecting, Some(attempt), None);
+
+    let result = connect(server_id, &credentials);
+
+    if let Err(e) = &result {
+        emit(server_id, ConnectionState::Reconnecting, Some(attempt), Some(e.clone()));
+    }
+
+    result
+}
+
+/// Connect with the stored credentials. Keyboard-interactive prompts can't be answered here,
+/// so servers that need a one-time code stay disconnected until the user connects again.
+fn connect(server_id: i64, credentials: &Credentials) -> Result<(), String> {
+    let app_handle = APP_HANDLE.get().ok_or("Connection supervisor is not running")?;
+    let conn = connection::get(app_handle)?;
+    let master_key = crypto::load_master_key(app_handle)?;
+
+    let server = service::get_server(&conn, server_id)?;
+
+    let result = match (&credentials.password, &credentials.passphrase) {
+        (Some(password), _) => service::try_connect_with_password(&conn, &master_key, &server, password, None),
+        (None, Some(passphrase)) => service::try_connect_with_key_passphrase(&conn, &master_key, &server, passphrase, false, None),
+        (None, None) => service::try_connect_to_server(&conn, &master_key, &server, None),
+    };
+    result.map_err(|e| e.to_string())?;
+
+    if let Some(sudo_password) = &credentials.sudo_password {
+        session::set_sudo_password(server_id, Some(sudo_password.clone()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use super::model::{TerminalClosed, TerminalInfo, TerminalOutput};
+use crate::common::utf8::Utf8Decoder;
+use once_cell::sync::Lazy;
+use ssh2::{Channel, ErrorCode, Session};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+const TERM: &str = "xterm-256color";
+/// Output kept per terminal so a frontend attaching later can redraw the screen
+const SCROLLBACK_LIMIT: usize = 256 * 1024;
+
+static NEXT_TERMINAL: AtomicU64 = AtomicU64::new(1);
+
+static TERMINALS: Lazy<Mutex<HashMap<String, Terminal>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+enum Input {
+    Data(Vec<u8>),
+    Resize { cols: u32, rows: u32 },
+    Close,
+}
+
+struct Terminal {
+    info: TerminalInfo,
+    input: Sender<Input>,
+    scrollback: Arc<Mutex<String>>,
+}
+
+fn terminals() -> Result<MutexGuard<'static, HashMap<String, Terminal>>, String> {
+    TERMINALS.lock().map_err(|_| "Failed to acquire terminal registry lock".to_string())
+}
+
+fn send(terminal_id: &str, input: Input) -> Result<(), String> {
+    terminals()?
+        .get(terminal_id)
+        .ok_or_else(|| format!("Terminal {} is not open", terminal_id))?
+        .input
+        .send(input)
+        .map_err(|_| format!("Terminal {} has already closed", terminal_id))
+}
+
+/// Start an interactive shell on its own session. Terminals don't share the pooled session,
+/// so a busy command never stalls typing, and vice versa.
+pub fn open(app_handle: AppHandle, session: Session, server_id: i64, cols: u32, rows: u32) -> Result<TerminalInfo, String> {
+    let mut channel = session.channel_session()
+        .map_err(|e| format!("Failed to open SSH channel: {}", e))?;
+    channel.request_pty(TERM, None, Some((cols, rows, 0, 0)))
+        .map_err(|e| format!("Failed to request a PTY: {}", e))?;
+    channel.shell()
+        .map_err(|e| format!("Failed to start shell: {}", e))?;
+
+    let info = TerminalInfo {
+        id: format!("term-{}-{}", chrono::Local::now().timestamp_millis(), NEXT_TERMINAL.fetch_add(1, Ordering::Relaxed)),
+        server_id,
+        cols,
+        rows,
+        opened_at: chrono::Local::now().to_rfc3339(),
+    };
+
+    let (input, receiver) = mpsc::channel();
+    let scrollback = Arc::new(Mutex::new(String::new()));
+
+    terminals()?.insert(info.id.clone(), Terminal {
+        info: info.clone(),
+        input,
+        scrollback: scrollback.clone(),
+    });
+
+    let terminal_id = info.id.clone();
+    std::thread::spawn(move || {
+        let exit_status = run(&app_handle, &terminal_id, server_id, &session, channel, receiver, &scrollback);
+
+        if let Ok(mut terminals) = terminals() {
+            terminals.remove(&terminal_id);
+        }
+        let _ = session.disconnect(None, "Terminal closed", None);
+
+        let closed = TerminalClosed { terminal_id: terminal_id.clone(), server_id, exit_status };
+        if let Err(e) = app_handle.emit("terminal-closed", closed) {
+            eprintln!("Failed to emit close of terminal {}: {}", terminal_id, e);
+        }
+    });
+
+    Ok(info)
+}
+
+pub fn write(terminal_id: &str, data: &str) -> Result<(), String> {
+    send(terminal_id, Input::Data(data.as_bytes().to_vec()))
+}
+
+pub fn resize(terminal_id: &str, cols: u32, rows: u32) -> Result<(), String> {
+    send(terminal_id, Input::Resize { cols, rows })?;
+
+    if let Some(terminal) = terminals()?.get_mut(terminal_id) {
+        terminal.info.cols = cols;
+        terminal.info.rows = rows;
+    }
+
+    Ok(())
+}
+
+/// Output produced so far, for a frontend attaching to a terminal that is already running.
+/// Live output keeps arriving as `terminal-output` events whether anyone is attached or not.
+pub fn scrollback(terminal_id: &str) -> Result<String, String> {
+    let scrollback = terminals()?
+        .get(terminal_id)
+        .map(|terminal| terminal.scrollback.clone())
+        .ok_or_else(|| format!("Terminal {} is not open", terminal_id))?;
+
+    let scrollback = scrollback.lock()
+        .map_err(|_| format!("Failed to acquire scrollback lock for terminal {}", terminal_id))?;
+
+    Ok(scrollback.clone())
+}
+
+pub fn close(terminal_id: &str) -> Result<(), String> {
+    send(terminal_id, Input::Close)
+}
+
+pub fn list(server_id: Option<i64>) -> Result<Vec<TerminalInfo>, String> {
+    let mut infos: Vec<TerminalInfo> = terminals()?
+        .values()
+        .filter(|terminal| server_id.is_none_or(|id| terminal.info.server_id == id))
+        .map(|terminal| terminal.info.clone())
+        .collect();
+
+    infos.sort_by(|a, b| a.opened_at.cmp(&b.opened_at));
+
+    Ok(infos)
+}
+
+/// libssh2 reports a non-blocking call that has to be retried as `LIBSSH2_ERROR_EAGAIN`
+fn is_would_block(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::Session(-37)
+}
+
+fn append_scrollback(scrollback: &Mutex<String>, data: &str) {
+    let Ok(mut scrollback) = scrollback.lock() else {
+        return;
+    };
+
+    scrollback.push_str(data);
+
+    if scrollback.len() > SCROLLBACK_LIMIT {
+        let mut cut = scrollback.len() - SCROLLBACK_LIMIT;
+        while !scrollback.is_char_boundary(cut) {
+            cut += 1;
+        }
+        scrollback.drain(..cut);
+    }
+}
+
+/// Pump input, resizes and output until the shell exits or the terminal is closed,
+/// returning the shell's exit status if it exited on its own.
+fn run(
+    app_handle: &AppHandle,
+    terminal_id: &str,
+    server_id: i64,
+    session: &Session,
+    mut channel: Channel,
+    input: Receiver<Input>,
+    scrollback: &Mutex<String>,
+) -> Option<i32> {
+    session.set_blocking(false);
+
+    let mut decoder = Utf8Decoder::default();
+    let mut buf = [0u8; 8192];
+    let mut to_channel: Vec<u8> = Vec::new();
+    let mut pending_resize = None;
+
+    let exited = loop {
+        let mut progressed = false;
+
+        match input.try_recv() {
+            Ok(Input::Data(data)) => {
+                to_channel.extend_from_slice(&data);
+                progressed = true;
+            }
+            Ok(Input::Resize { cols, rows }) => {
+                pending_resize = Some((cols, rows));
+                progressed = true;
+            }
+            Ok(Input::Close) | Err(TryRecvError::Disconnected) => break false,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        if let Some((cols, rows)) = pending_resize {
+            match channel.request_pty_size(cols, rows, None, None) {
+                Ok(()) => pending_resize = None,
+                Err(e) if is_would_block(&e) => {}
+                Err(e) => {
+                    eprintln!("Failed to resize terminal {}: {}", terminal_id, e);
+                    pending_resize = None;
+                }
+            }
+        }
+
+        if !to_channel.is_empty() {
+            match channel.write(&to_channel) {
+                Ok(n) => {
+                    to_channel.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    eprintln!("Failed to write to terminal {}: {}", terminal_id, e);
+                    break false;
+                }
+            }
+        }
+
+        match channel.read(&mut buf) {
+            Ok(0) => {}
+            Ok(n) => {
+                let data = decoder.decode(&buf[..n]);
+                append_scrollback(scrollback, &data);
+
+                let output = TerminalOutput { terminal_id: terminal_id.to_string(), server_id, data };
+                if let Err(e) = app_handle.emit("terminal-output", output) {
+                    eprintln!("Failed to emit output of terminal {}: {}", terminal_id, e);
+                }
+                progressed = true;
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => {
+                eprintln!("Failed to read from terminal {}: {}", terminal_id, e);
+                break false;
+            }
+        }
+
+        if channel.eof() && !progressed {
+            break true;
+        }
+
+        if !progressed {
+            let _ = session.keepalive_send();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    };
+
+    session.set_blocking(true);
+
+    if !exited {
+        let _ = channel.close();
+        return None;
+    }
+
+    let _ = channel.wait_close();
+    channel.exit_status().ok()
+}
@@ -0,0 +1,233 @@
+use ssh2::{Channel, Session};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+pub const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Longest sleep between polls of an idle tunnel, which is also the most latency it adds
+const MAX_IDLE_WAIT: Duration = Duration::from_millis(25);
+
+/// Open a direct-tcpip channel to `host:port` through `bastion` and expose it as a local socket,
+/// so it can be handed to `Session::set_tcp_stream` for the next hop. The bastion session is
+/// owned by the relay thread and disconnected once either side closes.
+pub fn open(bastion: Session, host: &str, port: u16) -> Result<TcpStream, String> {
+    let channel = bastion.channel_direct_tcpip(host, port, None)
+        .map_err(|e| format!("Failed to open tunnel to {}:{}: {}", host, port, e))?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind local tunnel socket: {}", e))?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+
+    let client = TcpStream::connect(local_addr)
+        .map_err(|e| format!("Failed to connect local tunnel socket: {}", e))?;
+    let client_addr = client.local_addr().map_err(|e| e.to_string())?;
+
+    // Only accept our own connection, not another local process racing for the port
+    let relay_end = loop {
+        let (stream, peer) = listener.accept()
+            .map_err(|e| format!("Failed to accept local tunnel socket: {}", e))?;
+        if peer == client_addr {
+            break stream;
+        }
+    };
+
+    let target = format!("{}:{}", host, port);
+    std::thread::spawn(move || {
+        if let Err(e) = relay(&bastion, channel, relay_end) {
+            eprintln!("Tunnel to {} closed: {}", target, e);
+        }
+        let _ = bastion.disconnect(None, "Tunnel closed", None);
+    });
+
+    Ok(client)
+}
+
+/// What a call to `Pipe::pump` achieved
+#[derive(PartialEq, Eq)]
+pub enum Flow {
+    Moved,
+    Idle,
+    Closed,
+}
+
+/// Bytes flowing both ways between an SSH channel and a local socket, moved without blocking.
+/// The channel's session has to be in non-blocking mode while pumping.
+pub struct Pipe {
+    channel: Channel,
+    stream: TcpStream,
+    to_channel: Vec<u8>,
+    to_socket: Vec<u8>,
+    /// The local side is done sending, but may still be waiting for the reply
+    local_eof: bool,
+    eof_sent: bool,
+}
+
+impl Pipe {
+    pub fn new(channel: Channel, stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Pipe {
+            channel,
+            stream,
+            to_channel: Vec::new(),
+            to_socket: Vec::new(),
+            local_eof: false,
+            eof_sent: false,
+        })
+    }
+
+    /// Move whatever is ready in either direction, using `buf` as scratch space. When the local
+    /// side stops sending, the channel gets EOF once everything before it is through, and the
+    /// pipe stays open until the server's reply has been delivered and it closes the channel.
+    pub fn pump(&mut self, buf: &mut [u8]) -> Result<Flow, String> {
+        let mut flow = Flow::Idle;
+
+        if self.to_channel.is_empty() && !self.local_eof {
+            match self.stream.read(buf) {
+                Ok(0) => {
+                    self.local_eof = true;
+                    flow = Flow::Moved;
+                }
+                Ok(n) => {
+                    self.to_channel.extend_from_slice(&buf[..n]);
+                    flow = Flow::Moved;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Local socket error: {}", e)),
+            }
+        }
+
+        if !self.to_channel.is_empty() {
+            match self.channel.write(&self.to_channel) {
+                Ok(n) => {
+                    self.to_channel.drain(..n);
+                    flow = Flow::Moved;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Channel write error: {}", e)),
+            }
+        } else if self.local_eof && !self.eof_sent {
+            match self.channel.send_eof().map_err(std::io::Error::from) {
+                Ok(()) => {
+                    self.eof_sent = true;
+                    flow = Flow::Moved;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Channel EOF error: {}", e)),
+            }
+        }
+
+        if self.to_socket.is_empty() {
+            match self.channel.read(buf) {
+                Ok(0) if self.channel.eof() => return Ok(Flow::Closed),
+                Ok(0) => {}
+                Ok(n) => {
+                    self.to_socket.extend_from_slice(&buf[..n]);
+                    flow = Flow::Moved;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Channel read error: {}", e)),
+            }
+        }
+
+        if !self.to_socket.is_empty() {
+            match self.stream.write(&self.to_socket) {
+                Ok(n) => {
+                    self.to_socket.drain(..n);
+                    flow = Flow::Moved;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Local socket error: {}", e)),
+            }
+        }
+
+        Ok(flow)
+    }
+
+    pub fn close(mut self) {
+        let _ = self.channel.close();
+    }
+}
+
+/// Paces a loop polling non-blocking pipes. The longer nothing moves, the longer it sleeps, up
+/// to `MAX_IDLE_WAIT`, and keepalives go out only when the session's interval says one is due.
+pub struct Idle {
+    wait: Duration,
+    next_keepalive: Instant,
+}
+
+impl Idle {
+    pub fn new() -> Self {
+        Idle {
+            wait: Duration::ZERO,
+            next_keepalive: Instant::now(),
+        }
+    }
+
+    /// Something moved, so poll again right away
+    pub fn reset(&mut self) {
+        self.wait = Duration::ZERO;
+    }
+
+    /// Sleep before the next poll, sending a keepalive first if one is due
+    pub fn wait(&mut self, session: &Session) -> Result<(), String> {
+        let now = Instant::now();
+        if now >= self.next_keepalive {
+            // Seconds until the next one is due, 0 when keepalives are off
+            let seconds = session.keepalive_send().map_err(|e| e.to_string())?;
+            self.next_keepalive = now + Duration::from_secs(u64::from(seconds.max(1)));
+        }
+
+        std::thread::sleep(self.backoff());
+        Ok(())
+    }
+
+    fn backoff(&mut self) -> Duration {
+        self.wait = (self.wait * 2).clamp(Duration::from_millis(1), MAX_IDLE_WAIT);
+        self.wait
+    }
+}
+
+/// Copy bytes both ways between an SSH channel and a socket until either side closes.
+/// The session is switched to non-blocking mode, so it must not be shared with other users.
+pub fn relay(session: &Session, channel: Channel, stream: TcpStream) -> Result<(), String> {
+    session.set_blocking(false);
+
+    let mut pipe = Pipe::new(channel, stream)?;
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut idle = Idle::new();
+
+    let result = loop {
+        match pipe.pump(&mut buf) {
+            Ok(Flow::Moved) => idle.reset(),
+            // A failed keepalive shows up as a failed read soon enough
+            Ok(Flow::Idle) => {
+                let _ = idle.wait(session);
+            }
+            Ok(Flow::Closed) => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+
+    session.set_blocking(true);
+    pipe.close();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_wait_backs_off_up_to_the_limit() {
+        let mut idle = Idle::new();
+
+        let waits: Vec<_> = (0..8).map(|_| idle.backoff().as_millis()).collect();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 25, 25, 25]);
+
+        idle.reset();
+        assert_eq!(idle.backoff(), Duration::from_millis(1));
+    }
+}
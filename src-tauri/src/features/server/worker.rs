@@ -0,0 +1,100 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+
+/// Read-only queries that may run on one server at the same time. Each one is a channel on the
+/// server's session, and sshd allows 10 per connection by default (`MaxSessions`).
+const MAX_READS_PER_SERVER: usize = 4;
+
+/// What an operation does to a server, which decides what it may run alongside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Only looks at the server; runs in parallel with anything up to the read limit
+    Read,
+    /// Changes packages, files or services; one at a time per server, alongside reads
+    Write,
+}
+
+#[derive(Default)]
+struct Slots {
+    reads: usize,
+    writing: bool,
+}
+
+impl Slots {
+    fn is_free(&self, access: Access) -> bool {
+        match access {
+            Access::Read => self.reads < MAX_READS_PER_SERVER,
+            Access::Write => !self.writing,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.reads == 0 && !self.writing
+    }
+}
+
+/// Slots taken on each server. One condvar is shared by all servers, as waiters are few.
+static SLOTS: Lazy<(Mutex<HashMap<i64, Slots>>, Condvar)> = Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+
+/// A slot on a server, released when dropped.
+struct Permit {
+    server_id: i64,
+    access: Access,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let (lock, freed) = &*SLOTS;
+        let Ok(mut slots) = lock.lock() else {
+            return;
+        };
+
+        if let Some(server) = slots.get_mut(&self.server_id) {
+            match self.access {
+                Access::Read => server.reads -= 1,
+                Access::Write => server.writing = false,
+            }
+            if server.is_empty() {
+                slots.remove(&self.server_id);
+            }
+        }
+
+        freed.notify_all();
+    }
+}
+
+/// Block until a slot of the given kind is free on the server.
+fn acquire(server_id: i64, access: Access) -> Result<Permit, String> {
+    let (lock, freed) = &*SLOTS;
+    let poisoned = || "Failed to acquire worker slot lock".to_string();
+
+    let mut slots = lock.lock().map_err(|_| poisoned())?;
+    while slots.get(&server_id).is_some_and(|server| !server.is_free(access)) {
+        slots = freed.wait(slots).map_err(|_| poisoned())?;
+    }
+
+    let server = slots.entry(server_id).or_default();
+    match access {
+        Access::Read => server.reads += 1,
+        Access::Write => server.writing = true,
+    }
+
+    Ok(Permit { server_id, access })
+}
+
+/// Run a remote operation on the blocking thread pool once the server has a free slot for it,
+/// so the UI stays responsive and operations on other servers are never held up.
+pub async fn run<T, E, F>(server_id: i64, access: Access, f: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<String> + Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || {
+        let _permit = acquire(server_id, access)?;
+        f()
+    })
+    .await
+    .map_err(|e| E::from(format!("Remote operation failed to run: {}", e)))?
+}
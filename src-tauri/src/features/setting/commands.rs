@@ -1,6 +1,7 @@
 use super::service;
 use tauri::AppHandle;
 use super::model::Setting;
+use crate::common::crypto;
 use crate::database::connection;
 
 #[tauri::command]
@@ -33,6 +34,14 @@ pub fn update_setting(app_handle: AppHandle, key: String, value: String) -> Resu
     service::update_setting(conn, key, value)
 }
 
+#[tauri::command]
+pub fn update_secret_setting(app_handle: AppHandle, key: String, value: String) -> Result<(), String> {
+    let conn = connection::get(&app_handle)?;
+    let master_key = crypto::load_master_key(&app_handle)?;
+
+    service::update_secret_setting(conn, &master_key, key, value)
+}
+
 #[tauri::command]
 pub fn reset_app(app_handle: AppHandle) -> Result<(), String> {
     service::reset_app(app_handle.clone())
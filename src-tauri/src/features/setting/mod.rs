@@ -2,4 +2,5 @@ pub mod model;
 pub mod commands;
 mod service;
 
-pub use commands::*;
\ No newline at end of file
+pub use commands::*;
+pub(crate) use service::{get_secret_setting, get_setting_or};
\ No newline at end of file
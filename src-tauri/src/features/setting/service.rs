@@ -4,6 +4,7 @@ use rusqlite::params;
 use rusqlite::Connection;
 use tauri::{AppHandle, Manager};
 use super::model::Setting;
+use crate::common::crypto::{self, MasterKey};
 
 pub fn init_default_settings(conn: Connection) -> Result<(), String> {
     let mut stmt = conn.prepare("SELECT COUNT(*) FROM settings")
@@ -28,7 +29,27 @@ pub fn init_default_settings(conn: Connection) -> Result<(), String> {
         ).map_err(|e| e.to_string())?;
 
         conn.execute(
-            "INSERT INTO settings (key, value) VALUES (?1, ?2, ?3)",
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)",
+            params!["connection/retry_attempts", "5"],
+        ).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)",
+            params!["connection/keep_alive_interval", "60"],
+        ).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)",
+            params!["security/store_ssh_key_passwords", "true"],
+        ).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)",
+            params!["advanced/proxy_type", "none"],
+        ).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)",
             params!["server/default_port", "22"],
         ).map_err(|e| e.to_string())?;
     }
@@ -44,6 +65,25 @@ pub fn get_setting(conn: Connection, key: String) -> Result<String, String> {
     ).map_err(|e| e.to_string())
 }
 
+/// Read a setting for internal use, falling back to `default` when it is missing.
+pub fn get_setting_or(conn: &Connection, key: &str, default: &str) -> String {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![key],
+        |row| row.get(0)
+    ).unwrap_or_else(|_| default.to_string())
+}
+
+/// Read a setting stored with `update_secret_setting`. Empty or missing values are `None`.
+pub fn get_secret_setting(conn: &Connection, master_key: &MasterKey, key: &str) -> Result<Option<String>, String> {
+    let encrypted = get_setting_or(conn, key, "");
+    if encrypted.is_empty() {
+        return Ok(None);
+    }
+
+    crypto::decrypt(master_key, &encrypted).map(Some)
+}
+
 pub fn get_settings(conn: Connection) -> Result<Vec<Setting>, String> {
     let mut stmt = conn.prepare("SELECT id, key, value FROM settings")
         .map_err(|e| e.to_string())?;
@@ -73,6 +113,17 @@ pub fn update_setting(conn: Connection, key: String, value: String) -> Result<()
     Ok(())
 }
 
+/// Store a setting encrypted with the master key, so it never shows up in plain text.
+/// An empty value clears it.
+pub fn update_secret_setting(conn: Connection, master_key: &MasterKey, key: String, value: String) -> Result<(), String> {
+    let value = match value.is_empty() {
+        true => value,
+        false => crypto::encrypt(master_key, &value)?,
+    };
+
+    update_setting(conn, key, value)
+}
+
 pub fn reset_app(app_handle: AppHandle) -> Result<(), String> {
     let app_dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
     let db_path = app_dir.join("syndeos.db");
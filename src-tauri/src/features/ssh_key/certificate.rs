@@ -0,0 +1,243 @@
+use super::model::{Certificate, CertificateStatus};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Local, TimeDelta};
+use std::fs;
+use std::path::Path;
+
+/// Certificates expiring within this many days are flagged so they can be renewed in time
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// SSH wire format reader over a certificate blob
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("Certificate is truncated".to_string());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.take(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(bytes);
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.bytes()?).to_string())
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            self.bytes()?;
+        }
+        Ok(())
+    }
+}
+
+/// The `-cert.pub` companion OpenSSH looks for next to a private key, if it exists
+pub fn companion_certificate(key_path: &str) -> Option<String> {
+    let private_path = key_path.strip_suffix(".pub").unwrap_or(key_path);
+    let certificate_path = format!("{}-cert.pub", private_path);
+
+    Path::new(&certificate_path).is_file().then_some(certificate_path)
+}
+
+/// Parse an OpenSSH certificate file as written by `ssh-keygen -s`.
+pub fn read_certificate(path: &str) -> Result<Certificate, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read certificate {}: {}", path, e))?;
+
+    let mut fields = contents.split_whitespace();
+    let cert_type = fields.next().ok_or("Certificate file is empty")?;
+    let blob = fields.next().ok_or("Certificate file has no key data")?;
+
+    if !cert_type.contains("-cert-v01@openssh.com") {
+        return Err(format!("{} is not an OpenSSH certificate", path));
+    }
+
+    let blob = STANDARD.decode(blob).map_err(|e| format!("Failed to decode certificate: {}", e))?;
+    let mut reader = Reader { data: &blob };
+
+    let blob_type = reader.string()?;
+    if blob_type != cert_type {
+        return Err(format!("Certificate type mismatch: {} vs {}", cert_type, blob_type));
+    }
+
+    // Nonce, then the public key fields, which depend on the key type
+    reader.skip(1)?;
+    let key_fields = match blob_type.as_str() {
+        "ssh-rsa-cert-v01@openssh.com" => 2,
+        "ssh-dss-cert-v01@openssh.com" => 4,
+        "ssh-ed25519-cert-v01@openssh.com" => 1,
+        "sk-ssh-ed25519-cert-v01@openssh.com" => 2,
+        t if t.starts_with("ecdsa-sha2-") => 2,
+        t if t.starts_with("sk-ecdsa-sha2-") => 3,
+        t => return Err(format!("Unsupported certificate type {}", t)),
+    };
+    reader.skip(key_fields)?;
+
+    let serial = reader.u64()?;
+    let is_host = reader.u32()? == 2;
+    let key_id = reader.string()?;
+
+    let mut principals_reader = Reader { data: reader.bytes()? };
+    let mut principals = Vec::new();
+    while !principals_reader.data.is_empty() {
+        principals.push(principals_reader.string()?);
+    }
+
+    let valid_after = timestamp(reader.u64()?);
+    let valid_before = timestamp(reader.u64()?);
+
+    Ok(Certificate {
+        key_id,
+        serial,
+        cert_type: if is_host { "host" } else { "user" }.to_string(),
+        principals,
+        status: status(valid_after, valid_before),
+        valid_after: valid_after.map(|time| time.to_rfc3339()),
+        valid_before: valid_before.map(|time| time.to_rfc3339()),
+    })
+}
+
+/// 0 and u64::MAX stand for "always" and "forever"
+fn timestamp(seconds: u64) -> Option<DateTime<Local>> {
+    if seconds == 0 || seconds == u64::MAX {
+        return None;
+    }
+
+    DateTime::from_timestamp(i64::try_from(seconds).ok()?, 0).map(|time| time.with_timezone(&Local))
+}
+
+fn status(valid_after: Option<DateTime<Local>>, valid_before: Option<DateTime<Local>>) -> CertificateStatus {
+    let now = Local::now();
+
+    match (valid_after, valid_before) {
+        (Some(after), _) if now < after => CertificateStatus::NotYetValid,
+        (_, Some(before)) if now >= before => CertificateStatus::Expired,
+        (_, Some(before)) if before - now < TimeDelta::days(EXPIRY_WARNING_DAYS) => CertificateStatus::Expiring,
+        _ => CertificateStatus::Valid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519_CERT: &str = "ssh-ed25519-cert-v01@openssh.com";
+
+    fn string(out: &mut Vec<u8>, data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        out.extend_from_slice(data);
+    }
+
+    /// The fields of an ed25519 user certificate up to its validity, which is all that is read
+    fn blob(principals: &[&str], valid_after: u64, valid_before: u64) -> Vec<u8> {
+        let mut blob = Vec::new();
+        string(&mut blob, ED25519_CERT.as_bytes());
+        string(&mut blob, &[7; 32]);
+        string(&mut blob, &[9; 32]);
+        blob.extend_from_slice(&42u64.to_be_bytes());
+        blob.extend_from_slice(&1u32.to_be_bytes());
+        string(&mut blob, b"deploy@ci");
+
+        let mut names = Vec::new();
+        for principal in principals {
+            string(&mut names, principal.as_bytes());
+        }
+        string(&mut blob, &names);
+
+        blob.extend_from_slice(&valid_after.to_be_bytes());
+        blob.extend_from_slice(&valid_before.to_be_bytes());
+        blob
+    }
+
+    fn read(name: &str, contents: &str) -> Result<Certificate, String> {
+        let path = std::env::temp_dir().join(format!("syndeos-test-{}-{}-cert.pub", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+
+        let result = read_certificate(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn file(blob: &[u8]) -> String {
+        format!("{} {} comment\n", ED25519_CERT, STANDARD.encode(blob))
+    }
+
+    #[test]
+    fn reads_user_certificate() {
+        let certificate = read("user", &file(&blob(&["deploy", "root"], 0, u64::MAX))).unwrap();
+
+        assert_eq!(certificate.key_id, "deploy@ci");
+        assert_eq!(certificate.serial, 42);
+        assert_eq!(certificate.cert_type, "user");
+        assert_eq!(certificate.principals, ["deploy", "root"]);
+        assert_eq!(certificate.valid_after, None);
+        assert_eq!(certificate.valid_before, None);
+        assert_eq!(certificate.status, CertificateStatus::Valid);
+    }
+
+    #[test]
+    fn rejects_truncated_certificate() {
+        let blob = blob(&["deploy"], 0, u64::MAX);
+
+        for len in [3, 10, 50, blob.len() - 1] {
+            let error = read("truncated", &file(&blob[..len])).unwrap_err();
+            assert!(error.contains("truncated"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_length_past_the_end() {
+        let mut blob = Vec::new();
+        blob.extend_from_slice(&u32::MAX.to_be_bytes());
+
+        assert!(read("length", &file(&blob)).unwrap_err().contains("truncated"));
+    }
+
+    #[test]
+    fn rejects_mismatched_and_plain_keys() {
+        let mismatched = format!("ssh-rsa-cert-v01@openssh.com {}", STANDARD.encode(blob(&[], 0, u64::MAX)));
+        assert!(read("mismatched", &mismatched).unwrap_err().contains("mismatch"));
+
+        assert!(read("plain", "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIA== key").unwrap_err().contains("not an OpenSSH certificate"));
+        assert!(read("empty", "").is_err());
+    }
+
+    #[test]
+    fn status_follows_validity() {
+        let now = Local::now();
+
+        assert_eq!(status(None, None), CertificateStatus::Valid);
+        assert_eq!(status(Some(now + TimeDelta::hours(1)), None), CertificateStatus::NotYetValid);
+        assert_eq!(status(None, Some(now - TimeDelta::seconds(1))), CertificateStatus::Expired);
+        assert_eq!(status(None, Some(now + TimeDelta::days(1))), CertificateStatus::Expiring);
+        assert_eq!(status(Some(now - TimeDelta::days(1)), Some(now + TimeDelta::days(30))), CertificateStatus::Valid);
+    }
+
+    #[test]
+    fn timestamps_at_the_bounds_are_unbounded() {
+        assert_eq!(timestamp(0), None);
+        assert_eq!(timestamp(u64::MAX), None);
+        assert_eq!(timestamp(1_700_000_000).map(|time| time.timestamp()), Some(1_700_000_000));
+    }
+}
@@ -1,6 +1,7 @@
 use super::service;
 use tauri::AppHandle;
 use super::model::SshKey;
+use crate::common::crypto;
 use crate::database::connection;
 
 #[tauri::command]
@@ -34,15 +35,33 @@ pub fn get_ssh_keys(app_handle: AppHandle) -> Result<Vec<SshKey>, String> {
 #[tauri::command]
 pub fn add_ssh_key(app_handle: AppHandle, name: String, path: String, password: String, is_default: bool) -> Result<SshKey, String> {
     let conn = connection::get(&app_handle)?;
+    let master_key = crypto::load_master_key(&app_handle)?;
 
-    service::add_ssh_key(&conn, name, path, password, is_default)
+    service::add_ssh_key(&conn, &master_key, name, path, password, is_default)
+}
+
+#[tauri::command]
+pub fn set_ssh_key_passphrase(app_handle: AppHandle, id: i64, passphrase: String) -> Result<(), String> {
+    let conn = connection::get(&app_handle)?;
+    let master_key = crypto::load_master_key(&app_handle)?;
+
+    service::store_ssh_key_passphrase(&conn, &master_key, id, &passphrase)
+}
+
+/// Attach an OpenSSH certificate to a key, or detach it by passing no path
+#[tauri::command]
+pub fn set_ssh_key_certificate(app_handle: AppHandle, id: i64, certificate_path: Option<String>) -> Result<SshKey, String> {
+    let conn = connection::get(&app_handle)?;
+
+    service::set_ssh_key_certificate(&conn, id, certificate_path)
 }
 
 #[tauri::command]
 pub fn generate_ssh_key(app_handle: AppHandle, name: String, password: String, is_default: bool) -> Result<SshKey, String> {
     let conn = connection::get(&app_handle)?;
+    let master_key = crypto::load_master_key(&app_handle)?;
 
-    service::generate_ssh_key(&conn, name, password, is_default)
+    service::generate_ssh_key(&conn, &master_key, name, password, is_default)
 }
 
 #[tauri::command]
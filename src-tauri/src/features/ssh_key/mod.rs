@@ -1,5 +1,7 @@
 pub mod model;
 pub mod commands;
+mod certificate;
 mod service;
 
-pub use commands::*;
\ No newline at end of file
+pub use commands::*;
+pub(crate) use service::{find_ssh_key_by_path, get_ssh_key_certificate, get_ssh_key_passphrase, is_private_key_encrypted, link_ssh_key, store_ssh_key_passphrase};
\ No newline at end of file
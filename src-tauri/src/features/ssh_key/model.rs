@@ -6,7 +6,37 @@ pub struct SshKey {
     pub name: String,
     pub path: String,
     pub password: Option<String>,
+    pub has_passphrase: bool,
     pub is_default: bool,
+    /// OpenSSH certificate presented along with the key, usually `<key>-cert.pub`
+    #[serde(default)]
+    pub certificate_path: Option<String>,
+    /// Parsed from `certificate_path` when the key is read
+    #[serde(default)]
+    pub certificate: Option<Certificate>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CertificateStatus {
+    Valid,
+    /// Expires within a week
+    Expiring,
+    Expired,
+    NotYetValid,
+}
+
+/// What an OpenSSH certificate grants. Validity bounds are RFC 3339, None meaning unbounded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub key_id: String,
+    pub serial: u64,
+    /// "user" or "host"
+    pub cert_type: String,
+    pub principals: Vec<String>,
+    pub valid_after: Option<String>,
+    pub valid_before: Option<String>,
+    pub status: CertificateStatus,
+}
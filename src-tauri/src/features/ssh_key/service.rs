@@ -1,8 +1,12 @@
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use std::fs;
-use crate::common;
-use super::model::SshKey;
-use common::{hasher};
+use std::path::Path;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crate::common::crypto::{self, MasterKey};
+use crate::features::setting;
+use super::certificate;
+use super::model::{Certificate, SshKey};
 
 pub fn init_ssh_keys(conn: &Connection) -> Result<Vec<SshKey>, String> {
     let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
@@ -31,6 +35,13 @@ pub fn init_ssh_keys(conn: &Connection) -> Result<Vec<SshKey>, String> {
 
         let path_str = path.to_string_lossy().to_string();
 
+        // Certificates are `<key>-cert.pub`, picked up along with their key below
+        if key_name.ends_with("-cert.pub") {
+            continue;
+        }
+
+        let certificate_path = certificate::companion_certificate(&path_str);
+
         let mut stmt = conn.prepare("SELECT COUNT(*) FROM ssh_keys WHERE path = ?1")
             .map_err(|e| e.to_string())?;
 
@@ -38,34 +49,93 @@ pub fn init_ssh_keys(conn: &Connection) -> Result<Vec<SshKey>, String> {
             .map_err(|e| e.to_string())?;
 
         if count > 0 {
+            // A certificate may have been issued for a key we already know
+            if certificate_path.is_some() {
+                conn.execute(
+                    "UPDATE ssh_keys SET certificate_path = ?1 WHERE path = ?2 AND certificate_path IS NULL",
+                    params![certificate_path, path_str],
+                ).map_err(|e| e.to_string())?;
+            }
             continue;
         }
 
         let now = chrono::Local::now().to_rfc3339();
 
         conn.execute(
-            "INSERT INTO ssh_keys (name, path, is_default, created_at, updated_at)
-             VALUES (?1, ?2, 0, ?3, ?4)",
-            &[&key_name, &path_str, &now, &now],
+            "INSERT INTO ssh_keys (name, path, certificate_path, is_default, created_at, updated_at)
+             VALUES (?1, ?2, ?3, 0, ?4, ?5)",
+            params![key_name, path_str, certificate_path, now, now],
         ).map_err(|e| e.to_string())?;
 
-        let id = conn.last_insert_rowid();
-
-        added_keys.push(SshKey {
-            id: Some(id),
-            name: key_name,
-            path: path_str,
-            password: None,
-            is_default: false,
-            created_at: Option::from(now.clone()),
-            updated_at: Option::from(now),
-        });
+        added_keys.push(get_ssh_key(conn, conn.last_insert_rowid())?);
     }
 
     Ok(added_keys)
 }
 
-pub fn add_ssh_key(conn: &Connection, name: String, path: String, password: String, is_default: bool) -> Result<SshKey, String> {
+/// The key stored with `path` as either its private key or its `.pub` file
+pub fn find_ssh_key_by_path(conn: &Connection, path: &str) -> Result<Option<i64>, String> {
+    let public_path = format!("{}.pub", path.trim_end_matches(".pub"));
+    let private_path = public_path.trim_end_matches(".pub");
+
+    let mut stmt = conn.prepare("SELECT id FROM ssh_keys WHERE path = ?1 OR path = ?2 ORDER BY id LIMIT 1")
+        .map_err(|e| e.to_string())?;
+
+    stmt.query_row(params![private_path, public_path], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+/// Find the key for a private key file, adding it the way `init_ssh_keys` does if it isn't
+/// stored yet. None if the file doesn't exist.
+pub fn link_ssh_key(conn: &Connection, path: &str) -> Result<Option<i64>, String> {
+    if let Some(id) = find_ssh_key_by_path(conn, path)? {
+        return Ok(Some(id));
+    }
+
+    let private_path = Path::new(path);
+    if !private_path.is_file() {
+        return Ok(None);
+    }
+
+    let public_path = format!("{}.pub", path);
+    let stored_path = match Path::new(&public_path).is_file() {
+        true => public_path,
+        false => path.to_string(),
+    };
+
+    let key_name = Path::new(&stored_path).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| stored_path.clone());
+
+    let now = chrono::Local::now().to_rfc3339();
+
+    let certificate_path = certificate::companion_certificate(&stored_path);
+
+    conn.execute(
+        "INSERT INTO ssh_keys (name, path, certificate_path, is_default, created_at, updated_at)
+         VALUES (?1, ?2, ?3, 0, ?4, ?5)",
+        params![key_name, stored_path, certificate_path, now, now],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(Some(conn.last_insert_rowid()))
+}
+
+fn store_passphrases(conn: &Connection) -> bool {
+    setting::get_setting_or(conn, "security/store_ssh_key_passwords", "true") == "true"
+}
+
+/// Passphrases are encrypted under the local master key rather than hashed, since they have
+/// to be handed back to libssh2 to unlock the private key.
+fn encrypt_passphrase(conn: &Connection, master_key: &MasterKey, passphrase: &str) -> Result<Option<String>, String> {
+    if passphrase.trim().is_empty() || !store_passphrases(conn) {
+        return Ok(None);
+    }
+
+    crypto::encrypt(master_key, passphrase).map(Some)
+}
+
+pub fn add_ssh_key(conn: &Connection, master_key: &MasterKey, name: String, path: String, password: String, is_default: bool) -> Result<SshKey, String> {
     let now = chrono::Local::now().to_rfc3339();
     
     if is_default {
@@ -75,55 +145,57 @@ pub fn add_ssh_key(conn: &Connection, name: String, path: String, password: Stri
         ).map_err(|e| e.to_string())?;
     }
     
-    let password_hash = if password.trim().is_empty() {
-            None
-        } else {
-            Some(hasher::hash_password(&password).map_err(|e| e.to_string())?)
-        };
+    let encrypted_password = encrypt_passphrase(conn, master_key, &password)?;
+    let certificate_path = certificate::companion_certificate(&path);
 
     conn.execute(
-        "INSERT INTO ssh_keys (name, path, password, is_default, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![name, path, password_hash, is_default, now, now],
+        "INSERT INTO ssh_keys (name, path, password, certificate_path, is_default, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![name, path, encrypted_password, certificate_path, is_default, now, now],
     ).map_err(|e| e.to_string())?;
     
     get_ssh_key(&conn, conn.last_insert_rowid())
 }
 
+const SSH_KEY_COLUMNS: &str = "id, name, path, password IS NOT NULL, is_default, certificate_path, created_at, updated_at";
+
+fn ssh_key_from_row(row: &rusqlite::Row) -> rusqlite::Result<SshKey> {
+    let certificate_path: Option<String> = row.get(5)?;
+
+    // A missing or unreadable certificate leaves the key usable on its own
+    let certificate = certificate_path.as_deref().and_then(|path| {
+        certificate::read_certificate(path)
+            .map_err(|e| eprintln!("Warning: {}", e))
+            .ok()
+    });
+
+    Ok(SshKey {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        path: row.get(2)?,
+        password: None,
+        has_passphrase: row.get(3)?,
+        is_default: row.get(4)?,
+        certificate_path,
+        certificate,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
 pub fn get_ssh_key(conn: &Connection, id: i64) -> Result<SshKey, String> {
     conn.query_row(
-        "SELECT id, name, path, is_default, created_at, updated_at
-         FROM ssh_keys WHERE id = ?1",
+        &format!("SELECT {} FROM ssh_keys WHERE id = ?1", SSH_KEY_COLUMNS),
         params![id],
-        |row| Ok(SshKey {
-            id: Some(row.get(0)?),
-            name: row.get(1)?,
-            path: row.get(2)?,
-            password: None,
-            is_default: row.get(3)?,
-            created_at: row.get(4)?,
-            updated_at: row.get(5)?,
-        })
+        ssh_key_from_row,
     ).map_err(|e| e.to_string())
 }
 
 pub fn get_ssh_keys(conn: &Connection) -> Result<Vec<SshKey>, String> {
-    let mut stmt = conn.prepare("
-        SELECT id, name, path, is_default, created_at, updated_at
-        FROM ssh_keys
-    ").map_err(|e| e.to_string())?;
-
-    let key_iter = stmt.query_map([], |row| {
-        Ok(SshKey {
-            id: Some(row.get(0)?),
-            name: row.get(1)?,
-            path: row.get(2)?,
-            password: None,
-            is_default: row.get(3)?,
-            created_at: row.get(4)?,
-            updated_at: row.get(5)?,
-        })
-    }).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM ssh_keys", SSH_KEY_COLUMNS))
+        .map_err(|e| e.to_string())?;
+
+    let key_iter = stmt.query_map([], ssh_key_from_row).map_err(|e| e.to_string())?;
 
     let mut keys = Vec::new();
     for key in key_iter {
@@ -133,6 +205,93 @@ pub fn get_ssh_keys(conn: &Connection) -> Result<Vec<SshKey>, String> {
     Ok(keys)
 }
 
+/// Attach a certificate to a key, or detach it with None. The file must be a valid certificate.
+pub fn set_ssh_key_certificate(conn: &Connection, id: i64, certificate_path: Option<String>) -> Result<SshKey, String> {
+    if let Some(path) = &certificate_path {
+        certificate::read_certificate(path)?;
+    }
+
+    let now = chrono::Local::now().to_rfc3339();
+
+    conn.execute(
+        "UPDATE ssh_keys SET certificate_path = ?1, updated_at = ?2 WHERE id = ?3",
+        params![certificate_path, now, id],
+    ).map_err(|e| e.to_string())?;
+
+    get_ssh_key(conn, id)
+}
+
+/// The certificate to authenticate with along with a key, and what it grants
+pub fn get_ssh_key_certificate(conn: &Connection, id: i64) -> Result<Option<(String, Certificate)>, String> {
+    let certificate_path: Option<String> = conn.query_row(
+        "SELECT certificate_path FROM ssh_keys WHERE id = ?1",
+        params![id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())?;
+
+    match certificate_path {
+        Some(path) => {
+            let certificate = certificate::read_certificate(&path)?;
+            Ok(Some((path, certificate)))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn get_ssh_key_passphrase(conn: &Connection, master_key: &MasterKey, id: i64) -> Result<Option<String>, String> {
+    let encrypted: Option<String> = conn.query_row(
+        "SELECT password FROM ssh_keys WHERE id = ?1",
+        params![id],
+        |row| row.get(0)
+    ).map_err(|e| e.to_string())?;
+
+    encrypted.map(|encrypted| crypto::decrypt(master_key, &encrypted)).transpose()
+}
+
+/// Store (or clear, when empty) the passphrase of a key, honoring `security/store_ssh_key_passwords`.
+pub fn store_ssh_key_passphrase(conn: &Connection, master_key: &MasterKey, id: i64, passphrase: &str) -> Result<(), String> {
+    let now = chrono::Local::now().to_rfc3339();
+    let encrypted_password = encrypt_passphrase(conn, master_key, passphrase)?;
+
+    conn.execute(
+        "UPDATE ssh_keys SET password = ?1, updated_at = ?2 WHERE id = ?3",
+        params![encrypted_password, now, id],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Whether a private key file needs a passphrase. Handles the OpenSSH format as well as
+/// legacy PEM and PKCS#8 keys.
+pub fn is_private_key_encrypted(private_key_path: &Path) -> Result<bool, String> {
+    let contents = fs::read_to_string(private_key_path)
+        .map_err(|e| format!("Failed to read private key {}: {}", private_key_path.display(), e))?;
+
+    if contents.contains("BEGIN ENCRYPTED PRIVATE KEY") || contents.contains("Proc-Type: 4,ENCRYPTED") {
+        return Ok(true);
+    }
+
+    if !contents.contains("BEGIN OPENSSH PRIVATE KEY") {
+        return Ok(false);
+    }
+
+    let body: String = contents
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let decoded = STANDARD.decode(body.trim())
+        .map_err(|e| format!("Failed to decode private key: {}", e))?;
+
+    // "openssh-key-v1\0" followed by a length-prefixed cipher name, which is "none" when unencrypted
+    let magic = b"openssh-key-v1\0";
+    let rest = decoded.strip_prefix(magic.as_slice()).ok_or("Invalid OpenSSH private key")?;
+    let len_bytes: [u8; 4] = rest.get(..4).and_then(|b| b.try_into().ok()).ok_or("Invalid OpenSSH private key")?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    let cipher = rest.get(4..4 + len).ok_or("Invalid OpenSSH private key")?;
+
+    Ok(cipher != b"none")
+}
+
 pub fn set_default_ssh_key(conn: &Connection, id: i64) -> Result<(), String> {
     conn.execute(
         "UPDATE ssh_keys SET is_default = 0 WHERE is_default = 1",
@@ -177,7 +336,7 @@ pub fn delete_ssh_key(conn: &Connection, id: i64, delete_file: bool) -> Result<(
     Ok(())
 }
 
-pub fn generate_ssh_key(conn: &Connection, name: String, password: String, is_default: bool) -> Result<SshKey, String> {
+pub fn generate_ssh_key(conn: &Connection, master_key: &MasterKey, name: String, password: String, is_default: bool) -> Result<SshKey, String> {
     let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
     let ssh_dir = home_dir.join(".ssh");
 
@@ -218,5 +377,5 @@ pub fn generate_ssh_key(conn: &Connection, name: String, password: String, is_de
         return Err(format!("ssh-keygen failed: {}", String::from_utf8_lossy(&output.stderr)));
     }
     
-    add_ssh_key(&conn, public_key_name, public_key_path, password, is_default)
+    add_ssh_key(&conn, master_key, public_key_name, public_key_path, password, is_default)
 }
\ No newline at end of file
@@ -17,6 +17,10 @@ fn init_app(app_handle: AppHandle) -> Result<String, String> {
 
     println!("Tauri SSH Keys Initialization Successful!");
 
+    features::server::init_connection_supervisor(app_handle.clone());
+
+    println!("Tauri Connection Supervisor Initialization Successful!");
+
     Ok(db_result)
 }
 
@@ -33,11 +37,54 @@ pub fn run() {
             features::server::update_server,
             features::server::delete_server,
             features::server::get_servers,
+            features::server::refresh_server_facts,
             features::server::try_connect_to_server,
             features::server::connect_with_password,
+            features::server::respond_auth_prompt,
+            features::server::set_sudo_password,
+            features::server::connect_with_key_passphrase,
+            features::server::list_agent_identities,
             features::server::disconnect_from_server,
+            features::server::disconnect_all_servers,
+            features::server::list_sessions,
+            features::server::evict_idle_sessions,
+            features::server::cancel_job,
+            features::server::open_terminal,
+            features::server::write_terminal,
+            features::server::resize_terminal,
+            features::server::attach_terminal,
+            features::server::close_terminal,
+            features::server::list_terminals,
+            features::server::create_tunnel,
+            features::server::start_tunnel,
+            features::server::list_tunnels,
+            features::server::close_tunnel,
+            features::server::delete_tunnel,
+            features::server::list_remote_dir,
+            features::server::rename_remote_path,
+            features::server::delete_remote_path,
+            features::server::create_remote_dir,
+            features::server::chmod_remote_path,
+            features::server::read_remote_file,
+            features::server::write_remote_file,
+            features::server::upload_file,
+            features::server::download_file,
+            features::server::accept_host_key,
+            features::server::get_known_host,
+            features::server::forget_host_key,
+            features::server::import_known_hosts,
+            features::server::preview_ssh_config,
+            features::server::import_ssh_config,
             features::server::test,
 
+            // Package management commands
+            features::server::list_installed_packages,
+            features::server::check_package_updates,
+
+            // Service management commands
+            features::server::get_service_status,
+            features::server::control_service,
+
             // PHP version management commands
             features::server::install_php_version,
             features::server::remove_php_version,
@@ -63,11 +110,21 @@ pub fn run() {
             features::server::list_users,
             features::server::change_user_password,
 
-            // Server initial setup command
+            // Server initial setup commands
             features::server::setup_server,
+            features::server::list_setup_steps,
+            features::server::get_setup_progress,
+            features::server::reset_setup_progress,
+
+            // Recipe commands
+            features::server::list_recipes,
+            features::server::get_recipes_directory,
+            features::server::run_recipe,
 
             // SSH key management commands
             features::ssh_key::add_ssh_key,
+            features::ssh_key::set_ssh_key_passphrase,
+            features::ssh_key::set_ssh_key_certificate,
             features::ssh_key::delete_ssh_key,
             features::ssh_key::get_ssh_key,
             features::ssh_key::get_ssh_keys,
@@ -78,6 +135,7 @@ pub fn run() {
             features::setting::get_setting,
             features::setting::get_settings,
             features::setting::update_setting,
+            features::setting::update_secret_setting,
             features::setting::reset_app,
         ])
         .run(tauri::generate_context!())
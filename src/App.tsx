@@ -9,6 +9,7 @@ import { ServerProvider } from "@/components/providers/server.tsx";
 import { ThemeProvider } from "@/components/providers/theme.tsx";
 import { SettingsProvider } from "@/components/providers/settings.tsx";
 import { ConnectionDetails } from "@/components/features/server/connection-details.tsx";
+import { AuthPromptDialog } from "@/components/features/server/auth-prompt-dialog.tsx";
 import "./App.css";
 
 function App() {
@@ -29,6 +30,7 @@ function App() {
                                 <MainContent />
                             </SidebarProvider>
                             <ConnectionDetails />
+                            <AuthPromptDialog />
                         </ServerProvider>
                     </GlobalStateProvider>
                 </PageProvider>
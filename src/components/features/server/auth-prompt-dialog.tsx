@@ -0,0 +1,86 @@
+import React, { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from '@/components/ui/dialog';
+import { Button } from '@/components/ui/button';
+import { Input } from '@/components/ui/input';
+import { Label } from '@/components/ui/label';
+import { IconShieldLock } from '@tabler/icons-react';
+import { toast } from 'sonner';
+import { AuthPrompt } from '@/types';
+
+/**
+ * Answers keyboard-interactive prompts, such as one-time codes, sent by the backend
+ * while connecting to a server.
+ */
+export function AuthPromptDialog() {
+  const [prompt, setPrompt] = useState<AuthPrompt | null>(null);
+  const [responses, setResponses] = useState<string[]>([]);
+
+  useEffect(() => {
+    const unlisten = listen<AuthPrompt>('auth-prompt', (event) => {
+      setPrompt(event.payload);
+      setResponses(event.payload.prompts.map(() => ''));
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const respond = (answers: string[] | null) => {
+    if (!prompt) return;
+
+    invoke('respond_auth_prompt', { requestId: prompt.request_id, responses: answers })
+      .catch((error) => toast.error(`Failed to answer authentication prompt: ${error}`));
+
+    setPrompt(null);
+    setResponses([]);
+  };
+
+  const handleSubmit = (e: React.FormEvent) => {
+    e.preventDefault();
+    respond(responses);
+  };
+
+  return (
+    <Dialog open={prompt !== null} onOpenChange={(open) => !open && respond(null)}>
+      <DialogContent className="sm:max-w-[425px]">
+        <DialogHeader>
+          <DialogTitle className="flex items-center">
+            <IconShieldLock className="mr-2 h-5 w-5" />
+            Verification Required
+          </DialogTitle>
+          <DialogDescription>
+            {prompt?.instructions || `${prompt?.server_name} asks ${prompt?.username} for more information to sign in`}
+          </DialogDescription>
+        </DialogHeader>
+        <form onSubmit={handleSubmit}>
+          <div className="grid gap-4 py-4">
+            {prompt?.prompts.map((field, index) => (
+              <div key={index} className="grid gap-2">
+                <Label htmlFor={`auth-prompt-${index}`}>{field.text}</Label>
+                <Input
+                  id={`auth-prompt-${index}`}
+                  type={field.echo ? 'text' : 'password'}
+                  autoComplete="one-time-code"
+                  value={responses[index] ?? ''}
+                  onChange={(e) => setResponses(responses.map((value, i) => i === index ? e.target.value : value))}
+                  autoFocus={index === 0}
+                />
+              </div>
+            ))}
+          </div>
+          <DialogFooter>
+            <Button type="button" variant="outline" onClick={() => respond(null)}>
+              Cancel
+            </Button>
+            <Button type="submit">
+              Continue
+            </Button>
+          </DialogFooter>
+        </form>
+      </DialogContent>
+    </Dialog>
+  );
+}
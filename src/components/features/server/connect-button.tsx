@@ -7,6 +7,30 @@ import { PasswordDialog } from './password-dialog';
 import { Server } from '@/types';
 import { useServerContext, ConnectionStatuses } from "@/components/providers/server.tsx";
 
+type HostKeyMismatch = {
+  kind: 'host_key_mismatch';
+  server_id: number;
+  hostname: string;
+  expected_fingerprint: string;
+  actual_fingerprint: string;
+};
+
+type ConnectionError =
+  | HostKeyMismatch
+  | { kind: 'passphrase_required'; server_id: number; ssh_key_id: number }
+  | { kind: 'failed'; message: string };
+
+function isHostKeyMismatch(error: unknown): error is HostKeyMismatch {
+  return typeof error === 'object' && error !== null && (error as ConnectionError).kind === 'host_key_mismatch';
+}
+
+function errorMessage(error: unknown): string {
+  if (typeof error === 'object' && error !== null && (error as ConnectionError).kind === 'failed') {
+    return (error as { message: string }).message;
+  }
+  return String(error);
+}
+
 interface ConnectButtonProps {
   server: Server;
   onSuccess?: () => void;
@@ -25,11 +49,12 @@ export function ConnectButton({
   const { setConnectionStatus, connectionStatus, connectedServer, setConnectedServer, setConnectionError } = useServerContext();
   const [showPasswordDialog, setShowPasswordDialog] = useState(false);
   const [passwordError, setPasswordError] = useState<string | null>(null);
+  const [needsKeyPassphrase, setNeedsKeyPassphrase] = useState(false);
 
   const handleConnect = async () => {
     try {
       if (connectionStatus === ConnectionStatuses.connected) {
-        await invoke('disconnect_from_server');
+        await invoke('disconnect_from_server', { serverId: connectedServer?.id ?? server.id });
         setConnectionStatus(ConnectionStatuses.disconnected);
         setConnectedServer(null);
 
@@ -52,15 +77,36 @@ export function ConnectButton({
       setConnectedServer(server);
       if (onSuccess) onSuccess();
     } catch (error) {
-      toast.error('SSH key connection failed: {error}');
+      if (isHostKeyMismatch(error)) {
+        setConnectionStatus(ConnectionStatuses.disconnected);
+        toast.error(`Host key for ${error.hostname} has changed! Expected ${error.expected_fingerprint}, got ${error.actual_fingerprint}.`, {
+          duration: Infinity,
+          action: {
+            label: 'Trust new key',
+            onClick: () => invoke('accept_host_key', { serverId: error.server_id, fingerprint: error.actual_fingerprint })
+              .then(() => toast.success(`New host key for ${server.name} accepted`))
+              .catch((e) => toast.error(`Failed to accept host key: ${e}`)),
+          },
+        });
+        return;
+      }
+
+      if (typeof error === 'object' && error !== null && (error as ConnectionError).kind === 'passphrase_required') {
+        setNeedsKeyPassphrase(true);
+        setShowPasswordDialog(true);
+        setConnectionStatus(ConnectionStatuses.disconnected);
+        return;
+      }
+
+      toast.error(`SSH key connection failed: ${errorMessage(error)}`);
 
       setShowPasswordDialog(true);
 
       setConnectionStatus(ConnectionStatuses.disconnected);
 
-      setConnectionError(String(error));
+      setConnectionError(errorMessage(error));
 
-      if (onError) onError(String(error));
+      if (onError) onError(errorMessage(error));
     }
   };
 
@@ -68,12 +114,21 @@ export function ConnectButton({
     try {
       setConnectionStatus(ConnectionStatuses.connecting);
 
-      await invoke('connect_with_password', {
-        id: server.id, 
-        password 
-      });
+      if (needsKeyPassphrase) {
+        await invoke('connect_with_key_passphrase', {
+          id: server.id,
+          passphrase: password,
+          remember: true,
+        });
+      } else {
+        await invoke('connect_with_password', {
+          id: server.id, 
+          password 
+        });
+      }
 
       setShowPasswordDialog(false);
+      setNeedsKeyPassphrase(false);
 
       toast.success(`Connected to ${server.name}`);
 
@@ -83,14 +138,15 @@ export function ConnectButton({
       if (onSuccess) onSuccess();
     } catch (error) {
       console.error('Password connection failed:', error);
-      setPasswordError(`Failed to connect: ${error}`);
+      setPasswordError(`Failed to connect: ${errorMessage(error)}`);
       setConnectionStatus(ConnectionStatuses.disconnected);
-      if (onError) onError(String(error));
+      if (onError) onError(errorMessage(error));
     }
   };
 
   const handlePasswordCancel = () => {
     setShowPasswordDialog(false);
+    setNeedsKeyPassphrase(false);
     setPasswordError(null);
     setConnectionError(null);
   };
@@ -119,6 +175,8 @@ export function ConnectButton({
         onCancel={handlePasswordCancel}
         isLoading={(connectionStatus === ConnectionStatuses.connecting)}
         error={passwordError}
+        title={needsKeyPassphrase ? 'SSH Key Passphrase Required' : undefined}
+        description={needsKeyPassphrase ? `Enter the passphrase of the SSH key for "${server.name}"` : undefined}
       />
     </>
   );
@@ -0,0 +1,96 @@
+import { ReactNode, useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { toast } from 'sonner';
+import { Card, CardHeader, CardTitle, CardContent } from '@/components/ui/card';
+import { Button } from '@/components/ui/button';
+import { useServerContext } from '@/components/providers/server';
+import { fbytes, fdate } from '@/lib/utils';
+import { Server, ServerFacts } from '@/types';
+
+/**
+ * The facts gathered from the connected server on connect, which can be gathered again on demand.
+ */
+export function FactsCard() {
+    const { connectionStatus, connectedServer } = useServerContext();
+    const [facts, setFacts] = useState<ServerFacts | null>(connectedServer?.facts ?? null);
+    const [os, setOs] = useState(connectedServer?.os ?? null);
+    const [refreshing, setRefreshing] = useState(false);
+
+    // Facts are stored when connecting, after the server was loaded
+    useEffect(() => {
+        if (connectionStatus !== 'connected' || !connectedServer?.id) return;
+
+        invoke<Server>('get_server', { id: connectedServer.id })
+            .then((server) => {
+                setFacts(server.facts ?? null);
+                setOs(server.os ?? null);
+            })
+            .catch((error) => console.error('Failed to load server facts:', error));
+    }, [connectionStatus, connectedServer?.id]);
+
+    const handleRefresh = async () => {
+        if (!connectedServer?.id) return;
+
+        setRefreshing(true);
+        try {
+            setFacts(await invoke<ServerFacts>('refresh_server_facts', { serverId: connectedServer.id }));
+        } catch (error) {
+            toast.error(`Failed to refresh server facts: ${error}`);
+        } finally {
+            setRefreshing(false);
+        }
+    };
+
+    const row = (label: string, value: ReactNode) => (
+        <div className="flex justify-between">
+            <span className="font-medium">{label}:</span>
+            <span className="text-right">{value}</span>
+        </div>
+    );
+
+    return (
+        <Card>
+            <CardHeader>
+                <CardTitle className="flex items-center justify-between">
+                    Server Facts
+                    <Button
+                        variant="outline"
+                        size="sm"
+                        onClick={handleRefresh}
+                        disabled={refreshing || connectionStatus !== 'connected'}
+                    >
+                        {refreshing ? 'Refreshing...' : 'Refresh'}
+                    </Button>
+                </CardTitle>
+            </CardHeader>
+            <CardContent>
+                {!facts ? (
+                    <p className="text-sm text-muted-foreground">No facts gathered yet. Connect to the server to gather them.</p>
+                ) : (
+                    <div className="space-y-2">
+                        {row('Operating System', os?.name || 'Unknown')}
+                        {row('Kernel', `${facts.kernel} (${facts.arch})`)}
+                        {row('CPUs', facts.cpus)}
+                        {row('Memory', fbytes(facts.memory))}
+                        {row('Public IPs', facts.public_ips.join(', ') || 'None')}
+                        {row('Web Servers', facts.web_servers.join(', ') || 'None')}
+                        {row('Database Servers', facts.database_servers.join(', ') || 'None')}
+                        {row('SELinux', facts.selinux ?? 'Not installed')}
+                        <div>
+                            <span className="font-medium">Disks:</span>
+                            <div className="mt-1 space-y-1">
+                                {facts.disks.map((disk) => (
+                                    <div key={disk.mount_point} className="flex justify-between text-sm">
+                                        <span>{disk.mount_point} <span className="text-muted-foreground">({disk.device}, {disk.filesystem})</span></span>
+                                        <span>{fbytes(disk.used)} / {fbytes(disk.size)}</span>
+                                    </div>
+                                ))}
+                            </div>
+                        </div>
+                        <p className="text-xs text-muted-foreground">Gathered {fdate(facts.gathered_at)}</p>
+                    </div>
+                )}
+            </CardContent>
+        </Card>
+    );
+}
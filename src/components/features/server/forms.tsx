@@ -37,9 +37,10 @@ import {
     SelectValue,
 } from "@/components/ui/select";
 import {invoke} from "@tauri-apps/api/core";
-import {SshKey, SshKeys} from "@/types.ts";
+import {Server, SshKey, SshKeys} from "@/types.ts";
 import {Textarea} from "@/components/ui/textarea";
 import { toast } from "sonner";
+import {ProxyFields, proxyFormFields, proxyFormValues, refineProxy, saveProxyPassword, withProxy} from "./proxy-fields";
 
 const serverFormSchema = z.object({
     name: z.string().min(1, "Server name is required"),
@@ -50,10 +51,14 @@ const serverFormSchema = z.object({
         .int("Port must be an integer")
         .min(1, "Port must be at least 1")
         .max(65535, "Port must be at most 65535"),
+    auth_method: z.enum(["key", "agent", "password"]),
+    sudo_mode: z.enum(["passwordless", "password", "root"]),
     ssh_key_id: z.union([z.number(), z.literal("__clear__")]).optional(),
+    jump_host_id: z.union([z.number(), z.literal("__clear__")]).optional(),
     notes: z.string(),
-    settings: z.string()
-});
+    settings: z.string(),
+    ...proxyFormFields
+}).superRefine(refineProxy);
 
 type ServerFormValues = z.infer<typeof serverFormSchema>;
 
@@ -66,7 +71,7 @@ export function AddServerForm({
                                   onSuccess,
                                   children,
                               }: AddServerFormProps) {
-    const {fetchServers} = useGlobalState();
+    const {servers: jumpHosts, fetchServers} = useGlobalState();
     const [open, setOpen] = useState(false);
     const [sshKeys, setSshKeys] = useState<SshKeys>([]);
     const [loadingKeys, setLoadingKeys] = useState(false);
@@ -82,8 +87,12 @@ export function AddServerForm({
             username: "",
             port: 22,
             notes: "",
+            auth_method: "key",
+            sudo_mode: "passwordless",
             ssh_key_id: "__clear__",
-            settings: "{}"
+            jump_host_id: "__clear__",
+            settings: "{}",
+            ...proxyFormValues("{}")
         },
     });
 
@@ -117,16 +126,24 @@ export function AddServerForm({
                 ip_address: data.ip_address || "",
                 port: data.port,
                 username: data.username,
+                auth_method: data.auth_method,
+                sudo_mode: data.sudo_mode,
                 ssh_key_id: data.ssh_key_id,
+                jump_host_id: data.jump_host_id,
                 notes: data.notes || "",
-                settings: data.settings || "{}"
+                settings: withProxy(data.settings, data)
             };
 
             if (serverData.ssh_key_id === "__clear__") {
                 serverData.ssh_key_id = undefined;
             }
 
-            await invoke("add_server", {server: serverData});
+            if (serverData.jump_host_id === "__clear__") {
+                serverData.jump_host_id = undefined;
+            }
+
+            const server = await invoke<Server>("add_server", {server: serverData});
+            await saveProxyPassword(server.id!, data);
 
             if (onSuccess) {
                 onSuccess();
@@ -269,6 +286,60 @@ export function AddServerForm({
                             />
                         </div>
 
+                        {/* Authentication Method */}
+                        <FormField
+                            control={form.control}
+                            name="auth_method"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Authentication</FormLabel>
+                                    <Select onValueChange={field.onChange} value={field.value}>
+                                        <FormControl>
+                                            <SelectTrigger className="w-full">
+                                                <SelectValue placeholder="Select an authentication method"/>
+                                            </SelectTrigger>
+                                        </FormControl>
+                                        <SelectContent>
+                                            <SelectItem value="key">SSH key</SelectItem>
+                                            <SelectItem value="agent">ssh-agent</SelectItem>
+                                            <SelectItem value="password">Password</SelectItem>
+                                        </SelectContent>
+                                    </Select>
+                                    <FormDescription>
+                                        ssh-agent uses the identities loaded in your running agent
+                                    </FormDescription>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+
+                        {/* Sudo Mode */}
+                        <FormField
+                            control={form.control}
+                            name="sudo_mode"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Sudo</FormLabel>
+                                    <Select onValueChange={field.onChange} value={field.value}>
+                                        <FormControl>
+                                            <SelectTrigger className="w-full">
+                                                <SelectValue placeholder="Select how commands get root"/>
+                                            </SelectTrigger>
+                                        </FormControl>
+                                        <SelectContent>
+                                            <SelectItem value="passwordless">Passwordless sudo</SelectItem>
+                                            <SelectItem value="password">sudo with password</SelectItem>
+                                            <SelectItem value="root">Connect as root</SelectItem>
+                                        </SelectContent>
+                                    </Select>
+                                    <FormDescription>
+                                        The sudo password is asked for once per session and never stored
+                                    </FormDescription>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+
                         {/* SSH Key Selection */}
                         <FormField
                             control={form.control}
@@ -322,6 +393,44 @@ export function AddServerForm({
                             )}
                         />
 
+                        {/* Jump Host Selection */}
+                        <FormField
+                            control={form.control}
+                            name="jump_host_id"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Jump Host</FormLabel>
+                                    <Select
+                                        onValueChange={(value) => field.onChange(value === "__clear__" ? value : parseInt(value))}
+                                        value={field.value?.toString() || undefined}
+                                    >
+                                        <FormControl>
+                                            <SelectTrigger className="w-full">
+                                                <SelectValue placeholder="Connect directly"/>
+                                            </SelectTrigger>
+                                        </FormControl>
+                                        <SelectContent>
+                                            <SelectItem value="__clear__">Connect directly</SelectItem>
+                                            {jumpHosts.map((jumpHost) => (
+                                                <SelectItem key={jumpHost.id} value={String(jumpHost.id)}>
+                                                    <div className="flex items-center">
+                                                        <IconServer className="mr-2 h-4 w-4"/>
+                                                        <span>{jumpHost.name}</span>
+                                                    </div>
+                                                </SelectItem>
+                                            ))}
+                                        </SelectContent>
+                                    </Select>
+                                    <FormDescription>
+                                        Tunnel the connection through another server (ProxyJump)
+                                    </FormDescription>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+
+                        <ProxyFields/>
+
                         {/* Notes field */}
                         <FormField
                             control={form.control}
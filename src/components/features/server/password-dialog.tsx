@@ -13,6 +13,8 @@ interface PasswordDialogProps {
   onCancel: () => void;
   isLoading?: boolean;
   error?: string | null;
+  title?: string;
+  description?: string;
 }
 
 export function PasswordDialog({
@@ -23,6 +25,8 @@ export function PasswordDialog({
   onCancel,
   isLoading = false,
   error = null,
+  title = 'Server Password Required',
+  description,
 }: PasswordDialogProps) {
   const [password, setPassword] = useState('');
 
@@ -37,13 +41,13 @@ export function PasswordDialog({
         <DialogHeader>
           <DialogTitle className="flex items-center">
             <IconKey className="mr-2 h-5 w-5" />
-            Server Password Required
+            {title}
           </DialogTitle>
           <DialogDescription>
             {error ? (
               <span className="text-red-500">{error}</span>
             ) : (
-              `Enter password for server "${serverName}"`
+              description ?? `Enter password for server "${serverName}"`
             )}
           </DialogDescription>
         </DialogHeader>
@@ -0,0 +1,236 @@
+import { z } from "zod";
+import { invoke } from "@tauri-apps/api/core";
+import { useFormContext } from "react-hook-form";
+import { Input } from "@/components/ui/input";
+import {
+    FormField,
+    FormControl,
+    FormItem,
+    FormLabel,
+    FormMessage,
+    FormDescription,
+} from "@/components/ui/form";
+import {
+    Select,
+    SelectContent,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select";
+
+// Fields spread into a server form schema. They are saved under "proxy" in the server's settings JSON,
+// except the password, which is stored encrypted as a secret setting.
+export const proxyFormFields = {
+    proxy_mode: z.enum(["global", "none", "custom"]),
+    proxy_type: z.enum(["socks5", "http"]),
+    proxy_host: z.string(),
+    proxy_port: z.string().regex(/^\d*$/, "Port must be a number"),
+    proxy_username: z.string(),
+    proxy_password: z.string(),
+};
+
+export type ProxyFormValues = {
+    proxy_mode: "global" | "none" | "custom";
+    proxy_type: "socks5" | "http";
+    proxy_host: string;
+    proxy_port: string;
+    proxy_username: string;
+    proxy_password: string;
+};
+
+export function refineProxy(values: ProxyFormValues, ctx: z.RefinementCtx) {
+    if (values.proxy_mode === "custom" && !values.proxy_host) {
+        ctx.addIssue({ code: z.ZodIssueCode.custom, path: ["proxy_host"], message: "Proxy host is required" });
+    }
+}
+
+function parseSettings(settings: string): Record<string, unknown> {
+    try {
+        const parsed = JSON.parse(settings || "{}");
+        return parsed && typeof parsed === "object" && !Array.isArray(parsed) ? parsed : {};
+    } catch {
+        return {};
+    }
+}
+
+export function proxyFormValues(settings: string): ProxyFormValues {
+    const proxy = parseSettings(settings).proxy;
+    const values: ProxyFormValues = {
+        proxy_mode: "global",
+        proxy_type: "socks5",
+        proxy_host: "",
+        proxy_port: "",
+        proxy_username: "",
+        proxy_password: "",
+    };
+
+    if (proxy === "none") {
+        values.proxy_mode = "none";
+    } else if (proxy && typeof proxy === "object") {
+        const custom = proxy as Record<string, unknown>;
+        values.proxy_mode = "custom";
+        values.proxy_type = custom.type === "http" ? "http" : "socks5";
+        values.proxy_host = String(custom.host ?? "");
+        values.proxy_port = custom.port ? String(custom.port) : "";
+        values.proxy_username = String(custom.username ?? "");
+    }
+
+    return values;
+}
+
+// The settings JSON with "proxy" replaced by the form's choice, keeping any other entries
+export function withProxy(settings: string, values: ProxyFormValues): string {
+    const updated = parseSettings(settings);
+
+    switch (values.proxy_mode) {
+        case "global":
+            delete updated.proxy;
+            break;
+        case "none":
+            updated.proxy = "none";
+            break;
+        case "custom":
+            updated.proxy = {
+                type: values.proxy_type,
+                host: values.proxy_host,
+                port: Number(values.proxy_port) || (values.proxy_type === "socks5" ? 1080 : 8080),
+                username: values.proxy_username || null,
+            };
+            break;
+    }
+
+    return JSON.stringify(updated);
+}
+
+// Save or clear the password of the server's own proxy. An empty password keeps the saved one.
+export async function saveProxyPassword(serverId: number, values: ProxyFormValues) {
+    const key = `servers/${serverId}/proxy_password`;
+
+    if (values.proxy_mode !== "custom") {
+        await invoke("update_secret_setting", { key, value: "" });
+    } else if (values.proxy_password) {
+        await invoke("update_secret_setting", { key, value: values.proxy_password });
+    }
+}
+
+export function ProxyFields() {
+    const form = useFormContext<ProxyFormValues>();
+    const mode = form.watch("proxy_mode");
+    const type = form.watch("proxy_type");
+
+    return (
+        <>
+            <FormField
+                control={form.control}
+                name="proxy_mode"
+                render={({field}) => (
+                    <FormItem>
+                        <FormLabel>Proxy</FormLabel>
+                        <Select onValueChange={field.onChange} value={field.value}>
+                            <FormControl>
+                                <SelectTrigger className="w-full">
+                                    <SelectValue placeholder="Select a proxy"/>
+                                </SelectTrigger>
+                            </FormControl>
+                            <SelectContent>
+                                <SelectItem value="global">Use the global proxy setting</SelectItem>
+                                <SelectItem value="none">Connect directly</SelectItem>
+                                <SelectItem value="custom">Use a proxy for this server</SelectItem>
+                            </SelectContent>
+                        </Select>
+                        <FormDescription>
+                            The global proxy is set in the advanced settings
+                        </FormDescription>
+                        <FormMessage/>
+                    </FormItem>
+                )}
+            />
+
+            {mode === "custom" && (
+                <>
+                    <div className="grid grid-cols-1 sm:grid-cols-2 gap-4">
+                        <FormField
+                            control={form.control}
+                            name="proxy_type"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Proxy Type</FormLabel>
+                                    <Select onValueChange={field.onChange} value={field.value}>
+                                        <FormControl>
+                                            <SelectTrigger className="w-full">
+                                                <SelectValue/>
+                                            </SelectTrigger>
+                                        </FormControl>
+                                        <SelectContent>
+                                            <SelectItem value="socks5">SOCKS5</SelectItem>
+                                            <SelectItem value="http">HTTP CONNECT</SelectItem>
+                                        </SelectContent>
+                                    </Select>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+
+                        <FormField
+                            control={form.control}
+                            name="proxy_host"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Proxy Host</FormLabel>
+                                    <FormControl>
+                                        <Input {...field} placeholder="e.g. proxy.example.com"/>
+                                    </FormControl>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+                    </div>
+
+                    <div className="grid grid-cols-1 sm:grid-cols-2 gap-4">
+                        <FormField
+                            control={form.control}
+                            name="proxy_port"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Proxy Port</FormLabel>
+                                    <FormControl>
+                                        <Input {...field} placeholder={type === "socks5" ? "1080" : "8080"}/>
+                                    </FormControl>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+
+                        <FormField
+                            control={form.control}
+                            name="proxy_username"
+                            render={({field}) => (
+                                <FormItem>
+                                    <FormLabel>Proxy Username</FormLabel>
+                                    <FormControl>
+                                        <Input {...field} placeholder="Optional"/>
+                                    </FormControl>
+                                    <FormMessage/>
+                                </FormItem>
+                            )}
+                        />
+                    </div>
+
+                    <FormField
+                        control={form.control}
+                        name="proxy_password"
+                        render={({field}) => (
+                            <FormItem>
+                                <FormLabel>Proxy Password (encrypted)</FormLabel>
+                                <FormControl>
+                                    <Input {...field} type="password" autoComplete="off" placeholder="Leave empty to keep the saved password"/>
+                                </FormControl>
+                                <FormMessage/>
+                            </FormItem>
+                        )}
+                    />
+                </>
+            )}
+        </>
+    );
+}
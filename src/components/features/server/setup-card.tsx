@@ -0,0 +1,150 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { toast } from 'sonner';
+import { Card, CardHeader, CardTitle, CardContent } from '@/components/ui/card';
+import { Button } from '@/components/ui/button';
+import { Checkbox } from '@/components/ui/checkbox';
+import { useServerContext } from '@/components/providers/server';
+import { ProvisionStep, ProvisionStepEvent, StepProgress, StepState } from '@/types';
+
+const STATE_LABELS: Record<StepState, string> = {
+    checking: 'Checking...',
+    applying: 'Applying...',
+    done: 'Done',
+    satisfied: 'Already in place',
+    skipped: 'Skipped',
+    failed: 'Failed',
+};
+
+const STATE_COLORS: Record<StepState, string> = {
+    checking: 'text-yellow-600',
+    applying: 'text-yellow-600',
+    done: 'text-green-600',
+    satisfied: 'text-green-600',
+    skipped: 'text-muted-foreground',
+    failed: 'text-red-600',
+};
+
+/**
+ * Runs server setup step by step. Optional steps can be left out, and a setup that failed
+ * resumes from the step that failed.
+ */
+export function SetupCard() {
+    const { connectionStatus, connectedServer } = useServerContext();
+    const [steps, setSteps] = useState<ProvisionStep[]>([]);
+    const [skip, setSkip] = useState<string[]>([]);
+    const [progress, setProgress] = useState<Record<string, StepProgress>>({});
+    const [running, setRunning] = useState(false);
+
+    useEffect(() => {
+        invoke<ProvisionStep[]>('list_setup_steps')
+            .then(setSteps)
+            .catch((error) => console.error('Failed to load setup steps:', error));
+    }, []);
+
+    useEffect(() => {
+        if (!connectedServer?.id) return;
+
+        invoke<StepProgress[]>('get_setup_progress', { serverId: connectedServer.id })
+            .then((rows) => setProgress(Object.fromEntries(rows.map((row) => [row.step, row]))))
+            .catch((error) => console.error('Failed to load setup progress:', error));
+    }, [connectedServer?.id]);
+
+    useEffect(() => {
+        const unlisten = listen<ProvisionStepEvent>('provision-step', (event) => {
+            const { server_id, plan, step, state, error } = event.payload;
+            if (server_id !== connectedServer?.id || plan !== 'setup') return;
+
+            setProgress((current) => ({
+                ...current,
+                [step]: { step, state, error, updated_at: new Date().toISOString() },
+            }));
+        });
+
+        return () => {
+            unlisten.then((fn) => fn());
+        };
+    }, [connectedServer?.id]);
+
+    // Leaving a step out leaves out the steps that need it
+    const isSkipped = (step: ProvisionStep): boolean =>
+        skip.includes(step.id) || (!!step.requires && skip.includes(step.requires));
+
+    const toggle = (id: string, selected: boolean) => {
+        setSkip((current) => (selected ? current.filter((s) => s !== id) : [...current, id]));
+    };
+
+    const resuming = Object.values(progress).some((row) => !['done', 'satisfied', 'skipped'].includes(row.state));
+
+    const handleRun = async () => {
+        if (!connectedServer?.id) return;
+
+        setRunning(true);
+        try {
+            await invoke<string>('setup_server', { serverId: connectedServer.id, skip });
+            toast.success('Server setup completed successfully');
+        } catch (error) {
+            toast.error(`Server setup failed: ${error}`);
+        } finally {
+            setRunning(false);
+        }
+    };
+
+    const handleReset = async () => {
+        if (!connectedServer?.id) return;
+
+        try {
+            await invoke('reset_setup_progress', { serverId: connectedServer.id });
+            setProgress({});
+        } catch (error) {
+            toast.error(`Failed to reset setup progress: ${error}`);
+        }
+    };
+
+    return (
+        <Card>
+            <CardHeader>
+                <CardTitle className="flex items-center justify-between">
+                    Server Setup
+                    <div className="flex gap-2">
+                        {resuming && (
+                            <Button variant="outline" size="sm" onClick={handleReset} disabled={running}>
+                                Start Over
+                            </Button>
+                        )}
+                        <Button size="sm" onClick={handleRun} disabled={running || connectionStatus !== 'connected'}>
+                            {running ? 'Running...' : resuming ? 'Resume Setup' : 'Run Setup'}
+                        </Button>
+                    </div>
+                </CardTitle>
+            </CardHeader>
+            <CardContent>
+                <div className="space-y-2">
+                    {steps.map((step) => {
+                        const row = progress[step.id];
+                        const required = steps.find((s) => s.id === step.requires);
+
+                        return (
+                            <div key={step.id} className="flex items-center justify-between text-sm">
+                                <label className="flex items-center gap-2">
+                                    <Checkbox
+                                        checked={!isSkipped(step)}
+                                        disabled={!step.optional || running || (!!required && isSkipped(required))}
+                                        onCheckedChange={(checked) => toggle(step.id, checked === true)}
+                                    />
+                                    <span>{step.name}</span>
+                                </label>
+                                {row && (
+                                    <span className={STATE_COLORS[row.state]} title={row.error ?? undefined}>
+                                        {STATE_LABELS[row.state]}
+                                    </span>
+                                )}
+                            </div>
+                        );
+                    })}
+                </div>
+            </CardContent>
+        </Card>
+    );
+}
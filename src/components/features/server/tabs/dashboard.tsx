@@ -5,6 +5,8 @@ import { useState } from 'react';
 import { useServerContext } from '@/components/providers/server';
 import { invoke } from '@tauri-apps/api/core';
 import { toast } from 'sonner';
+import { FactsCard } from '@/components/features/server/facts-card';
+import { SetupCard } from '@/components/features/server/setup-card';
 
 export default function Dashboard() {
     const { connectionStatus, connectedServer } = useServerContext();
@@ -20,11 +22,12 @@ export default function Dashboard() {
                             <span className="text-sm mr-2">Status:</span>
                             <span className={`inline-block w-3 h-3 rounded-full mr-1 ${
                                 connectionStatus === 'connected' ? 'bg-green-500' : 
-                                connectionStatus === 'connecting' ? 'bg-yellow-500' : 'bg-red-500'
+                                connectionStatus === 'connecting' || connectionStatus === 'reconnecting' ? 'bg-yellow-500' : 'bg-red-500'
                             }`}></span>
                             <span className="text-sm font-normal">
                                 {connectionStatus === 'connected' ? 'Connected' : 
-                                 connectionStatus === 'connecting' ? 'Connecting...' : 'Disconnected'}
+                                 connectionStatus === 'connecting' ? 'Connecting...' :
+                                 connectionStatus === 'reconnecting' ? 'Reconnecting...' : 'Disconnected'}
                             </span>
                         </div>
                     </CardTitle>
@@ -85,6 +88,10 @@ export default function Dashboard() {
                 </CardContent>
             </Card>
 
+            <FactsCard />
+
+            <SetupCard />
+
             <div className="grid auto-rows-min gap-4 md:grid-cols-3">
                 <div className="aspect-video rounded-xl bg-white text-3xl flex justify-center items-center">
                     <p>Server Analytics 1</p>
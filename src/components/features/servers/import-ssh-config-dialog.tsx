@@ -0,0 +1,101 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from '@/components/ui/dialog';
+import { Button } from '@/components/ui/button';
+import { Checkbox } from '@/components/ui/checkbox';
+import { toast } from 'sonner';
+import { Server, SshConfigHost } from '@/types';
+
+interface ImportSshConfigDialogProps {
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+  onImported: () => void;
+}
+
+/**
+ * Lists the hosts in ~/.ssh/config that aren't servers yet and imports the selected ones.
+ */
+export function ImportSshConfigDialog({ open, onOpenChange, onImported }: ImportSshConfigDialogProps) {
+  const [hosts, setHosts] = useState<SshConfigHost[]>([]);
+  const [selected, setSelected] = useState<string[]>([]);
+  const [loading, setLoading] = useState(false);
+  const [importing, setImporting] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+
+    setLoading(true);
+    invoke<SshConfigHost[]>('preview_ssh_config')
+      .then((hosts) => {
+        setHosts(hosts);
+        setSelected(hosts.map((host) => host.alias));
+      })
+      .catch((error) => toast.error(`Failed to read SSH config: ${error}`))
+      .finally(() => setLoading(false));
+  }, [open]);
+
+  const toggle = (alias: string, checked: boolean) => {
+    setSelected((current) => checked ? [...current, alias] : current.filter((a) => a !== alias));
+  };
+
+  const handleImport = async () => {
+    setImporting(true);
+    try {
+      const imported = await invoke<Server[]>('import_ssh_config', { aliases: selected });
+      toast.success(`Imported ${imported.length} server${imported.length === 1 ? '' : 's'} from SSH config`);
+      onImported();
+      onOpenChange(false);
+    } catch (error) {
+      toast.error(`Failed to import servers: ${error}`);
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={onOpenChange}>
+      <DialogContent className="max-w-2xl">
+        <DialogHeader>
+          <DialogTitle>Import from SSH config</DialogTitle>
+          <DialogDescription>
+            Hosts from ~/.ssh/config that are not added yet. Identity files are linked to your SSH keys.
+          </DialogDescription>
+        </DialogHeader>
+
+        <div className="max-h-96 overflow-y-auto space-y-2">
+          {loading && <p className="text-sm text-muted-foreground">Reading SSH config...</p>}
+          {!loading && hosts.length === 0 && (
+            <p className="text-sm text-muted-foreground">No new hosts found in ~/.ssh/config.</p>
+          )}
+          {hosts.map((host) => (
+            <label key={host.alias} className="flex items-start gap-3 rounded border p-3">
+              <Checkbox
+                checked={selected.includes(host.alias)}
+                onCheckedChange={(checked) => toggle(host.alias, checked === true)}
+              />
+              <div className="text-sm">
+                <div className="font-medium">{host.alias}</div>
+                <div className="text-muted-foreground">
+                  {host.server.username}@{host.server.hostname}:{host.server.port}
+                  {host.proxy_jump && ` via ${host.proxy_jump}`}
+                </div>
+                <div className="text-muted-foreground">
+                  {host.identity_file ? `Key: ${host.identity_file}` : 'Authenticates with ssh-agent'}
+                </div>
+              </div>
+            </label>
+          ))}
+        </div>
+
+        <DialogFooter>
+          <Button variant="outline" onClick={() => onOpenChange(false)}>
+            Cancel
+          </Button>
+          <Button onClick={handleImport} disabled={importing || selected.length === 0}>
+            {importing ? 'Importing...' : `Import ${selected.length}`}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
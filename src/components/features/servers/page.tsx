@@ -30,6 +30,8 @@ import {
 import { MoreHorizontal, EyeIcon, EyeOffIcon } from "lucide-react";
 import {toast} from "sonner";
 import { EditServerDialog } from '@/components/features/server/edit-server-dialog';
+import { ImportSshConfigDialog } from './import-ssh-config-dialog';
+import { fbytes } from '@/lib/utils';
 
 export default function ServersPage(){
     const { servers, loading, error, visibleIpMap, toggleIpVisibility, fetchServers } = useGlobalState();
@@ -40,6 +42,7 @@ export default function ServersPage(){
     const [deleteError, setDeleteError] = useState<string | null>(null);
     const [editDialogOpen, setEditDialogOpen] = useState(false);
     const [serverToEdit, setServerToEdit] = useState<number | null>(null);
+    const [importDialogOpen, setImportDialogOpen] = useState(false);
 
     const handleManageSettings = (serverId: number) => {
         setServerToEdit(serverId);
@@ -82,10 +85,20 @@ export default function ServersPage(){
         );
     }
 
+    const importDialog = (
+        <ImportSshConfigDialog
+            open={importDialogOpen}
+            onOpenChange={setImportDialogOpen}
+            onImported={fetchServers}
+        />
+    );
+
     if (servers.length === 0) {
         return (
             <div className="flex flex-col justify-center items-center h-full gap-4">
                 <p>No servers found. Add a server to get started.</p>
+                <Button variant="outline" onClick={() => setImportDialogOpen(true)}>Import from SSH config</Button>
+                {importDialog}
             </div>
         );
     }
@@ -94,6 +107,9 @@ export default function ServersPage(){
 
     return (
         <div className="container mx-auto py-4">
+            <div className="flex justify-end mb-4">
+                <Button variant="outline" onClick={() => setImportDialogOpen(true)}>Import from SSH config</Button>
+            </div>
             <Table>
                 <TableHeader className="bg-accent text-accent-foreground">
                     <TableRow>
@@ -109,7 +125,15 @@ export default function ServersPage(){
                 <TableBody>
                     {servers.map((server) => (
                         <TableRow key={server.id}>
-                            <TableCell className="font-medium">{server.name}</TableCell>
+                            <TableCell className="font-medium">
+                                {server.name}
+                                {server.os && (
+                                    <div className="text-xs font-normal text-muted-foreground">
+                                        {server.os.name}
+                                        {server.facts && ` · ${server.facts.cpus} CPU · ${fbytes(server.facts.memory)}`}
+                                    </div>
+                                )}
+                            </TableCell>
                             <TableCell>{server.hostname}</TableCell>
                             <TableCell>
                                 <div className="flex items-center gap-2">
@@ -215,6 +239,8 @@ export default function ServersPage(){
                 </DialogContent>
             </Dialog>
 
+            {importDialog}
+
             {serverToEdit !== null && (
                 <EditServerDialog
                     open={editDialogOpen}
@@ -1,4 +1,5 @@
 import { Label } from "@/components/ui/label"
+import { Input } from "@/components/ui/input"
 import {
     Select,
     SelectContent,
@@ -21,11 +22,36 @@ import {
 import {Button} from "@/components/ui/button";
 import {invoke} from "@tauri-apps/api/core";
 import { useSettings } from "@/components/providers/settings"
-import { useState } from "react"
+import { useEffect, useState } from "react"
 
 export default function Advanced() {
-    const { settings, updateSettings, isLoading, scategories } = useSettings();
+    const { settings, updateSetting, isLoading, scategories } = useSettings();
     const [showSettings, setShowSettings] = useState(false);
+    const [proxyPassword, setProxyPassword] = useState('');
+    const [proxyPasswordSaved, setProxyPasswordSaved] = useState(false);
+    const [recipesDirectory, setRecipesDirectory] = useState('');
+
+    // The default directory, shown until another is set
+    useEffect(() => {
+        if (settings['advanced/recipes_directory']) return;
+
+        invoke<string>('get_recipes_directory')
+            .then(setRecipesDirectory)
+            .catch((error) => console.error('Failed to get recipes directory:', error));
+    }, [settings['advanced/recipes_directory']]);
+
+    const proxyType = settings['advanced/proxy_type'] || 'none';
+    const hasProxyPassword = proxyPasswordSaved || !!settings['advanced/proxy_password'];
+
+    const saveProxyPassword = async () => {
+        if (!proxyPassword) {
+            return;
+        }
+
+        await invoke('update_secret_setting', { key: 'advanced/proxy_password', value: proxyPassword });
+        setProxyPassword('');
+        setProxyPasswordSaved(true);
+    };
 
     return (
         <div className="space-y-6 mt-6">
@@ -47,17 +73,92 @@ export default function Advanced() {
                     </SelectContent>
                 </Select>
             </div>
-            <div>
-                <Label>Proxy Configuration</Label>
-                Coming Soon!
+            <div className="space-y-4">
+                <div>
+                    <Label id="proxy-type">Proxy Configuration</Label>
+                    <Select
+                        aria-labeledby="proxy-type"
+                        value={proxyType}
+                        onValueChange={(value) => updateSetting('advanced/proxy_type', value)}
+                    >
+                        <SelectTrigger className="w-[180px] bg-white mt-2">
+                            <SelectValue placeholder="Select a proxy" />
+                        </SelectTrigger>
+                        <SelectContent>
+                            <SelectItem value="none">No proxy</SelectItem>
+                            <SelectItem value="socks5">SOCKS5</SelectItem>
+                            <SelectItem value="http">HTTP CONNECT</SelectItem>
+                        </SelectContent>
+                    </Select>
+                    <p className="text-sm text-muted-foreground mt-2">
+                        Servers can override this with their own proxy, or connect directly, in the server form.
+                    </p>
+                </div>
+                {proxyType !== 'none' && (
+                    <div className="grid grid-cols-2 gap-4">
+                        <div>
+                            <Label htmlFor="proxy-host">Proxy Host</Label>
+                            <Input
+                                id="proxy-host"
+                                className="bg-white mt-2"
+                                value={settings['advanced/proxy_host'] || ''}
+                                onChange={(e) => updateSetting('advanced/proxy_host', e.target.value)}
+                            />
+                        </div>
+                        <div>
+                            <Label htmlFor="proxy-port">Proxy Port</Label>
+                            <Input
+                                id="proxy-port"
+                                type="number"
+                                className="bg-white mt-2"
+                                min={1}
+                                max={65535}
+                                placeholder={proxyType === 'socks5' ? '1080' : '8080'}
+                                value={settings['advanced/proxy_port'] || ''}
+                                onChange={(e) => updateSetting('advanced/proxy_port', e.target.value)}
+                            />
+                        </div>
+                        <div>
+                            <Label htmlFor="proxy-username">Proxy Username</Label>
+                            <Input
+                                id="proxy-username"
+                                className="bg-white mt-2"
+                                placeholder="optional"
+                                value={settings['advanced/proxy_username'] || ''}
+                                onChange={(e) => updateSetting('advanced/proxy_username', e.target.value)}
+                            />
+                        </div>
+                        <div>
+                            <Label htmlFor="proxy-password">Proxy Password (encrypted)</Label>
+                            <Input
+                                id="proxy-password"
+                                type="password"
+                                className="bg-white mt-2"
+                                placeholder={hasProxyPassword ? 'Saved, type to replace' : 'optional'}
+                                value={proxyPassword}
+                                onChange={(e) => setProxyPassword(e.target.value)}
+                                onBlur={saveProxyPassword}
+                            />
+                        </div>
+                    </div>
+                )}
             </div>
             <div>
                 <Label>Custom Terminal Configuration</Label>
                 Coming Soon!
             </div>
             <div>
-                <Label>Custom Script Paths</Label>
-                Coming Soon!
+                <Label htmlFor="recipes-directory">Recipes Directory</Label>
+                <Input
+                    id="recipes-directory"
+                    className="bg-white mt-2"
+                    placeholder={recipesDirectory}
+                    value={settings['advanced/recipes_directory'] || ''}
+                    onChange={(e) => updateSetting('advanced/recipes_directory', e.target.value)}
+                />
+                <p className="text-sm text-muted-foreground mt-2">
+                    Provisioning recipes (.toml) are loaded from here. A setup.toml or application.toml replaces the bundled recipe of that name.
+                </p>
             </div>
 
             <div>
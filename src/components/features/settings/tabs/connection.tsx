@@ -12,7 +12,7 @@ import { Button } from "@/components/ui/button"
 import { useState } from "react"
 
 export default function Connection() {
-    const { settings, updateSettings, isLoading, scategories } = useSettings();
+    const { settings, updateSetting, isLoading, scategories } = useSettings();
     const [showSettings, setShowSettings] = useState(false);
 
     return (
@@ -23,21 +23,24 @@ export default function Connection() {
                     id="ssh-connection-timeout" 
                     type="number" 
                     className="bg-white mt-2" 
-                    disabled 
+                    min={1}
                     value={settings['connection/ssh_timeout'] || '30'}
+                    onChange={(e) => updateSetting('connection/ssh_timeout', e.target.value)}
                 />
             </div>
             <div>
                 <Label id="connection-retry-attempts">Connection Retry Attempts</Label>
                 <Select 
                     aria-labeledby="connection-retry-attempts" 
-                    disabled
                     value={settings['connection/retry_attempts'] || '5'}
+                    onValueChange={(value) => updateSetting('connection/retry_attempts', value)}
                 >
                     <SelectTrigger className="w-[180px] bg-white mt-2">
                         <SelectValue placeholder="Select a value" />
                     </SelectTrigger>
                     <SelectContent>
+                        <SelectItem value="1">no retries</SelectItem>
+                        <SelectItem value="3">max 3 attempts</SelectItem>
                         <SelectItem value="5">max 5 attempts</SelectItem>
                         <SelectItem value="10">max 10 attempts</SelectItem>
                     </SelectContent>
@@ -49,8 +52,9 @@ export default function Connection() {
                     id="keep-alive-interval" 
                     type="number" 
                     className="bg-white mt-2" 
-                    disabled 
+                    min={0}
                     value={settings['connection/keep_alive_interval'] || '60'}
+                    onChange={(e) => updateSetting('connection/keep_alive_interval', e.target.value)}
                 />
             </div>
 
@@ -12,7 +12,7 @@ import { Button } from "@/components/ui/button"
 import { useState } from "react"
 
 export default function Security() {
-    const { settings, updateSettings, isLoading, scategories } = useSettings();
+    const { settings, updateSetting, isLoading, scategories } = useSettings();
     const [showSettings, setShowSettings] = useState(false);
 
     return (
@@ -22,8 +22,8 @@ export default function Security() {
                 <Checkbox 
                     id="store-ssh-key-passwords" 
                     className="bg-white mt-2" 
-                    disabled 
-                    checked={settings['security/store_ssh_key_passwords'] === 'true'}
+                    checked={settings['security/store_ssh_key_passwords'] !== 'false'}
+                    onCheckedChange={(checked) => updateSetting('security/store_ssh_key_passwords', checked === true ? 'true' : 'false')}
                 />
             </div>
             <div>
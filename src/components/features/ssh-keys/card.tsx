@@ -19,6 +19,21 @@ type Props = {
     update_keys: () => void;
 };
 
+function certificateBadge(sshKey: SshKey) {
+    switch (sshKey.certificate?.status) {
+        case 'valid':
+            return <Badge variant="outline">Valid</Badge>;
+        case 'expiring':
+            return <Badge variant="secondary">Expires soon</Badge>;
+        case 'expired':
+            return <Badge variant="destructive">Expired</Badge>;
+        case 'not_yet_valid':
+            return <Badge variant="secondary">Not yet valid</Badge>;
+        default:
+            return <Badge variant="destructive">Unreadable</Badge>;
+    }
+}
+
 export default function SshKeyCard({ sshKey, update_keys }: Props) {
     const [isDeleteModalOpen, setIsDeleteModalOpen] = useState(false);
 
@@ -67,6 +82,26 @@ export default function SshKeyCard({ sshKey, update_keys }: Props) {
                 <CardContent>
                     <p className="text-sm text-muted-foreground mb-2">Path:</p>
                     <p className="text-sm break-all bg-muted p-2 rounded-md">{sshKey.path}</p>
+                    {sshKey.certificate_path && (
+                        <div className="mt-4 space-y-2">
+                            <div className="flex items-center justify-between gap-2">
+                                <p className="text-sm text-muted-foreground">Certificate:</p>
+                                {certificateBadge(sshKey)}
+                            </div>
+                            <p className="text-sm break-all bg-muted p-2 rounded-md">{sshKey.certificate_path}</p>
+                            {sshKey.certificate && (
+                                <div className="text-sm text-muted-foreground space-y-1">
+                                    <p>Key ID: {sshKey.certificate.key_id}</p>
+                                    <p>Principals: {sshKey.certificate.principals.length > 0 ? sshKey.certificate.principals.join(', ') : 'any'}</p>
+                                    <p>
+                                        Valid: {sshKey.certificate.valid_after ? fdate(sshKey.certificate.valid_after) : 'always'}
+                                        {' – '}
+                                        {sshKey.certificate.valid_before ? fdate(sshKey.certificate.valid_before) : 'forever'}
+                                    </p>
+                                </div>
+                            )}
+                        </div>
+                    )}
                 </CardContent>
                 <CardFooter className="flex justify-between">
                     <Button
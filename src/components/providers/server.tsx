@@ -1,5 +1,7 @@
 import React, { createContext, useContext, useState, useEffect } from 'react';
-import {Server, ServerConnectionStatus} from "@/types";
+import { listen } from '@tauri-apps/api/event';
+import { toast } from 'sonner';
+import {ConnectionEvent, Server, ServerConnectionStatus} from "@/types";
 import { usePageContext } from './page';
 
 interface ServerContextType {
@@ -16,7 +18,8 @@ const ServerContext = createContext<ServerContextType | undefined>(undefined);
 export const ConnectionStatuses: { [T in ServerConnectionStatus]: T} = {
     connected: 'connected',
     disconnected: "disconnected",
-    connecting: 'connecting'
+    connecting: 'connecting',
+    reconnecting: 'reconnecting'
 };
 
 export function ServerProvider({ children }: { children: React.ReactNode }) {
@@ -31,6 +34,42 @@ export function ServerProvider({ children }: { children: React.ReactNode }) {
     }
   }, [connectedServer]);
 
+  // The backend reports lost sessions and reconnects them on its own
+  useEffect(() => {
+    if (!connectedServer) return;
+
+    const unlisten = listen<ConnectionEvent>('connection-state', (event) => {
+      const { server_id, state, attempt, error } = event.payload;
+      if (server_id !== connectedServer.id) return;
+
+      if (state === 'connected') {
+        setConnectionStatus(ConnectionStatuses.connected);
+        setConnectionError(null);
+        return;
+      }
+
+      if (!error) {
+        if (state === 'reconnecting' && attempt === 1) toast.info(`Reconnecting to ${connectedServer.name}...`);
+        if (state === 'reconnecting') setConnectionStatus(ConnectionStatuses.reconnecting);
+        return;
+      }
+
+      setConnectionError(error);
+
+      // A lost session is retried until the backend gives up
+      if (state === 'disconnected' && error.startsWith('Gave up')) {
+        setConnectionStatus(ConnectionStatuses.disconnected);
+        toast.error(`Lost connection to ${connectedServer.name}: ${error}`);
+      } else {
+        setConnectionStatus(ConnectionStatuses.reconnecting);
+      }
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [connectedServer]);
+
   return (
     <ServerContext.Provider 
       value={{
@@ -22,3 +22,16 @@ export function fdate(timestamp: string): string {
   return date.toLocaleString('en-US', options);
 }
 
+
+export function fbytes(bytes: number): string {
+  const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+  let value = bytes;
+  let unit = 0;
+
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+
+  return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
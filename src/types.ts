@@ -6,6 +6,11 @@ export type Server = {
     port: number;
     username: string;
     ssh_key_id?: number;
+    auth_method: 'key' | 'agent' | 'password';
+    sudo_mode: 'passwordless' | 'password' | 'root';
+    jump_host_id?: number;
+    os?: OsRelease | null;
+    facts?: ServerFacts | null;
     notes?: string;
     settings: string;
     created_at: string;
@@ -14,11 +19,68 @@ export type Server = {
 
 export type Servers = Server[];
 
+export type OsRelease = {
+    id: string;
+    id_like: string[];
+    version_id: string;
+    name: string;
+};
+
+export type Disk = {
+    device: string;
+    filesystem: string;
+    mount_point: string;
+    size: number;
+    used: number;
+};
+
+export type ServerFacts = {
+    kernel: string;
+    arch: string;
+    cpus: number;
+    memory: number;
+    disks: Disk[];
+    public_ips: string[];
+    web_servers: string[];
+    database_servers: string[];
+    selinux: string | null;
+    gathered_at: string;
+};
+
+export type Package = {
+    name: string;
+    version: string;
+};
+
+export type ServiceAction = 'start' | 'stop' | 'restart' | 'reload' | 'enable' | 'disable';
+
+export type ServiceStatus = {
+    name: string;
+    state: string;
+    active: boolean;
+    enabled: boolean;
+};
+
+export type CertificateStatus = 'valid' | 'expiring' | 'expired' | 'not_yet_valid';
+
+export type Certificate = {
+    key_id: string;
+    serial: number;
+    cert_type: 'user' | 'host';
+    principals: string[];
+    valid_after: string | null;
+    valid_before: string | null;
+    status: CertificateStatus;
+};
+
 export type SshKey = {
     id?: number;
     name: string;
     path: string;
+    has_passphrase: boolean;
     is_default: boolean;
+    certificate_path: string | null;
+    certificate: Certificate | null;
     created_at: string;
     updated_at: string;
 }
@@ -33,4 +95,152 @@ export type Setting = {
 
 export type Settings = Setting[];
 
-export type ServerConnectionStatus = 'connected' | 'disconnected' | 'connecting';
+export type ServerConnectionStatus = 'connected' | 'disconnected' | 'connecting' | 'reconnecting';
+
+export type ConnectionEvent = {
+    server_id: number;
+    state: 'connected' | 'disconnected' | 'reconnecting';
+    attempt: number | null;
+    error: string | null;
+};
+
+export type JobOutput = {
+    job_id: string;
+    server_id: number;
+    stream: 'stdout' | 'stderr' | 'info';
+    data: string;
+};
+
+export type JobFinished = {
+    job_id: string;
+    server_id: number;
+    success: boolean;
+    error?: string;
+};
+
+export type ProvisionStep = {
+    id: string;
+    name: string;
+    optional: boolean;
+    requires?: string | null;
+};
+
+export type RecipeInfo = {
+    name: string;
+    description: string;
+    path?: string | null;
+    steps: ProvisionStep[];
+};
+
+export type StepState = 'checking' | 'applying' | 'done' | 'satisfied' | 'skipped' | 'failed';
+
+export type ProvisionStepEvent = {
+    job_id: string;
+    server_id: number;
+    plan: string;
+    step: string;
+    name: string;
+    state: StepState;
+    error?: string | null;
+};
+
+export type StepProgress = {
+    step: string;
+    state: StepState;
+    error?: string | null;
+    updated_at: string;
+};
+
+export type TerminalInfo = {
+    id: string;
+    server_id: number;
+    cols: number;
+    rows: number;
+    opened_at: string;
+};
+
+export type TerminalOutput = {
+    terminal_id: string;
+    server_id: number;
+    data: string;
+};
+
+export type TerminalClosed = {
+    terminal_id: string;
+    server_id: number;
+    exit_status?: number;
+};
+
+export type RemoteFile = {
+    name: string;
+    path: string;
+    is_dir: boolean;
+    is_symlink: boolean;
+    size: number;
+    mode: number;
+    permissions: string;
+    uid?: number;
+    gid?: number;
+    owner?: string;
+    group?: string;
+    modified_at?: string;
+};
+
+export type TransferProgress = {
+    job_id: string;
+    server_id: number;
+    direction: 'upload' | 'download';
+    local_path: string;
+    remote_path: string;
+    transferred: number;
+    total: number;
+};
+
+export type TunnelStatus = {
+    id: number;
+    server_id: number;
+    local_port: number;
+    remote_host: string;
+    remote_port: number;
+    auto_restore: boolean;
+    created_at: string;
+    running: boolean;
+    active_connections: number;
+    total_connections: number;
+    error?: string;
+};
+
+export type AuthPromptField = {
+    text: string;
+    echo: boolean;
+};
+
+export type AuthPrompt = {
+    request_id: string;
+    server_id: number;
+    server_name: string;
+    username: string;
+    instructions: string;
+    prompts: AuthPromptField[];
+};
+
+export type CommandResult = {
+    command: string;
+    stdout: string;
+    stderr: string;
+    exit_code: number | null;
+    duration: number;
+};
+
+export type CommandError =
+    | { kind: 'failed'; message: string }
+    | { kind: 'exited' | 'timed_out' | 'cancelled'; message: string; result: CommandResult }
+    | { kind: 'sudo_password_required'; message: string; server_id: number }
+    | { kind: 'disconnected'; message: string; server_id: number; result: CommandResult | null };
+
+export type SshConfigHost = {
+    alias: string;
+    server: Server;
+    identity_file: string | null;
+    proxy_jump: string | null;
+};